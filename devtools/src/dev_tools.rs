@@ -8,10 +8,10 @@ use leptos_query::{
 use std::{collections::HashMap, time::Duration};
 
 use crate::component::*;
-use crate::timeout::{time_until_stale, use_timeout};
+use crate::DevtoolsFilter;
 
 #[component]
-pub(crate) fn InnerDevtools() -> impl IntoView {
+pub(crate) fn InnerDevtools(filter: DevtoolsFilter) -> impl IntoView {
     let mounted = create_rw_signal(false);
 
     create_effect(move |_| {
@@ -20,7 +20,7 @@ pub(crate) fn InnerDevtools() -> impl IntoView {
 
     move || {
         if mounted.get() {
-            view! { <DevtoolsClient/> }
+            view! { <DevtoolsClient filter=filter.clone()/> }
         } else {
             ().into_view()
         }
@@ -28,9 +28,9 @@ pub(crate) fn InnerDevtools() -> impl IntoView {
 }
 
 #[component]
-fn DevtoolsClient() -> impl IntoView {
+fn DevtoolsClient(filter: DevtoolsFilter) -> impl IntoView {
     let client = leptos_query::use_query_client();
-    let state = DevtoolsContext::new();
+    let state = DevtoolsContext::new(filter);
     client.register_cache_observer(state.clone());
     provide_context(state);
 
@@ -45,7 +45,7 @@ fn DevtoolsClient() -> impl IntoView {
             {
                 let cache = context.query_state.get();
 
-                if cache.get(&key).is_none() {
+                if !cache.contains_key(&key) {
                     context.selected_query.set(None);
                 }
             }
@@ -65,9 +65,11 @@ fn DevtoolsClient() -> impl IntoView {
 #[derive(Clone)]
 struct DevtoolsContext {
     owner: Owner,
+    event_filter: DevtoolsFilter,
     query_state: RwSignal<HashMap<QueryCacheKey, QueryCacheEntry>>,
     open: RwSignal<bool>,
     filter: RwSignal<String>,
+    state_filter: RwSignal<Option<StateFilter>>,
     sort: RwSignal<SortOption>,
     order_asc: RwSignal<bool>,
     selected_query: RwSignal<Option<QueryCacheEntry>>,
@@ -95,6 +97,57 @@ impl SortOption {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StateFilter {
+    Fetching,
+    Fresh,
+    Stale,
+    Error,
+}
+
+impl StateFilter {
+    const ALL: [StateFilter; 4] = [
+        StateFilter::Fetching,
+        StateFilter::Fresh,
+        StateFilter::Stale,
+        StateFilter::Error,
+    ];
+
+    fn as_str(&self) -> &str {
+        match self {
+            StateFilter::Fetching => "Fetching",
+            StateFilter::Fresh => "Fresh",
+            StateFilter::Stale => "Stale",
+            StateFilter::Error => "Error",
+        }
+    }
+
+    // Same colors as the state label of a row.
+    fn color(&self) -> ColorOption {
+        match self {
+            StateFilter::Fetching => ColorOption::Blue,
+            StateFilter::Fresh => ColorOption::Green,
+            StateFilter::Stale => ColorOption::Yellow,
+            StateFilter::Error => ColorOption::Red,
+        }
+    }
+
+    fn matches(&self, entry: &QueryCacheEntry) -> bool {
+        let is_stale = entry.is_stale.get();
+        entry.state.with(|state| match self {
+            StateFilter::Fetching => {
+                matches!(state, QueryState::Loading | QueryState::Fetching(_))
+            }
+            StateFilter::Fresh => matches!(state, QueryState::Loaded(_)) && !is_stale,
+            StateFilter::Stale => {
+                matches!(state, QueryState::Loaded(_)) && is_stale
+                    || matches!(state, QueryState::Invalid(_))
+            }
+            StateFilter::Error => matches!(state, QueryState::Error { .. }),
+        })
+    }
+}
+
 #[derive(Clone)]
 struct QueryCacheEntry {
     key: QueryCacheKey,
@@ -102,8 +155,87 @@ struct QueryCacheEntry {
     observer_count: RwSignal<usize>,
     gc_time: RwSignal<SettingTime>,
     stale_time: RwSignal<SettingTime>,
+    refetch_interval: RwSignal<SettingTime>,
     is_stale: Signal<bool>,
+    // Errors seen during the session, e.g. to spot flaky endpoints.
+    error_count: RwSignal<usize>,
+    errored_at: RwSignal<Option<Instant>>,
     mark_invalid: std::rc::Rc<dyn Fn() -> bool>,
+    execute: std::rc::Rc<dyn Fn()>,
+    evict: std::rc::Rc<dyn Fn() -> bool>,
+    evicts_at: std::rc::Rc<dyn Fn() -> Option<Instant>>,
+    observer_options: std::rc::Rc<dyn Fn() -> Vec<QueryOptions<String>>>,
+}
+
+impl QueryCacheEntry {
+    // Errors are compared by identity, so every failed fetch is counted once.
+    fn set_state(&self, state: QueryState<String>) {
+        let is_new_error = match state.error() {
+            Some(error) => self.state.with_untracked(|s| s.error() != Some(error)),
+            None => false,
+        };
+        if is_new_error {
+            self.error_count.update(|count| *count += 1);
+            self.errored_at.set(Some(Instant::now()));
+        }
+        self.state.set(state);
+    }
+
+    // Merge the options of the active observers the same way the query does.
+    fn update_effective_options(&self) {
+        let options = (self.observer_options)();
+
+        let stale_time = options
+            .iter()
+            .filter(|o| !o.isolated_stale_time)
+            .fold(SettingTime::None, |acc, o| {
+                acc.min(SettingTime::from_option(o.stale_time))
+            });
+        let refetch_interval = options.iter().fold(SettingTime::None, |acc, o| {
+            acc.min(SettingTime::from_option(o.refetch_interval))
+        });
+
+        self.stale_time.set(stale_time);
+        self.refetch_interval.set(refetch_interval);
+    }
+
+    // Time left until the query is garbage collected, while it has no observers.
+    // Ticks every second, because eviction happens in the background without a cache event.
+    fn use_gc_countdown(&self) -> Signal<Option<String>> {
+        let state = self.state;
+        let observer_count = self.observer_count;
+        let evicts_at = self.evicts_at.clone();
+        let (tick, set_tick) = create_signal(());
+
+        use_timeout(move || {
+            tick.track();
+            if observer_count.get() == 0 {
+                set_timeout_with_handle(move || set_tick.set(()), Duration::from_secs(1)).ok()
+            } else {
+                None
+            }
+        });
+
+        Signal::derive(move || {
+            if observer_count.get() > 0 {
+                return None;
+            }
+            tick.track();
+            state.track();
+            let countdown = match evicts_at() {
+                Some(evicts_at) => {
+                    let secs = evicts_at.0.saturating_sub(Instant::now().0).as_secs();
+                    if secs >= 60 {
+                        format!("{}m {}s", secs / 60, secs % 60)
+                    } else {
+                        format!("{}s", secs)
+                    }
+                }
+                None => "never".to_string(),
+            };
+            Some(countdown)
+        })
+    }
 }
 
 fn use_devtools_context() -> DevtoolsContext {
@@ -111,12 +243,14 @@ fn use_devtools_context() -> DevtoolsContext {
 }
 
 impl DevtoolsContext {
-    fn new() -> Self {
+    fn new(event_filter: DevtoolsFilter) -> Self {
         DevtoolsContext {
             owner: Owner::current().expect("Owner to be present"),
+            event_filter,
             query_state: create_rw_signal(HashMap::new()),
             open: create_rw_signal(false),
             filter: create_rw_signal("".to_string()),
+            state_filter: create_rw_signal(None),
             sort: create_rw_signal(SortOption::Time),
             order_asc: create_rw_signal(false),
             selected_query: create_rw_signal(None),
@@ -189,15 +323,31 @@ impl std::fmt::Display for SettingTime {
 
 impl CacheObserver for DevtoolsContext {
     fn process_cache_event(&self, event: CacheEvent) {
+        // Filtered queries never reach the reactive graph.
+        let key = match &event {
+            CacheEvent::Created(CreatedQuery { key, .. }) => key,
+            CacheEvent::Updated(SerializedQuery { key, .. }) => key,
+            CacheEvent::ObserverAdded(ObserverAdded { key, .. }) => key,
+            CacheEvent::Removed(key) | CacheEvent::ObserverRemoved(key) => key,
+        };
+        if !self.event_filter.matches(&key.0) {
+            return;
+        }
+
         match event {
             CacheEvent::Created(CreatedQuery {
                 key,
                 state,
                 mark_invalid,
+                execute,
+                evict,
+                evicts_at,
+                observer_options,
             }) => {
                 // Need to create signals with root owner, or else they will be disposed of.
                 let entry = with_owner(self.owner, || {
                     let stale_time = create_rw_signal(SettingTime::None);
+                    let is_error = state.error().is_some();
                     let state = create_rw_signal(state);
 
                     let is_stale = {
@@ -235,9 +385,16 @@ impl CacheObserver for DevtoolsContext {
                         state,
                         stale_time,
                         gc_time: create_rw_signal(SettingTime::None),
+                        refetch_interval: create_rw_signal(SettingTime::None),
                         observer_count: create_rw_signal(0),
                         is_stale,
+                        error_count: create_rw_signal(usize::from(is_error)),
+                        errored_at: create_rw_signal(is_error.then(Instant::now)),
                         mark_invalid,
+                        execute,
+                        evict,
+                        evicts_at,
+                        observer_options,
                     }
                 });
 
@@ -252,20 +409,17 @@ impl CacheObserver for DevtoolsContext {
             CacheEvent::Updated(SerializedQuery { key, state }) => {
                 let map = self.query_state.get_untracked();
                 if let Some(entry) = map.get(&key) {
-                    entry.state.set(state);
+                    entry.set_state(state);
                 }
                 self.query_state.set(map);
             }
             CacheEvent::ObserverAdded(observer) => {
                 let ObserverAdded { key, options } = observer;
-                let QueryOptions {
-                    stale_time,
-                    gc_time,
-                    ..
-                } = options;
+                let QueryOptions { gc_time, .. } = options;
                 self.query_state.update(|map| {
                     if let Some(entry) = map.get_mut(&key) {
                         entry.observer_count.update(|c| *c += 1);
+                        // The garbage collector keeps the longest gc time it has seen.
                         {
                             let current_gc = entry.gc_time.get_untracked();
                             let setting_gc = SettingTime::from_option(gc_time);
@@ -273,12 +427,7 @@ impl CacheObserver for DevtoolsContext {
                             let new_gc = current_gc.max(setting_gc);
                             entry.gc_time.set(new_gc);
                         }
-                        {
-                            let current_stale = entry.stale_time.get_untracked();
-                            let setting_stale = SettingTime::from_option(stale_time);
-                            let new_stale = current_stale.min(setting_stale);
-                            entry.stale_time.set(new_stale);
-                        }
+                        entry.update_effective_options();
                     }
                 });
             }
@@ -290,6 +439,7 @@ impl CacheObserver for DevtoolsContext {
                                 *c -= 1
                             }
                         });
+                        entry.update_effective_options();
                     }
                 });
             }
@@ -304,6 +454,7 @@ fn Devtools() -> impl IntoView {
         query_state,
         selected_query,
         filter,
+        state_filter,
         sort,
         order_asc,
         ..
@@ -311,11 +462,14 @@ fn Devtools() -> impl IntoView {
 
     let query_state = Signal::derive(move || {
         let filter = filter.get().to_ascii_lowercase();
+        let state_filter = state_filter.get();
 
         // Filtered
+        #[allow(clippy::unnecessary_map_or)]
         let mut query_state = query_state.with(|map| {
             map.iter()
                 .filter(|(key, _)| key.0.to_ascii_lowercase().contains(&filter))
+                .filter(|(_, q)| state_filter.map_or(true, |f| f.matches(q)))
                 .map(|(_, q)| q)
                 .cloned()
                 .collect::<Vec<_>>()
@@ -440,6 +594,7 @@ fn Devtools() -> impl IntoView {
                                 <div class="lq-py-1 lq-px-2 lq-border-lq-border lq-border-b lq-flex lq-items-center lq-w-full lq-justify-between lq-max-w-full lq-overflow-x-auto lq-gap-2 lq-no-scrollbar">
                                     <div class="lq-flex lq-items-center lq-gap-2">
                                         <SearchInput/>
+                                        <SetStateFilter/>
                                         <SetSort/>
                                         <SetSortOrder/>
                                     </div>
@@ -604,6 +759,31 @@ fn SearchInput() -> impl IntoView {
     }
 }
 
+#[component]
+fn SetStateFilter() -> impl IntoView {
+    let DevtoolsContext { state_filter, .. } = use_devtools_context();
+
+    StateFilter::ALL
+        .into_iter()
+        .map(|option| {
+            let active = move || state_filter.get() == Some(option);
+            view! {
+                <button
+                    type="button"
+                    title=format!("Only show {} queries", option.as_str().to_ascii_lowercase())
+                    aria-pressed=move || active().to_string()
+                    style:opacity=move || if active() { "1" } else { "0.5" }
+                    on:click=move |_| {
+                        state_filter.update(|f| *f = if *f == Some(option) { None } else { Some(option) })
+                    }
+                >
+                    <DotBadge color=option.color()>{option.as_str().to_string()}</DotBadge>
+                </button>
+            }
+        })
+        .collect_view()
+}
+
 #[component]
 fn SetSort() -> impl IntoView {
     let DevtoolsContext { sort, .. } = use_devtools_context();
@@ -719,8 +899,12 @@ fn QueryRow(entry: QueryCacheEntry) -> impl IntoView {
         state,
         observer_count,
         is_stale,
+        mark_invalid,
+        execute,
+        evict,
         ..
     } = entry.clone();
+    let gc_countdown = entry.use_gc_countdown();
     let observer = move || {
         let count = observer_count.get();
         if count == 0 {
@@ -743,7 +927,7 @@ fn QueryRow(entry: QueryCacheEntry) -> impl IntoView {
             on:click={
                 let key = key.clone();
                 move |_| {
-                    if selected_query.get_untracked().map_or(false, |q| q.key == key) {
+                    if selected_query.get_untracked().is_some_and(|q| q.key == key) {
                         selected_query.set(None);
                     } else {
                         selected_query.set(Some(entry.clone()))
@@ -757,6 +941,49 @@ fn QueryRow(entry: QueryCacheEntry) -> impl IntoView {
                 <RowStateLabel state=state.into() is_stale/>
             </span>
             <span class="lq-text-sm">{key.0}</span>
+            {move || {
+                gc_countdown
+                    .get()
+                    .map(|countdown| {
+                        view! {
+                            <span class="lq-text-xs lq-text-zinc-400">"GC " {countdown}</span>
+                        }
+                    })
+            }}
+
+            // Stop propagation, so the row isn't selected.
+            <span class="lq-ml-auto lq-flex lq-items-center lq-gap-1">
+                <Button
+                    color=ColorOption::Blue
+                    on:click=move |ev| {
+                        ev.stop_propagation();
+                        execute();
+                    }
+                >
+
+                    Refetch
+                </Button>
+                <Button
+                    color=ColorOption::Red
+                    on:click=move |ev| {
+                        ev.stop_propagation();
+                        mark_invalid();
+                    }
+                >
+
+                    Invalidate
+                </Button>
+                <Button
+                    color=ColorOption::Gray
+                    on:click=move |ev| {
+                        ev.stop_propagation();
+                        evict();
+                    }
+                >
+
+                    Remove
+                </Button>
+            </span>
         </li>
     }
 }
@@ -772,6 +999,10 @@ fn RowStateLabel(state: Signal<QueryState<String>>, is_stale: Signal<bool>) -> i
             QueryState::Loaded(_) if is_stale => "Stale",
             QueryState::Loaded(_) => "Loaded",
             QueryState::Invalid(_) => "Invalid",
+            QueryState::Error { error, .. } if error.downcast::<QueryTimeout>().is_some() => {
+                "Timed Out"
+            }
+            QueryState::Error { .. } => "Error",
         }
     });
 
@@ -783,7 +1014,7 @@ fn RowStateLabel(state: Signal<QueryState<String>>, is_stale: Signal<bool>) -> i
             }
             QueryState::Loaded(_) if is_stale => ColorOption::Yellow,
             QueryState::Loaded(_) => ColorOption::Green,
-            QueryState::Invalid(_) => ColorOption::Red,
+            QueryState::Invalid(_) | QueryState::Error { .. } => ColorOption::Red,
         }
     });
 
@@ -796,33 +1027,43 @@ fn RowStateLabel(state: Signal<QueryState<String>>, is_stale: Signal<bool>) -> i
     }
 }
 
+// Time of day in the local timezone of the browser.
+#[cfg(feature = "csr")]
+fn format_time(instant: Instant) -> String {
+    use wasm_bindgen::JsValue;
+    let time = JsValue::from_f64(instant.0.as_millis() as f64);
+    let date = js_sys::Date::new(&time);
+    let hours = date.get_hours();
+    let minutes = date.get_minutes();
+    let seconds = date.get_seconds();
+    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+}
+
+#[cfg(not(feature = "csr"))]
+fn format_time(instant: Instant) -> String {
+    instant.to_string()
+}
+
 #[component]
 fn SelectedQuery(query: QueryCacheEntry) -> impl IntoView {
+    let gc_countdown = query.use_gc_countdown();
     let QueryCacheEntry {
         key: query_key,
         state: query_state,
         is_stale,
         observer_count,
         mark_invalid,
+        execute,
+        evict,
         stale_time,
         gc_time,
+        refetch_interval,
+        error_count,
+        errored_at,
+        ..
     } = query;
 
-    #[cfg(feature = "csr")]
-    let last_update = Signal::derive(move || {
-        use wasm_bindgen::JsValue;
-        query_state.get().updated_at().map(|i| {
-            let time = JsValue::from_f64(i.0.as_millis() as f64);
-            let date = js_sys::Date::new(&time);
-            let hours = date.get_hours();
-            let minutes = date.get_minutes();
-            let seconds = date.get_seconds();
-            format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
-        })
-    });
-
-    #[cfg(not(feature = "csr"))]
-    let last_update = Signal::derive(move || query_state.get().updated_at().map(|i| i.to_string()));
+    let last_update = Signal::derive(move || query_state.get().updated_at().map(format_time));
 
     // Pretty print the JSON
     #[cfg(feature = "csr")]
@@ -854,6 +1095,12 @@ fn SelectedQuery(query: QueryCacheEntry) -> impl IntoView {
 
     let gc_time = Signal::derive(move || gc_time.get().to_string());
 
+    let refetch_interval = Signal::derive(move || refetch_interval.get().to_string());
+
+    let error = Signal::derive(move || query_state.get().error().map(|e| e.to_string()));
+
+    let errored_at = Signal::derive(move || errored_at.get().map(format_time));
+
     view! {
         <div class="lq-w-1/2 lq-overflow-y-scroll lq-max-h-full lq-border-black lq-border-l-4">
             <div class="lq-flex lq-flex-col lq-w-full lq-h-full lq-items-center">
@@ -876,6 +1123,40 @@ fn SelectedQuery(query: QueryCacheEntry) -> impl IntoView {
                             <dt class="lq-text-zinc-100">Last Update</dt>
                             <dd class="lq-text-zinc-200">{last_update}</dd>
                         </div>
+                        {move || {
+                            error
+                                .get()
+                                .map(|error| {
+                                    view! {
+                                        <div class=entry_class>
+                                            <dt class="lq-text-zinc-100">Error</dt>
+                                            <dd>
+                                                <DotBadge color=ColorOption::Red>
+                                                    // Selectable with a single click, to copy it.
+                                                    <span style:user-select="all">{error.clone()}</span>
+                                                </DotBadge>
+                                            </dd>
+                                        </div>
+                                    }
+                                })
+                        }}
+                        {move || {
+                            errored_at
+                                .get()
+                                .map(|errored_at| {
+                                    view! {
+                                        <div class=entry_class>
+                                            <dt class="lq-text-zinc-100">Last Error</dt>
+                                            <dd class="lq-text-zinc-200">{errored_at}</dd>
+                                        </div>
+                                        <div class=entry_class>
+                                            <dt class="lq-text-zinc-100">Errors</dt>
+                                            <dd class="lq-text-zinc-200">{error_count}</dd>
+                                        </div>
+                                    }
+                                })
+                        }}
+
                         <div class=entry_class>
                             <dt class="lq-text-zinc-100">Active Observers</dt>
                             <dd class="lq-text-zinc-200">{observer_count}</dd>
@@ -889,6 +1170,22 @@ fn SelectedQuery(query: QueryCacheEntry) -> impl IntoView {
                             <dt class="lq-text-zinc-100">GC Time</dt>
                             <dd class="lq-text-zinc-200">{gc_time}</dd>
                         </div>
+                        {move || {
+                            gc_countdown
+                                .get()
+                                .map(|countdown| {
+                                    view! {
+                                        <div class=entry_class>
+                                            <dt class="lq-text-zinc-100">GC In</dt>
+                                            <dd class="lq-text-zinc-200">{countdown}</dd>
+                                        </div>
+                                    }
+                                })
+                        }}
+                        <div class=entry_class>
+                            <dt class="lq-text-zinc-100">Refetch Interval</dt>
+                            <dd class="lq-text-zinc-200">{refetch_interval}</dd>
+                        </div>
                     </dl>
                 </div>
                 <div class="lq-w-full">
@@ -896,6 +1193,15 @@ fn SelectedQuery(query: QueryCacheEntry) -> impl IntoView {
                         Query Actions
                     </div>
                     <div class="lq-flex lq-items-center lq-gap-2 lq-p-1">
+                        <Button
+                            color=ColorOption::Blue
+                            on:click=move |_| {
+                                execute();
+                            }
+                        >
+
+                            Refetch
+                        </Button>
                         <Button
                             color=ColorOption::Red
                             on:click=move |_| {
@@ -905,6 +1211,15 @@ fn SelectedQuery(query: QueryCacheEntry) -> impl IntoView {
 
                             Invalidate
                         </Button>
+                        <Button
+                            color=ColorOption::Gray
+                            on:click=move |_| {
+                                evict();
+                            }
+                        >
+
+                            Remove
+                        </Button>
                     </div>
                 </div>
                 <div class="lq-text-sm lq-text-lq-foreground lq-p-1 lq-bg-lq-accent lq-w-full">
@@ -921,3 +1236,82 @@ fn SelectedQuery(query: QueryCacheEntry) -> impl IntoView {
         </div>
     }
 }
+
+#[cfg(all(test, not(feature = "csr")))]
+mod tests {
+    use super::*;
+
+    fn entry(context: &DevtoolsContext, key: &str) -> Option<QueryCacheEntry> {
+        context
+            .query_state
+            .with_untracked(|map| map.get(&QueryCacheKey(key.to_string())).cloned())
+    }
+
+    #[test]
+    fn filtered_events_are_dropped() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let filter = DevtoolsFilter::default()
+            .key_prefix("1")
+            .key_predicate(|key| key != "13");
+        let context = DevtoolsContext::new(filter);
+        client.register_cache_observer(context.clone());
+
+        client.set_query_data::<u32, u32>(1, 10);
+        client.set_query_data::<u32, u32>(2, 20);
+        client.set_query_data::<u32, u32>(12, 120);
+        client.set_query_data::<u32, u32>(13, 130);
+
+        let mut keys = context
+            .query_state
+            .with_untracked(|map| map.keys().map(|key| key.0.clone()).collect::<Vec<_>>());
+        keys.sort();
+        assert_eq!(vec!["1".to_string(), "12".to_string()], keys);
+    }
+
+    #[test]
+    fn state_filter() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let context = DevtoolsContext::new(DevtoolsFilter::default());
+        client.register_cache_observer(context.clone());
+        client.set_query_data::<u32, u32>(1, 10);
+
+        let entry = entry(&context, "1").expect("Entry for the query");
+        assert!(StateFilter::Fresh.matches(&entry));
+        assert!(!StateFilter::Stale.matches(&entry));
+        assert!(!StateFilter::Fetching.matches(&entry));
+        assert!(!StateFilter::Error.matches(&entry));
+
+        assert!(client.invalidate_query::<u32, u32>(1));
+        assert!(!StateFilter::Fresh.matches(&entry));
+        assert!(StateFilter::Stale.matches(&entry));
+    }
+
+    #[test]
+    fn row_actions_apply_to_the_cache() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let context = DevtoolsContext::new(DevtoolsFilter::default());
+        client.register_cache_observer(context.clone());
+        client.set_query_data::<u32, u32>(1, 10);
+        let entry = entry(&context, "1").expect("Entry for the query");
+
+        assert!((entry.mark_invalid)());
+        assert_eq!(1, client.stale_queries_count());
+        assert!(entry
+            .state
+            .with_untracked(|state| matches!(state, QueryState::Invalid(_))));
+
+        assert!((entry.evict)());
+        assert_eq!(None, client.get_query_data::<u32, u32>(&1));
+        assert!(context.query_state.with_untracked(HashMap::is_empty));
+        assert!(!(entry.evict)());
+    }
+}
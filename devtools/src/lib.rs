@@ -68,23 +68,99 @@
 //! ```
 //!
 //! Now you should be able to see the devtools mounted to the bottom right of your app!
+//!
+//! In apps with many queries, pass a `DevtoolsFilter` to only show the queries you care about.
+//!
+//! ```rust
+//! use leptos_query_devtools::{DevtoolsFilter, LeptosQueryDevtools};
+//! use leptos::*;
+//!
+//! #[component]
+//! fn App() -> impl IntoView {
+//!     view!{
+//!         <LeptosQueryDevtools filter=DevtoolsFilter::default().key_prefix("TrackId") />
+//!     }
+//! }
+//! ```
 
 use leptos::*;
+use std::rc::Rc;
 
 #[component]
-pub fn LeptosQueryDevtools() -> impl IntoView {
+pub fn LeptosQueryDevtools(
+    /// Only show the queries that match the filter.
+    #[prop(optional)]
+    filter: DevtoolsFilter,
+) -> impl IntoView {
     #[cfg(any(debug_assertions, feature = "force"))]
     {
         use dev_tools::InnerDevtools;
-        view! { <InnerDevtools/> }
+        view! { <InnerDevtools filter=filter/> }
+    }
+    #[cfg(not(any(debug_assertions, feature = "force")))]
+    {
+        let _ = filter;
     }
 }
 
-#[cfg(any(debug_assertions, feature = "force"))]
-mod dev_tools;
+/// Restricts which queries are shown in the devtools.
+///
+/// Events of filtered queries are dropped before they reach the devtools, which keeps the devtools responsive in apps with thousands of queries.
+///
+/// Example:
+/// ```
+/// use leptos_query_devtools::*;
+/// use leptos::*;
+///
+/// #[component]
+/// fn App() -> impl IntoView {
+///     // Keys are serialized with Debug, so the name of a key type filters by type.
+///     let filter = DevtoolsFilter::default().key_prefix("TrackId");
+///
+///     view!{
+///         <LeptosQueryDevtools filter=filter />
+///     }
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct DevtoolsFilter {
+    key_prefix: Option<String>,
+    #[allow(clippy::type_complexity)]
+    key_predicate: Option<Rc<dyn Fn(&str) -> bool>>,
+}
+
+impl DevtoolsFilter {
+    /// Only show queries whose serialized key starts with `prefix`.
+    pub fn key_prefix(self, prefix: impl Into<String>) -> Self {
+        DevtoolsFilter {
+            key_prefix: Some(prefix.into()),
+            ..self
+        }
+    }
+
+    /// Only show queries whose serialized key matches `predicate`.
+    pub fn key_predicate(self, predicate: impl Fn(&str) -> bool + 'static) -> Self {
+        DevtoolsFilter {
+            key_predicate: Some(Rc::new(predicate)),
+            ..self
+        }
+    }
+
+    #[cfg_attr(not(any(debug_assertions, feature = "force")), allow(dead_code))]
+    #[allow(clippy::unnecessary_map_or)]
+    fn matches(&self, key: &str) -> bool {
+        self.key_prefix
+            .as_ref()
+            .map_or(true, |prefix| key.starts_with(prefix.as_str()))
+            && self
+                .key_predicate
+                .as_ref()
+                .map_or(true, |predicate| predicate(key))
+    }
+}
 
 #[cfg(any(debug_assertions, feature = "force"))]
-mod timeout;
+mod dev_tools;
 
 #[cfg(any(debug_assertions, feature = "force"))]
 mod component;
@@ -16,7 +16,7 @@ pub fn App() -> impl IntoView {
     // Provides Query Client for entire app.
     provide_query_client_with_options_and_persister(
         Default::default(),
-        query_persister::LocalStoragePersister,
+        query_persister::LocalStoragePersister::DEFAULT,
     );
 
     view! {
@@ -165,6 +165,7 @@ fn post_query() -> QueryScope<PostKey, Option<String>> {
             resource_option: Some(ResourceOption::NonBlocking),
             stale_time: Some(Duration::from_secs(5)),
             gc_time: Some(Duration::from_secs(60)),
+            ..Default::default()
         },
     )
 }
@@ -164,6 +164,7 @@ fn AllTodos() -> impl IntoView {
             QueryState::Fetching(_) => "fetching",
             QueryState::Loaded(_) => "loaded",
             QueryState::Invalid(_) => "invalid",
+            QueryState::Error { .. } => "error",
         };
         logging::log!("STATE: {log}")
     });
@@ -81,7 +81,7 @@ fn SelectPersister() -> impl IntoView {
         match persister {
             Persister::LocalStorage => {
                 client.remove_persister();
-                client.add_persister(LocalStoragePersister);
+                client.add_persister(LocalStoragePersister::DEFAULT);
             }
             Persister::IndexDB => {
                 client.remove_persister();
@@ -0,0 +1,60 @@
+//! Hot read workload on the cache, with the default hasher and with `ahash`.
+//!
+//! Run with `cargo bench -p leptos_query --bench cache_hasher`.
+
+use std::{
+    hash::BuildHasher,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use leptos::*;
+use leptos_query::*;
+
+const QUERIES: usize = 1_000;
+const ROUNDS: usize = 200;
+
+// Long keys, which are expensive to hash.
+fn key(index: usize) -> String {
+    format!("/api/organizations/acme/projects/leptos-query/issues/{index:08}")
+}
+
+fn hot_reads(hasher: Option<impl BuildHasher + 'static>) -> Duration {
+    let runtime = create_runtime();
+    match hasher {
+        Some(hasher) => {
+            provide_query_client_with_options_and_hasher(DefaultQueryOptions::default(), hasher)
+        }
+        None => provide_query_client(),
+    }
+    let client = use_query_client();
+
+    let keys = (0..QUERIES).map(key).collect::<Vec<_>>();
+    for (index, key) in keys.iter().enumerate() {
+        client.set_query_data::<String, usize>(key.clone(), index);
+    }
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for key in keys.iter() {
+            black_box(client.get_query_data::<String, usize>(black_box(key)));
+        }
+    }
+    let elapsed = start.elapsed();
+
+    runtime.dispose();
+    elapsed
+}
+
+fn main() {
+    let reads = (QUERIES * ROUNDS) as u32;
+    let report = |name: &str, elapsed: Duration| {
+        println!("{name:>8}: {:?} per read", elapsed / reads);
+    };
+
+    // Warm up.
+    hot_reads(None::<ahash::RandomState>);
+
+    report("default", hot_reads(None::<ahash::RandomState>));
+    report("ahash", hot_reads(Some(ahash::RandomState::new())));
+}
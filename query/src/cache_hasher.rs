@@ -0,0 +1,138 @@
+use std::{
+    collections::hash_map::{DefaultHasher, RandomState},
+    hash::{BuildHasher, Hash, Hasher},
+    rc::Rc,
+};
+
+/// The hasher used for the query maps in the cache.
+///
+/// Defaults to [`RandomState`]. Swap in a faster, non DoS-resistant hasher (e.g. `ahash`) for keys that are expensive to hash,
+/// using [`provide_query_client_with_options_and_hasher()`](crate::provide_query_client_with_options_and_hasher).
+#[derive(Clone)]
+pub struct CacheHasher(BuildCacheHasher);
+
+// The default is kept unboxed, so hashing a key doesn't allocate.
+#[derive(Clone)]
+enum BuildCacheHasher {
+    Default(RandomState),
+    Custom {
+        // Hashes a key with a hasher built on the stack, so the maps' lookups don't allocate.
+        #[allow(clippy::type_complexity)]
+        hash_one: Rc<dyn Fn(&mut dyn FnMut(&mut dyn Hasher)) -> u64>,
+        build_hasher: Rc<dyn Fn() -> Box<dyn Hasher>>,
+    },
+}
+
+impl CacheHasher {
+    /// Creates a cache hasher from any [`BuildHasher`].
+    pub fn new<S>(build_hasher: S) -> Self
+    where
+        S: BuildHasher + 'static,
+        S::Hasher: 'static,
+    {
+        let build_hasher = Rc::new(build_hasher);
+        Self(BuildCacheHasher::Custom {
+            hash_one: {
+                let build_hasher = build_hasher.clone();
+                Rc::new(move |hash| {
+                    let mut hasher = build_hasher.build_hasher();
+                    hash(&mut hasher);
+                    hasher.finish()
+                })
+            },
+            build_hasher: Rc::new(move || Box::new(build_hasher.build_hasher())),
+        })
+    }
+}
+
+impl Default for CacheHasher {
+    fn default() -> Self {
+        Self(BuildCacheHasher::Default(RandomState::new()))
+    }
+}
+
+impl BuildHasher for CacheHasher {
+    type Hasher = CacheKeyHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        match &self.0 {
+            BuildCacheHasher::Default(state) => {
+                CacheKeyHasher(KeyHasher::Default(state.build_hasher()))
+            }
+            BuildCacheHasher::Custom { build_hasher, .. } => {
+                CacheKeyHasher(KeyHasher::Custom(build_hasher()))
+            }
+        }
+    }
+
+    // Used by the maps for every lookup.
+    fn hash_one<T: Hash>(&self, x: T) -> u64 {
+        match &self.0 {
+            BuildCacheHasher::Default(state) => state.hash_one(x),
+            BuildCacheHasher::Custom { hash_one, .. } => {
+                hash_one(&mut |mut hasher: &mut dyn Hasher| x.hash(&mut hasher))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for CacheHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheHasher").finish_non_exhaustive()
+    }
+}
+
+/// The [`Hasher`] built by a [`CacheHasher`].
+pub struct CacheKeyHasher(KeyHasher);
+
+enum KeyHasher {
+    Default(DefaultHasher),
+    Custom(Box<dyn Hasher>),
+}
+
+impl CacheKeyHasher {
+    fn hasher(&mut self) -> &mut dyn Hasher {
+        match &mut self.0 {
+            KeyHasher::Default(hasher) => hasher,
+            KeyHasher::Custom(hasher) => hasher.as_mut(),
+        }
+    }
+}
+
+// The typed writes are forwarded, as hashers may handle them faster than bytes.
+impl Hasher for CacheKeyHasher {
+    fn finish(&self) -> u64 {
+        match &self.0 {
+            KeyHasher::Default(hasher) => hasher.finish(),
+            KeyHasher::Custom(hasher) => hasher.finish(),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher().write(bytes)
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.hasher().write_u8(i)
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.hasher().write_u16(i)
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.hasher().write_u32(i)
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.hasher().write_u64(i)
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.hasher().write_u128(i)
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.hasher().write_usize(i)
+    }
+}
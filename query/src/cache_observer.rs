@@ -8,6 +8,20 @@ pub trait CacheObserver {
     fn process_cache_event(&self, event: CacheEvent);
 }
 
+pub use crate::query_cache::CacheObserverKey;
+
+// Adapts a closure, for QueryClient::subscribe_events.
+pub(crate) struct FnObserver<F>(pub(crate) F);
+
+impl<F> CacheObserver for FnObserver<F>
+where
+    F: Fn(CacheEvent),
+{
+    fn process_cache_event(&self, event: CacheEvent) {
+        (self.0)(event)
+    }
+}
+
 /// The events that can be observed from the query cache.
 #[derive(Clone, Debug)]
 pub enum CacheEvent {
@@ -79,6 +93,15 @@ pub struct CreatedQuery {
     pub state: QueryState<String>,
     /// Mark invalid
     pub mark_invalid: Rc<dyn Fn() -> bool>,
+    /// Execute the query, using the fetcher of its observers.
+    pub execute: Rc<dyn Fn()>,
+    /// Remove the query from the cache. Returns false if it was already removed.
+    pub evict: Rc<dyn Fn() -> bool>,
+    /// When the query is garbage collected once it has no observers. None if it's never collected.
+    pub evicts_at: Rc<dyn Fn() -> Option<crate::Instant>>,
+    /// The options of the active observers of the query, in subscription order.
+    /// Useful to determine the effective options, e.g. the minimum stale time.
+    pub observer_options: Rc<dyn Fn() -> Vec<crate::QueryOptions<String>>>,
 }
 
 impl Debug for CreatedQuery {
@@ -123,12 +146,50 @@ where
             state.map_data(|data| leptos::Serializable::ser(data).expect("Serialize Query State"))
         });
 
-        let mark_invalid = Rc::new(move || query.mark_invalid());
+        let mark_invalid = {
+            let query = query.clone();
+            Rc::new(move || query.mark_invalid())
+        };
+
+        let execute = {
+            let query = query.clone();
+            Rc::new(move || query.execute())
+        };
+
+        let evict = {
+            let query = query.clone();
+            Rc::new(move || {
+                crate::use_query_client()
+                    .cache
+                    .evict_query::<K, V>(query.get_key())
+            })
+        };
+
+        let evicts_at = {
+            let query = query.clone();
+            Rc::new(move || query.evicts_at())
+        };
+
+        let observer_options = Rc::new(move || {
+            query
+                .observer_options()
+                .into_iter()
+                .map(|options| {
+                    options.map_value(|v| {
+                        leptos::Serializable::ser(&v).expect("Serialize Query Options")
+                    })
+                })
+                .collect()
+        });
 
         CreatedQuery {
             key,
             state,
             mark_invalid,
+            execute,
+            evict,
+            evicts_at,
+            observer_options,
         }
     }
 }
@@ -0,0 +1,72 @@
+use std::{cell::Cell, rc::Rc};
+
+use futures::future::{FutureExt, Shared};
+use futures_channel::oneshot;
+
+/// A token that is resolved when the in-flight request of a query is cancelled.
+///
+/// Retrieve it with [`QueryClient::cancel_token()`](crate::QueryClient::cancel_token) when the fetcher is invoked,
+/// and await [`CancelToken::cancelled()`] to run cleanup, or poll [`CancelToken::is_cancelled()`] to stop work early.
+///
+/// Example:
+/// ```
+/// use leptos_query::*;
+/// use std::future::Future;
+///
+/// // Use as `move |id| fetch_item(&client, id)`.
+/// fn fetch_item(client: &QueryClient, id: u32) -> impl Future<Output = String> {
+///     // The token must be retrieved synchronously, while the request is being started.
+///     let token = client.cancel_token::<u32, String>(&id);
+///     async move {
+///         if token.map_or(false, |token| token.is_cancelled()) {
+///             return String::new();
+///         }
+///         format!("Item {id}")
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CancelToken {
+    cancelled: Rc<Cell<bool>>,
+    sender: Rc<Cell<Option<oneshot::Sender<()>>>>,
+    receiver: Shared<oneshot::Receiver<()>>,
+}
+
+impl std::fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.cancelled.get())
+            .finish()
+    }
+}
+
+impl CancelToken {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        Self {
+            cancelled: Rc::new(Cell::new(false)),
+            sender: Rc::new(Cell::new(Some(sender))),
+            receiver: receiver.shared(),
+        }
+    }
+
+    /// Whether the request has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+
+    /// Resolves once the request has been cancelled.
+    /// Never resolves if the request completes without being cancelled.
+    pub async fn cancelled(&self) {
+        if self.receiver.clone().await.is_err() {
+            futures::future::pending::<()>().await;
+        }
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.set(true);
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(());
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use std::{any::TypeId, collections::HashMap};
+
+use crate::query_cache::QueryCache;
+
+/// A snapshot of every query in the cache, taken with [`QueryClient::checkpoint()`](crate::QueryClient::checkpoint).
+///
+/// Restore it with [`QueryClient::restore()`](crate::QueryClient::restore).
+pub struct Checkpoint {
+    pub(crate) snapshots: HashMap<(TypeId, TypeId), Box<dyn CacheSnapshot>>,
+}
+
+impl std::fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("types", &self.snapshots.len())
+            .finish()
+    }
+}
+
+// The snapshot of a single cache entry, i.e. all queries of one key and value type.
+pub(crate) trait CacheSnapshot {
+    fn restore(&self, cache: &QueryCache);
+}
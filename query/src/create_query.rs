@@ -161,8 +161,8 @@ where
         &self.options
     }
 
-    /// Prefetches a query and stores it in the cache. Useful for preloading data before it is needed.
-    /// If you don't need the result opt for [`fetch_query()`](Self::fetch_query)
+    /// Prefetches a query and stores it in the cache, unless it is already fresh. Useful for preloading data before it is needed.
+    /// If you need the result opt for [`fetch_query_data()`](Self::fetch_query_data)
     /// This should usually be called in a [`create_effect`](leptos::create_effect) or on an event (e.g. on:click).
     pub async fn prefetch_query(&self, key: K) {
         use_query_client()
@@ -170,6 +170,14 @@ where
             .await
     }
 
+    /// Prefetches multiple queries concurrently, skipping those that are already fresh.
+    /// Useful for warming a known set of entities before navigating.
+    pub async fn prefetch_queries(&self, keys: Vec<K>) {
+        use_query_client()
+            .prefetch_queries(keys, self.make_fetcher())
+            .await
+    }
+
     /// Fetch a query and store it in cache.
     /// Result can be read outside of Transition.
     ///
@@ -181,6 +189,22 @@ where
             .await
     }
 
+    /// Fetches a query if it has no fresh data, and returns its data.
+    /// Useful for loaders, see [`QueryClient::fetch_query_data()`](crate::QueryClient::fetch_query_data).
+    pub async fn fetch_query_data(&self, key: K) -> Option<V> {
+        use_query_client()
+            .fetch_query_data(key, self.make_fetcher())
+            .await
+    }
+
+    /// Returns the data of a query, fetching it only if it has no fresh data.
+    /// Useful for loaders, see [`QueryClient::ensure_query_data()`](crate::QueryClient::ensure_query_data).
+    pub async fn ensure_query_data(&self, key: K) -> V {
+        use_query_client()
+            .ensure_query_data(key, self.make_fetcher())
+            .await
+    }
+
     /// Retrieves the current state of a query identified by the given key function.
     ///
     /// Returns A [`Signal`] containing the current [`QueryState`] of the query. If the query does not exist, the signal's value will be [`None`].
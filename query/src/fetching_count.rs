@@ -0,0 +1,58 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use leptos::*;
+
+use crate::{
+    cache_observer::{CacheEvent, CacheObserver, QueryCacheKey},
+    QueryState,
+};
+
+// Counts the queries that are loading or fetching, across all key and value types.
+// Maintained from cache events, so the signal only changes when a query starts or stops fetching.
+#[derive(Clone)]
+pub(crate) struct FetchingCount {
+    fetching: Rc<RefCell<HashSet<QueryCacheKey>>>,
+    count: RwSignal<usize>,
+}
+
+impl FetchingCount {
+    // Must be created under the owner of the client, so the signal lives as long as the client.
+    pub(crate) fn new() -> Self {
+        Self {
+            fetching: Rc::new(RefCell::new(HashSet::new())),
+            count: RwSignal::new(0),
+        }
+    }
+
+    pub(crate) fn signal(&self) -> Signal<usize> {
+        self.count.into()
+    }
+
+    fn update_fetching(&self, key: QueryCacheKey, state: &QueryState<String>) {
+        let mut fetching = self.fetching.borrow_mut();
+        if matches!(state, QueryState::Loading | QueryState::Fetching(_)) {
+            fetching.insert(key);
+        } else {
+            fetching.remove(&key);
+        }
+    }
+}
+
+impl CacheObserver for FetchingCount {
+    fn process_cache_event(&self, event: CacheEvent) {
+        match event {
+            CacheEvent::Created(query) => self.update_fetching(query.key, &query.state),
+            CacheEvent::Updated(query) => self.update_fetching(query.key, &query.state),
+            CacheEvent::Removed(key) => {
+                self.fetching.borrow_mut().remove(&key);
+            }
+            CacheEvent::ObserverAdded(_) | CacheEvent::ObserverRemoved(_) => return,
+        }
+
+        // Avoid notifying subscribers when nothing changed.
+        let count = self.fetching.borrow().len();
+        if self.count.get_untracked() != count {
+            self.count.set(count);
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+// Limits refetch on focus to one focus event per throttle window, across all observers of the client.
+// Every observer listens on the window itself, so the observers handling the same event must all pass.
+// Events are told apart by their timestamp, which is shared by all listeners of an event.
+#[derive(Clone)]
+pub(crate) struct FocusThrottle {
+    throttle: Option<Duration>,
+    // Timestamp in milliseconds of the last focus event that passed.
+    last_event: Rc<Cell<Option<f64>>>,
+}
+
+impl FocusThrottle {
+    pub(crate) fn new(throttle: Option<Duration>) -> Self {
+        Self {
+            throttle,
+            last_event: Rc::new(Cell::new(None)),
+        }
+    }
+
+    // Whether the focus event with the given timestamp may refetch.
+    #[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+    pub(crate) fn passes(&self, event_time: f64) -> bool {
+        let Some(throttle) = self.throttle else {
+            return true;
+        };
+        match self.last_event.get() {
+            Some(last) if last == event_time => true,
+            Some(last) if event_time - last < throttle.as_secs_f64() * 1000.0 => false,
+            _ => {
+                self.last_event.set(Some(event_time));
+                true
+            }
+        }
+    }
+}
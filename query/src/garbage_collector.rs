@@ -78,6 +78,18 @@ where
         }
     }
 
+    /// Overrides the gc time, re-arming a pending timeout.
+    pub fn set_gc_time(&self, gc_time: Option<Duration>) {
+        self.gc_time.set(match gc_time {
+            Some(gc_time) => GcTime::Some(gc_time),
+            None => GcTime::Never,
+        });
+        if let Some(handle) = self.handle.take() {
+            handle.clear();
+            self.enable_gc();
+        }
+    }
+
     pub fn enable_gc(&self) {
         if self.handle.get().is_some() {
             return;
@@ -87,13 +99,13 @@ where
         let updated_at = self.query.get_updated_at();
 
         if let (GcTime::Some(gc_time), Some(updated_at)) = (gc_time, updated_at) {
-            let time_until_gc = crate::util::time_until_stale(updated_at, gc_time);
+            let time_until_gc = crate::time_until_stale(updated_at, gc_time);
             let query = self.query.clone();
             let new_handle = set_timeout_with_handle(
                 move || {
                     let client = crate::use_query_client();
                     let key = query.get_key();
-                    client.cache.evict_query::<K, V>(key);
+                    client.cache.gc_evict_query::<K, V>(key);
                 },
                 time_until_gc,
             )
@@ -103,6 +115,28 @@ where
         }
     }
 
+    /// When the query is evicted once it has no observers.
+    /// None if it's never evicted, e.g. the gc time is infinite or there is no data yet.
+    pub fn evicts_at(&self) -> Option<crate::Instant> {
+        match (self.gc_time.get(), self.query.get_updated_at()) {
+            (GcTime::Some(gc_time), Some(updated_at)) => {
+                Some(crate::Instant(updated_at.0 + gc_time))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the gc time has elapsed, regardless of whether the timeout fired.
+    /// Timeouts fire late if the device was asleep or the tab was throttled.
+    pub fn is_overdue(&self) -> bool {
+        match (self.gc_time.get(), self.query.get_updated_at()) {
+            (GcTime::Some(gc_time), Some(updated_at)) => {
+                crate::time_until_stale(updated_at, gc_time).is_zero()
+            }
+            _ => false,
+        }
+    }
+
     pub fn disable_gc(&self) {
         if let Some(handle) = self.handle.take() {
             handle.clear();
@@ -115,9 +149,8 @@ mod test {
     use super::*;
 
     fn create_query() -> GarbageCollector<String, String> {
-        let query = Query::<String, String>::new("key".into());
-        let gc = query.get_gc().expect("gc should be present");
-        gc
+        let query = Query::<String, String>::new("key".into(), 0);
+        query.get_gc().expect("gc should be present")
     }
 
     #[test]
@@ -136,5 +169,27 @@ mod test {
         gc.update_gc_time(None);
 
         assert_eq!(gc.gc_time.get(), GcTime::Never);
+
+        gc.set_gc_time(Some(Duration::from_secs(5)));
+
+        assert_eq!(gc.gc_time.get(), GcTime::Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_evicts_at() {
+        let gc = create_query();
+        gc.update_gc_time(Some(Duration::from_secs(10)));
+        assert_eq!(gc.evicts_at(), None);
+
+        let data = crate::QueryData::now("value".to_string());
+        let updated_at = data.updated_at;
+        gc.query.seed(data);
+        assert_eq!(
+            gc.evicts_at(),
+            Some(crate::Instant(updated_at.0 + Duration::from_secs(10)))
+        );
+
+        gc.set_gc_time(None);
+        assert_eq!(gc.evicts_at(), None);
     }
 }
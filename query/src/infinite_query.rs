@@ -0,0 +1,483 @@
+use std::{future::Future, rc::Rc};
+
+use futures::FutureExt;
+use leptos::*;
+
+use crate::{
+    query::{self, Query},
+    use_query, use_query_client, QueryKey, QueryOptions, QueryResult, QueryState, QueryValue,
+    RefetchFn,
+};
+
+/// The cached value of an infinite query: every page, with the param it was fetched with, in order.
+pub type InfinitePages<P, V> = Vec<(P, V)>;
+
+/// How the pages of an infinite query are addressed, e.g. by cursor or page number.
+pub struct PageParams<V, P> {
+    initial: P,
+    initial_pages: usize,
+    next: PageParamFn<V, P>,
+    previous: Option<PageParamFn<V, P>>,
+}
+
+type PageParamFn<V, P> = Rc<dyn Fn(&V) -> Option<P>>;
+
+impl<V, P> Clone for PageParams<V, P>
+where
+    P: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            initial: self.initial.clone(),
+            initial_pages: self.initial_pages,
+            next: self.next.clone(),
+            previous: self.previous.clone(),
+        }
+    }
+}
+
+impl<V, P> PageParams<V, P> {
+    /// The param of the first page, and how to get the param of the page after a given page.
+    /// [`None`](Option::None) means there is no next page.
+    pub fn new(
+        initial_page_param: P,
+        get_next_page_param: impl Fn(&V) -> Option<P> + 'static,
+    ) -> Self {
+        Self {
+            initial: initial_page_param,
+            initial_pages: 1,
+            next: Rc::new(get_next_page_param),
+            previous: None,
+        }
+    }
+
+    /// Set how to get the param of the page before a given page, to load pages in both directions.
+    /// [`None`](Option::None) means there is no previous page.
+    pub fn set_get_previous_page_param(
+        self,
+        get_previous_page_param: impl Fn(&V) -> Option<P> + 'static,
+    ) -> Self {
+        Self {
+            previous: Some(Rc::new(get_previous_page_param)),
+            ..self
+        }
+    }
+
+    /// Set how many pages the first load fetches, starting from the initial page param,
+    /// e.g. to restore the window of pages that was loaded before navigating back.
+    /// Fewer pages are loaded if there is no next page. Default is 1.
+    pub fn set_initial_pages(self, initial_pages: usize) -> Self {
+        Self {
+            initial_pages: initial_pages.max(1),
+            ..self
+        }
+    }
+}
+
+/// Reactive infinite query result.
+pub struct InfiniteQueryResult<V, P, R>
+where
+    V: 'static,
+    P: 'static,
+    R: RefetchFn,
+{
+    /// The loaded pages, in order. None if the first page has not been fetched yet.
+    pub pages: Signal<Option<Vec<V>>>,
+    /// The result of the underlying query, whose value holds every page.
+    pub query: QueryResult<InfinitePages<P, V>, R>,
+    /// If there is a page after the last loaded page.
+    pub has_next_page: Signal<bool>,
+    /// If there is a page before the first loaded page.
+    pub has_previous_page: Signal<bool>,
+    /// If the next page is being fetched.
+    pub is_fetching_next_page: Signal<bool>,
+    /// If the previous page is being fetched.
+    pub is_fetching_previous_page: Signal<bool>,
+
+    fetch_page: Rc<dyn Fn(PageDirection)>,
+}
+
+impl<V, P, R> Clone for InfiniteQueryResult<V, P, R>
+where
+    V: Clone,
+    P: Clone,
+    R: RefetchFn,
+{
+    fn clone(&self) -> Self {
+        Self {
+            pages: self.pages,
+            query: self.query.clone(),
+            has_next_page: self.has_next_page,
+            has_previous_page: self.has_previous_page,
+            is_fetching_next_page: self.is_fetching_next_page,
+            is_fetching_previous_page: self.is_fetching_previous_page,
+            fetch_page: self.fetch_page.clone(),
+        }
+    }
+}
+
+impl<V, P, R> InfiniteQueryResult<V, P, R>
+where
+    R: RefetchFn,
+{
+    /// Fetches the page after the last loaded page, if there is one.
+    /// Does nothing while the query is fetching.
+    pub fn fetch_next_page(&self) {
+        (self.fetch_page)(PageDirection::Next)
+    }
+
+    /// Fetches the page before the first loaded page, if there is one.
+    /// Does nothing while the query is fetching.
+    pub fn fetch_previous_page(&self) {
+        (self.fetch_page)(PageDirection::Previous)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PageDirection {
+    Next,
+    Previous,
+}
+
+/// Creates a query that loads its data page by page, e.g. for infinite scrolling.
+///
+/// All pages are stored in a single cache entry for the key, so stale time, gc and invalidation apply to the pages together.
+/// The first load fetches [`initial_pages`](PageParams::set_initial_pages) pages, starting from the initial page param.
+/// Refetching, e.g. after invalidation, refetches every loaded page in order, starting from the param of the first page.
+/// The params of later pages are derived from the refetched pages, so a changed list stays consistent.
+///
+/// Example
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// // The items of the page, and the cursor of the next page.
+/// type Page = (Vec<String>, Option<u32>);
+///
+/// async fn fetch_feed(feed: String, cursor: u32) -> Page {
+///     let items = vec![format!("{feed} item {cursor}")];
+///     (items, (cursor < 10).then_some(cursor + 1))
+/// }
+///
+/// #[component]
+/// fn Feed(feed: String) -> impl IntoView {
+///     let feed_query = use_infinite_query(
+///         move || feed.clone(),
+///         fetch_feed,
+///         PageParams::new(0, |(_, next_cursor): &Page| *next_cursor),
+///         QueryOptions::default(),
+///     );
+///     let pages = feed_query.pages;
+///     let has_next_page = feed_query.has_next_page;
+///
+///     view! {
+///         <ul>
+///             {move || {
+///                 pages
+///                     .get()
+///                     .unwrap_or_default()
+///                     .into_iter()
+///                     .flat_map(|(items, _)| items)
+///                     .map(|item| view! { <li>{item}</li> })
+///                     .collect_view()
+///             }}
+///         </ul>
+///         <button disabled=move || !has_next_page.get() on:click=move |_| feed_query.fetch_next_page()>
+///             "Load more"
+///         </button>
+///     }
+/// }
+/// ```
+pub fn use_infinite_query<K, V, P, Fu>(
+    key: impl Fn() -> K + 'static,
+    fetcher: impl Fn(K, P) -> Fu + 'static,
+    page_params: PageParams<V, P>,
+    options: QueryOptions<InfinitePages<P, V>>,
+) -> InfiniteQueryResult<V, P, impl RefetchFn>
+where
+    K: QueryKey + 'static,
+    V: Clone + 'static,
+    P: Clone + 'static,
+    InfinitePages<P, V>: QueryValue,
+    Fu: Future<Output = V> + 'static,
+{
+    let fetcher = Rc::new(fetcher);
+
+    let result = use_query(
+        key,
+        fetch_all_pages(fetcher.clone(), page_params.clone()),
+        options,
+    );
+
+    let is_fetching_next_page = RwSignal::new(false);
+    let is_fetching_previous_page = RwSignal::new(false);
+
+    let data = result.data;
+    let has_page = {
+        let page_params = page_params.clone();
+        move |direction: PageDirection| {
+            data.with(|pages| {
+                pages
+                    .as_ref()
+                    .is_some_and(|pages| page_param(&page_params, pages, direction).is_some())
+            })
+        }
+    };
+
+    let fetch_page = {
+        let result = result.clone();
+        move |direction: PageDirection| {
+            let query = result
+                .query::<K>()
+                .expect("Infinite query result holds a query of its key type");
+            let fetching = match direction {
+                PageDirection::Next => is_fetching_next_page,
+                PageDirection::Previous => is_fetching_previous_page,
+            };
+            fetch_page(query, fetcher.clone(), &page_params, direction, fetching)
+        }
+    };
+
+    InfiniteQueryResult {
+        pages: Signal::derive(move || {
+            data.with(|pages| {
+                pages
+                    .as_ref()
+                    .map(|pages| pages.iter().map(|(_, page)| page.clone()).collect())
+            })
+        }),
+        has_next_page: Signal::derive({
+            let has_page = has_page.clone();
+            move || has_page(PageDirection::Next)
+        }),
+        has_previous_page: Signal::derive(move || has_page(PageDirection::Previous)),
+        is_fetching_next_page: is_fetching_next_page.into(),
+        is_fetching_previous_page: is_fetching_previous_page.into(),
+        query: result,
+        fetch_page: Rc::new(fetch_page),
+    }
+}
+
+// The param of the page after the last page, or before the first page.
+fn page_param<V, P>(
+    page_params: &PageParams<V, P>,
+    pages: &InfinitePages<P, V>,
+    direction: PageDirection,
+) -> Option<P> {
+    match direction {
+        PageDirection::Next => pages.last().and_then(|(_, page)| (page_params.next)(page)),
+        PageDirection::Previous => {
+            let previous = page_params.previous.as_ref()?;
+            pages.first().and_then(|(_, page)| previous(page))
+        }
+    }
+}
+
+// Refetches the loaded pages in order, or loads the initial pages.
+fn fetch_all_pages<K, V, P, Fu>(
+    fetcher: Rc<impl Fn(K, P) -> Fu + 'static>,
+    page_params: PageParams<V, P>,
+) -> impl Fn(K) -> futures::future::LocalBoxFuture<'static, InfinitePages<P, V>>
+where
+    K: QueryKey + 'static,
+    V: Clone + 'static,
+    P: Clone + 'static,
+    InfinitePages<P, V>: QueryValue,
+    Fu: Future<Output = V> + 'static,
+{
+    let client = use_query_client();
+    move |key| {
+        let loaded = client
+            .get_query_data::<K, InfinitePages<P, V>>(&key)
+            .unwrap_or_default();
+        let count = match loaded.len() {
+            0 => page_params.initial_pages,
+            loaded => loaded,
+        };
+        let first_param = loaded
+            .into_iter()
+            .next()
+            .map(|(param, _)| param)
+            .unwrap_or_else(|| page_params.initial.clone());
+        let fetcher = fetcher.clone();
+        let next = page_params.next.clone();
+        async move {
+            let mut pages = Vec::with_capacity(count);
+            let mut param = Some(first_param);
+            while let Some(current) = param.take() {
+                let page = fetcher(key.clone(), current.clone()).await;
+                if pages.len() + 1 < count {
+                    param = next(&page);
+                }
+                pages.push((current, page));
+            }
+            pages
+        }
+        .boxed_local()
+    }
+}
+
+fn fetch_page<K, V, P, Fu>(
+    query: Query<K, InfinitePages<P, V>>,
+    fetcher: Rc<impl Fn(K, P) -> Fu + 'static>,
+    page_params: &PageParams<V, P>,
+    direction: PageDirection,
+    fetching: RwSignal<bool>,
+) where
+    K: QueryKey + 'static,
+    V: Clone + 'static,
+    P: Clone + 'static,
+    InfinitePages<P, V>: QueryValue,
+    Fu: Future<Output = V> + 'static,
+{
+    // The first page is loaded by the query itself.
+    let Some(pages) = query.with_state(|state| state.data().cloned()) else {
+        return;
+    };
+    let Some(param) = page_param(page_params, &pages, direction) else {
+        return;
+    };
+    if query.is_fetching() || query.with_state(|state| matches!(state, QueryState::Loading)) {
+        return;
+    }
+
+    fetching.set(true);
+    spawn_local(async move {
+        let page_fetcher = move |key: K| {
+            let mut pages = pages.clone();
+            let param = param.clone();
+            fetcher(key, param.clone()).map(move |page| {
+                match direction {
+                    PageDirection::Next => pages.push((param, page)),
+                    PageDirection::Previous => pages.insert(0, (param, page)),
+                }
+                Ok(pages)
+            })
+        };
+        query::execute_without_dedup(query, page_fetcher, true).await;
+        fetching.set(false);
+    });
+}
+
+#[cfg(all(test, not(any(feature = "csr", feature = "hydrate"))))]
+mod tests {
+    use super::*;
+    use crate::provide_query_client;
+    use std::cell::RefCell;
+
+    // use_query refetches its resource on every state change, which can't run within a test executor,
+    // so the pages are fetched directly.
+    #[test]
+    fn pages_are_loaded_and_refetched_in_order() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetched = Rc::new(RefCell::new(Vec::new()));
+        let fetcher = Rc::new({
+            let fetched = fetched.clone();
+            move |_: String, cursor: u32| {
+                fetched.borrow_mut().push(cursor);
+                async move { cursor * 10 }
+            }
+        });
+        let page_params = PageParams::new(1, |page: &u32| (*page < 30).then_some(page / 10 + 1))
+            .set_get_previous_page_param(|page: &u32| (*page > 0).then(|| page / 10 - 1));
+        let query = client
+            .cache
+            .get_or_create_query::<String, InfinitePages<u32, u32>>("feed".into());
+        let pages = || {
+            query
+                .with_state(|state| state.data().cloned())
+                .map(|pages| pages.into_iter().map(|(_, page)| page).collect::<Vec<_>>())
+        };
+        let has_page = |direction| {
+            query
+                .with_state(|state| state.data().cloned())
+                .and_then(|pages| page_param(&page_params, &pages, direction))
+                .is_some()
+        };
+        let fetch_all = || {
+            let fetch_all = fetch_all_pages(fetcher.clone(), page_params.clone());
+            assert!(query::execute_query(query.clone(), fetch_all)
+                .now_or_never()
+                .is_some());
+        };
+        let fetching = RwSignal::new(false);
+        let fetch = |direction| {
+            fetch_page(
+                query.clone(),
+                fetcher.clone(),
+                &page_params,
+                direction,
+                fetching,
+            )
+        };
+
+        // Nothing to extend before the first page is loaded.
+        fetch(PageDirection::Next);
+        assert_eq!(None, pages());
+
+        fetch_all();
+        assert_eq!(Some(vec![10]), pages());
+        assert!(has_page(PageDirection::Next));
+
+        fetch(PageDirection::Next);
+        fetch(PageDirection::Next);
+        assert_eq!(Some(vec![10, 20, 30]), pages());
+        assert!(!has_page(PageDirection::Next));
+        assert!(!fetching.get_untracked());
+
+        // There is no page after the last page.
+        fetch(PageDirection::Next);
+        assert_eq!(vec![1, 2, 3], *fetched.borrow());
+
+        fetch(PageDirection::Previous);
+        assert_eq!(Some(vec![0, 10, 20, 30]), pages());
+        assert!(!has_page(PageDirection::Previous));
+
+        // A refetch, e.g. after invalidation, refetches every page in order, in a single cache entry.
+        fetched.borrow_mut().clear();
+        fetch_all();
+        assert_eq!(vec![0, 1, 2, 3], *fetched.borrow());
+        assert_eq!(Some(vec![0, 10, 20, 30]), pages());
+        assert_eq!(1, client.size().get_untracked());
+    }
+
+    #[test]
+    fn initial_pages_are_loaded_together() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetched = Rc::new(RefCell::new(Vec::new()));
+        let fetcher = Rc::new({
+            let fetched = fetched.clone();
+            move |_: String, cursor: u32| {
+                fetched.borrow_mut().push(cursor);
+                async move { cursor }
+            }
+        });
+        let fetch_all = |key: &str, initial_pages| {
+            let page_params = PageParams::new(2, |page: &u32| (*page < 5).then_some(page + 1))
+                .set_initial_pages(initial_pages);
+            let query = client
+                .cache
+                .get_or_create_query::<String, InfinitePages<u32, u32>>(key.into());
+            let fetch_all = fetch_all_pages(fetcher.clone(), page_params);
+            assert!(query::execute_query(query.clone(), fetch_all)
+                .now_or_never()
+                .is_some());
+            query.with_state(|state| state.data().cloned()).unwrap()
+        };
+
+        // Starts at the initial page param.
+        assert_eq!(vec![(2, 2), (3, 3), (4, 4)], fetch_all("window", 3));
+        assert_eq!(vec![2, 3, 4], *fetched.borrow());
+
+        // Stops at the last page.
+        fetched.borrow_mut().clear();
+        assert_eq!(4, fetch_all("end", 10).len());
+        assert_eq!(vec![2, 3, 4, 5], *fetched.borrow());
+    }
+}
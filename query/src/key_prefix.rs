@@ -0,0 +1,64 @@
+/// Partial matching for composite keys, e.g. tuples like `(Resource, Id, Filters)`.
+///
+/// A key has a prefix if its leading elements are equal to the prefix elements.
+/// Implemented for tuples of up to 4 elements, where the prefix is a tuple of the leading elements.
+/// Used by [`QueryClient::invalidate_by_prefix()`](crate::QueryClient::invalidate_by_prefix).
+///
+/// Example:
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+/// enum Resource {
+///     Track,
+///     Album,
+/// }
+///
+/// // (Resource, Id, Page)
+/// type ListKey = (Resource, u32, u32);
+///
+/// fn use_track_list(id: u32, page: u32) -> QueryResult<Vec<String>, impl RefetchFn> {
+///     use_query(
+///         move || (Resource::Track, id, page),
+///         |(_, id, page): ListKey| async move { vec![format!("Track {id} page {page}")] },
+///         QueryOptions::default(),
+///     )
+/// }
+///
+/// fn invalidate_track(id: u32) {
+///     // Invalidates every page of the track list.
+///     use_query_client().invalidate_by_prefix::<ListKey, Vec<String>, _>(&(Resource::Track, id));
+/// }
+///
+/// assert!((Resource::Album, 1, 2).has_prefix(&(Resource::Album,)));
+/// assert!(!(Resource::Album, 1, 2).has_prefix(&(Resource::Album, 2)));
+/// ```
+pub trait KeyPrefix<P> {
+    /// Whether the leading elements of the key are equal to the prefix.
+    fn has_prefix(&self, prefix: &P) -> bool;
+}
+
+macro_rules! impl_key_prefix {
+    (($($key:ident),+) => ($($index:tt: $prefix:ident),+)) => {
+        impl<$($key),+> KeyPrefix<($($prefix,)+)> for ($($key,)+)
+        where
+            $($prefix: PartialEq,)+
+        {
+            fn has_prefix(&self, prefix: &($($prefix,)+)) -> bool {
+                $(self.$index == prefix.$index)&&+
+            }
+        }
+    };
+}
+
+impl_key_prefix!((A) => (0: A));
+impl_key_prefix!((A, B) => (0: A));
+impl_key_prefix!((A, B) => (0: A, 1: B));
+impl_key_prefix!((A, B, C) => (0: A));
+impl_key_prefix!((A, B, C) => (0: A, 1: B));
+impl_key_prefix!((A, B, C) => (0: A, 1: B, 2: C));
+impl_key_prefix!((A, B, C, D) => (0: A));
+impl_key_prefix!((A, B, C, D) => (0: A, 1: B));
+impl_key_prefix!((A, B, C, D) => (0: A, 1: B, 2: C));
+impl_key_prefix!((A, B, C, D) => (0: A, 1: B, 2: C, 3: D));
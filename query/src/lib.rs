@@ -47,6 +47,7 @@
 //! - `ssr` Server-side rendering: Initiate queries on the server.
 //! - `hydrate` Hydration: Ensure that queries are hydrated on the client, when using server-side rendering.
 //! - `local_storage` - Enables local storage persistance for queries.
+//! - `session_storage` - Enables session storage persistance for queries, scoped to a single browser tab.
 //! - `index_db` - Enables index db persistance for queries.
 //!
 //! ## Version compatibility for Leptos and Leptos Query
@@ -240,32 +241,63 @@
 //! ```
 //!
 
+mod cache_hasher;
 /// Subcriptions to cache-wide query events.
 pub mod cache_observer;
+mod cancel_token;
+mod checkpoint;
 mod create_query;
+mod fetching_count;
+mod focus_throttle;
 mod garbage_collector;
+mod infinite_query;
 mod instant;
+mod key_prefix;
+mod mutation;
+mod optimistic_list;
 mod query;
 mod query_cache;
 mod query_client;
 mod query_executor;
 mod query_observer;
 mod query_options;
+mod query_options_provider;
 /// Utitities for client side query persistance.
 pub mod query_persister;
 mod query_result;
 mod query_state;
+mod tag_index;
+#[cfg(test)]
+mod test_timer;
+mod timeout;
+mod use_cache_pressure;
+mod use_is_group_fetching;
 mod use_query;
-mod util;
+mod use_query_mut;
+mod use_query_states;
 
+pub use cache_hasher::*;
+pub use cancel_token::*;
+pub use checkpoint::*;
 pub use create_query::*;
+pub use infinite_query::*;
 pub use instant::*;
+pub use key_prefix::*;
+pub use mutation::*;
+pub use optimistic_list::*;
+pub use query::Query;
 pub use query_client::*;
 pub use query_executor::*;
 pub use query_options::*;
+pub use query_options_provider::*;
 pub use query_result::*;
 pub use query_state::*;
+pub use timeout::*;
+pub use use_cache_pressure::*;
+pub use use_is_group_fetching::*;
 pub use use_query::*;
+pub use use_query_mut::*;
+pub use use_query_states::*;
 
 /// Convenience trait for query key requirements.
 pub trait QueryKey: std::fmt::Debug + Clone + std::hash::Hash + Eq {}
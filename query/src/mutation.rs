@@ -0,0 +1,420 @@
+use std::{cell::RefCell, future::Future, rc::Rc};
+
+use futures::future::{FutureExt, LocalBoxFuture, Shared};
+use leptos::*;
+
+use crate::{use_query_client, QueryKey, QueryValue};
+
+/// The in-flight future of a mutation, resolving with its result.
+/// Can be cloned and awaited any number of times.
+pub type MutationFuture<V, E> = Shared<LocalBoxFuture<'static, Result<V, E>>>;
+
+type Rollback = Box<dyn FnOnce()>;
+
+/// Creates a mutation. Useful for write operations, e.g. creating, updating or deleting server state.
+///
+/// A mutation does not touch the cache on its own.
+/// Use [`MutationResult::on_success`] or [`MutationResult::on_settled`] to update the cache afterwards, e.g. with [`QueryClient::invalidate_query()`](crate::QueryClient::invalidate_query),
+/// or [`MutationResult::optimistic_update`] to update it right away.
+///
+/// Calling [`mutate`](MutationResult::mutate) while the mutation is pending does not run it again,
+/// the returned future resolves with the result of the pending call instead.
+///
+/// Example
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// async fn rename_todo((id, title): (u32, String)) -> Result<String, String> {
+///     Ok(title)
+/// }
+///
+/// #[component]
+/// fn RenameTodo(id: u32) -> impl IntoView {
+///     let client = use_query_client();
+///     let rename = use_mutation(rename_todo).on_success(move |_| {
+///         client.invalidate_query::<u32, String>(id);
+///     });
+///
+///     view! {
+///         <button
+///             disabled=move || rename.is_pending.get()
+///             on:click={
+///                 let rename = rename.clone();
+///                 move |_| {
+///                     rename.mutate((id, "Renamed".to_string()));
+///                 }
+///             }
+///         >
+///             "Rename"
+///         </button>
+///         <p>{move || rename.error.get()}</p>
+///     }
+/// }
+/// ```
+pub fn use_mutation<A, V, E, Fu>(mutator: impl Fn(A) -> Fu + 'static) -> MutationResult<A, V, E>
+where
+    A: 'static,
+    V: Clone + 'static,
+    E: Clone + 'static,
+    Fu: Future<Output = Result<V, E>> + 'static,
+{
+    let data = RwSignal::new(None);
+    let error = RwSignal::new(None);
+    let is_pending = RwSignal::new(false);
+
+    MutationResult {
+        data: data.into(),
+        error: error.into(),
+        is_pending: is_pending.into(),
+        mutation: Mutation {
+            mutator: Rc::new(move |args| mutator(args).boxed_local()),
+            in_flight: Rc::new(RefCell::new(None)),
+            on_success: Rc::new(RefCell::new(None)),
+            on_settled: Rc::new(RefCell::new(None)),
+            optimistic_updates: Rc::new(RefCell::new(Vec::new())),
+            data,
+            error,
+            is_pending,
+        },
+    }
+}
+
+/// Reactive mutation result.
+pub struct MutationResult<A, V, E>
+where
+    A: 'static,
+    V: 'static,
+    E: 'static,
+{
+    /// The value of the last successful mutation. None if no mutation succeeded yet.
+    pub data: Signal<Option<V>>,
+    /// The error of the last mutation. None if it succeeded or is pending.
+    pub error: Signal<Option<E>>,
+    /// If the mutation is in flight.
+    pub is_pending: Signal<bool>,
+
+    mutation: Mutation<A, V, E>,
+}
+
+impl<A, V, E> Clone for MutationResult<A, V, E> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data,
+            error: self.error,
+            is_pending: self.is_pending,
+            mutation: self.mutation.clone(),
+        }
+    }
+}
+
+impl<A, V, E> MutationResult<A, V, E>
+where
+    A: 'static,
+    V: Clone + 'static,
+    E: Clone + 'static,
+{
+    /// Runs the mutation in the background, unless it is already pending.
+    ///
+    /// Returns the in-flight future, to await completion imperatively. Dropping it does not cancel the mutation.
+    pub fn mutate(&self, args: A) -> MutationFuture<V, E> {
+        let future = self.mutation.start(args);
+        spawn_local(future.clone().map(|_| ()));
+        future
+    }
+
+    /// Sets a callback that is called with the value of every successful mutation, e.g. to invalidate or evict affected queries.
+    pub fn on_success(self, on_success: impl Fn(&V) + 'static) -> Self {
+        *self.mutation.on_success.borrow_mut() = Some(Rc::new(on_success));
+        self
+    }
+
+    /// Sets a callback that is called with the result of every mutation, whether it succeeded or failed.
+    ///
+    /// Called after optimistic updates of a failed mutation are rolled back, so invalidating the affected queries here refetches from the restored state.
+    pub fn on_settled(self, on_settled: impl Fn(&Result<V, E>) + 'static) -> Self {
+        *self.mutation.on_settled.borrow_mut() = Some(Rc::new(on_settled));
+        self
+    }
+
+    /// Optimistically updates the query of `key` when the mutation starts, and rolls it back if the mutation fails.
+    ///
+    /// `update` receives the mutation arguments and the current data, and returns the optimistic data, or None to leave the query untouched.
+    /// The query state is snapshotted right before the update, and restored with [`QueryClient::restore_query()`](crate::QueryClient::restore_query) on failure.
+    /// A query that did not exist is reset to [`QueryState::Created`](crate::QueryState::Created).
+    ///
+    /// Can be called multiple times, e.g. to update a list and a detail query.
+    ///
+    /// Example
+    /// ```
+    /// use leptos::*;
+    /// use leptos_query::*;
+    ///
+    /// async fn rename_todo((id, title): (u32, String)) -> Result<String, String> {
+    ///     Ok(title)
+    /// }
+    ///
+    /// fn use_rename_todo() -> MutationResult<(u32, String), String, String> {
+    ///     let client = use_query_client();
+    ///     use_mutation(rename_todo)
+    ///         .optimistic_update(|(id, _): &(u32, String)| *id, |(_, title), _: Option<&String>| {
+    ///             Some(title.clone())
+    ///         })
+    ///         .on_settled(move |_| {
+    ///             client.invalidate_query_type::<u32, String>();
+    ///         })
+    /// }
+    /// ```
+    pub fn optimistic_update<K, Q>(
+        self,
+        key: impl Fn(&A) -> K + 'static,
+        update: impl Fn(&A, Option<&Q>) -> Option<Q> + 'static,
+    ) -> Self
+    where
+        K: QueryKey + 'static,
+        Q: QueryValue + 'static,
+    {
+        let client = use_query_client();
+        self.mutation
+            .optimistic_updates
+            .borrow_mut()
+            .push(Rc::new(move |args: &A| {
+                let key = key(args);
+                let snapshot = client.snapshot_query::<K, Q>(&key);
+                let data = update(args, snapshot.as_ref().and_then(|state| state.data()))?;
+                client.set_query_data::<K, Q>(key.clone(), data);
+
+                let client = client.clone();
+                Some(
+                    Box::new(move || client.restore_query(key, snapshot.unwrap_or_default()))
+                        as Rollback,
+                )
+            }));
+        self
+    }
+}
+
+#[allow(clippy::type_complexity)]
+struct Mutation<A, V, E>
+where
+    V: 'static,
+    E: 'static,
+{
+    mutator: Rc<dyn Fn(A) -> LocalBoxFuture<'static, Result<V, E>>>,
+    // Guards against concurrent calls, like the current request of a query.
+    in_flight: Rc<RefCell<Option<MutationFuture<V, E>>>>,
+    on_success: Rc<RefCell<Option<Rc<dyn Fn(&V)>>>>,
+    on_settled: Rc<RefCell<Option<Rc<dyn Fn(&Result<V, E>)>>>>,
+    // Apply an optimistic update, and return how to roll it back.
+    optimistic_updates: Rc<RefCell<Vec<Rc<dyn Fn(&A) -> Option<Rollback>>>>>,
+    data: RwSignal<Option<V>>,
+    error: RwSignal<Option<E>>,
+    is_pending: RwSignal<bool>,
+}
+
+impl<A, V, E> Clone for Mutation<A, V, E>
+where
+    V: 'static,
+    E: 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            mutator: self.mutator.clone(),
+            in_flight: self.in_flight.clone(),
+            on_success: self.on_success.clone(),
+            on_settled: self.on_settled.clone(),
+            optimistic_updates: self.optimistic_updates.clone(),
+            data: self.data,
+            error: self.error,
+            is_pending: self.is_pending,
+        }
+    }
+}
+
+impl<A, V, E> Mutation<A, V, E>
+where
+    A: 'static,
+    V: Clone + 'static,
+    E: Clone + 'static,
+{
+    // Returns the in-flight future if there is one, otherwise starts a new one.
+    fn start(&self, args: A) -> MutationFuture<V, E> {
+        if let Some(in_flight) = self.in_flight.borrow().as_ref() {
+            return in_flight.clone();
+        }
+
+        self.is_pending.set(true);
+        self.error.set(None);
+
+        let optimistic_updates = self.optimistic_updates.borrow().clone();
+        let rollbacks = optimistic_updates
+            .iter()
+            .filter_map(|optimistic_update| optimistic_update(&args))
+            .collect::<Vec<_>>();
+
+        let fetch = (self.mutator)(args);
+        let mutation = self.clone();
+        let future = async move {
+            let result = fetch.await;
+            mutation.in_flight.borrow_mut().take();
+            match &result {
+                Ok(value) => {
+                    mutation.data.set(Some(value.clone()));
+                    let on_success = mutation.on_success.borrow().clone();
+                    if let Some(on_success) = on_success {
+                        on_success(value);
+                    }
+                }
+                Err(error) => {
+                    // Before on_settled, so an invalidation there isn't overwritten by the rollback.
+                    for rollback in rollbacks.into_iter().rev() {
+                        rollback();
+                    }
+                    mutation.error.set(Some(error.clone()));
+                }
+            }
+            let on_settled = mutation.on_settled.borrow().clone();
+            if let Some(on_settled) = on_settled {
+                on_settled(&result);
+            }
+            mutation.is_pending.set(false);
+            result
+        }
+        .boxed_local()
+        .shared();
+
+        *self.in_flight.borrow_mut() = Some(future.clone());
+        future
+    }
+}
+
+#[cfg(all(test, not(any(feature = "csr", feature = "hydrate"))))]
+mod tests {
+    use super::*;
+    use futures_channel::oneshot;
+    use std::cell::Cell;
+
+    #[test]
+    fn concurrent_calls_share_the_pending_mutation() {
+        let _ = create_runtime();
+
+        let calls = Rc::new(Cell::new(0));
+        let (sender, receiver) = oneshot::channel::<u32>();
+        let receiver = Rc::new(RefCell::new(Some(receiver)));
+        let result = use_mutation({
+            let calls = calls.clone();
+            move |args: u32| {
+                calls.set(calls.get() + 1);
+                let receiver = receiver.borrow_mut().take().expect("Mutator called once");
+                async move { receiver.await.map(|value| value + args).map_err(|_| ()) }
+            }
+        });
+
+        let mut first = result.mutation.start(1);
+        let mut second = result.mutation.start(2);
+        assert!((&mut first).now_or_never().is_none());
+        assert!((&mut second).now_or_never().is_none());
+        assert_eq!(1, calls.get());
+        assert!(result.is_pending.get_untracked());
+
+        sender.send(10).unwrap();
+        assert_eq!(Some(Ok(11)), first.now_or_never());
+        assert_eq!(Some(Ok(11)), second.now_or_never());
+        assert!(!result.is_pending.get_untracked());
+        assert_eq!(Some(11), result.data.get_untracked());
+    }
+
+    #[test]
+    fn on_success_and_error() {
+        let _ = create_runtime();
+
+        let succeeded = Rc::new(RefCell::new(Vec::new()));
+        let result = use_mutation(|args: i32| async move {
+            if args >= 0 {
+                Ok(args)
+            } else {
+                Err("negative")
+            }
+        })
+        .on_success({
+            let succeeded = succeeded.clone();
+            move |value| succeeded.borrow_mut().push(*value)
+        });
+
+        assert_eq!(Some(Ok(1)), result.mutate(1).now_or_never());
+        assert_eq!(vec![1], *succeeded.borrow());
+        assert_eq!(Some(1), result.data.get_untracked());
+        assert_eq!(None, result.error.get_untracked());
+
+        assert_eq!(Some(Err("negative")), result.mutate(-1).now_or_never());
+        assert_eq!(vec![1], *succeeded.borrow());
+        assert_eq!(Some(1), result.data.get_untracked());
+        assert_eq!(Some("negative"), result.error.get_untracked());
+        assert!(!result.is_pending.get_untracked());
+    }
+
+    #[test]
+    fn failed_mutation_rolls_back_optimistic_update() {
+        use crate::{provide_query_client, QueryState};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        client.set_query_data::<u32, String>(0, "old".into());
+
+        let settled = Rc::new(RefCell::new(Vec::new()));
+        let result = use_mutation(|(_, title): (u32, String)| async move {
+            if title.is_empty() {
+                Err("empty")
+            } else {
+                Ok(title)
+            }
+        })
+        .optimistic_update(
+            |(id, _): &(u32, String)| *id,
+            |(_, title), _: Option<&String>| Some(title.clone()),
+        )
+        .on_settled({
+            let settled = settled.clone();
+            let client = client.clone();
+            // The rollback already happened.
+            move |_| {
+                settled
+                    .borrow_mut()
+                    .push(client.get_query_data::<u32, String>(&0))
+            }
+        });
+
+        assert!(result.mutate((0, "new".into())).now_or_never().is_some());
+        assert_eq!(
+            Some("new".to_string()),
+            client.get_query_data::<u32, String>(&0)
+        );
+
+        assert!(result.mutate((0, String::new())).now_or_never().is_some());
+        assert_eq!(
+            Some("new".to_string()),
+            client.get_query_data::<u32, String>(&0)
+        );
+        assert!(matches!(
+            client.peek_query_state::<u32, String>(&0),
+            Some(QueryState::Loaded(_))
+        ));
+
+        // A query that did not exist is reset.
+        assert!(result.mutate((1, String::new())).now_or_never().is_some());
+        assert_eq!(
+            Some(QueryState::Created),
+            client.peek_query_state::<u32, String>(&1)
+        );
+
+        assert_eq!(
+            vec![
+                Some("new".to_string()),
+                Some("new".to_string()),
+                Some("new".to_string())
+            ],
+            *settled.borrow()
+        );
+    }
+}
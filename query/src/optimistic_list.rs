@@ -0,0 +1,52 @@
+use crate::{QueryClient, QueryKey, QueryValue};
+
+/// A query value that is a list, e.g. a [`Vec`].
+/// Used by [`QueryClient::optimistic_list_insert()`] and [`QueryClient::optimistic_list_remove()`].
+pub trait AsList {
+    /// The type of the list items.
+    type Item;
+
+    /// Mutable access to the list.
+    fn as_list_mut(&mut self) -> &mut Vec<Self::Item>;
+}
+
+impl<T> AsList for Vec<T> {
+    type Item = T;
+
+    fn as_list_mut(&mut self) -> &mut Vec<T> {
+        self
+    }
+}
+
+/// Restores the data of a query as it was before an optimistic update.
+///
+/// Call [`rollback()`](Self::rollback) if the mutation fails. Dropping the handle keeps the optimistic data.
+#[must_use = "Dropping the handle keeps the optimistic data, call rollback() if the mutation fails"]
+pub struct RollbackHandle<K, V> {
+    client: QueryClient,
+    key: K,
+    snapshot: Option<V>,
+}
+
+impl<K, V> RollbackHandle<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    pub(crate) fn new(client: QueryClient, key: K, snapshot: Option<V>) -> Self {
+        Self {
+            client,
+            key,
+            snapshot,
+        }
+    }
+
+    /// Restores the data from before the optimistic update.
+    /// The snapshot is restored even if the query was updated since, e.g. by a refetch.
+    pub fn rollback(self) {
+        if let Some(snapshot) = self.snapshot {
+            self.client
+                .update_query_data_mut::<K, V>(&self.key, move |data| *data = snapshot);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     cell::{Cell, RefCell},
-    collections::HashMap,
+    collections::VecDeque,
     future::Future,
     rc::Rc,
     time::Duration,
@@ -8,29 +8,58 @@ use std::{
 
 use futures_channel::oneshot;
 use leptos::*;
+use slotmap::SlotMap;
 
 use crate::{
+    cancel_token::CancelToken,
     garbage_collector::GarbageCollector,
     query_cache::CacheNotification,
     query_is_suppressed,
-    query_observer::{ObserverKey, QueryObserver},
-    use_query_client,
-    util::time_until_stale,
-    QueryData, QueryState,
+    query_observer::{ListenerKey, ObserverKey, QueryObserver},
+    time_until_stale, use_query_client, FetchSource, QueryData, QueryState,
 };
 
+/// A single query in the cache, identified by its key.
+///
+/// This is an advanced API, prefer [`use_query()`](crate::use_query()) and the [`QueryClient`](crate::QueryClient) methods.
+/// A query handle bypasses the invariants maintained by observers, e.g. [`Query::set_state`] notifies observers
+/// without recording where the data came from, and [`Query::execute`] ignores staleness.
 #[derive(Clone)]
 pub struct Query<K, V> {
     key: K,
 
     // Cancellation
     current_request: Rc<Cell<Option<oneshot::Sender<()>>>>,
+    cancel_token: Rc<RefCell<Option<CancelToken>>>,
+    // Mocked queries are never fetched or invalidated.
+    mocked: Rc<Cell<bool>>,
+    // Set once the client's owner is disposed, the query is inert afterwards.
+    shut_down: Rc<Cell<bool>>,
+    // Set once the query is evicted from the cache, state updates are dropped afterwards.
+    disposed: Rc<Cell<bool>>,
+    // Set while a coalesced execution is queued for the end of the tick.
+    execute_queued: Rc<Cell<bool>>,
+    // Set when the query is invalidated during a fetch, the fetched data is invalidated once it arrives.
+    refetch_pending: Rc<Cell<bool>>,
+    // Logical time of the last lookup in the cache, to evict the least recently used query.
+    last_access: Rc<Cell<u64>>,
 
     // State
     state: Rc<RefCell<QueryState<V>>>,
+    fetch_source: Rc<Cell<Option<FetchSource>>>,
+    // Increases on every update, except those that keep the data as is, e.g. a refetch that returned equal data.
+    data_version: Rc<Cell<u64>>,
+    // The most recent errors, oldest first, up to the client's error history size.
+    errors: Rc<RefCell<VecDeque<(crate::Instant, crate::QueryError)>>>,
+    error_history_size: usize,
 
     // Synchronization
-    observers: Rc<RefCell<HashMap<ObserverKey, QueryObserver<K, V>>>>,
+    // Kept in subscription order, so observers are notified in a deterministic order.
+    #[allow(clippy::type_complexity)]
+    observers: Rc<RefCell<Vec<(ObserverKey, QueryObserver<K, V>)>>>,
+    // Notified of state changes without counting as observers, e.g. to wait for a request to settle.
+    #[allow(clippy::type_complexity)]
+    state_listeners: Rc<RefCell<SlotMap<ListenerKey, Box<dyn Fn(&QueryState<V>)>>>>,
     garbage_collector: Rc<RefCell<Option<GarbageCollector<K, V>>>>,
 }
 
@@ -62,12 +91,24 @@ where
     K: crate::QueryKey + 'static,
     V: crate::QueryValue + 'static,
 {
-    pub fn new(key: K) -> Self {
+    pub(crate) fn new(key: K, error_history_size: usize) -> Self {
         let query = Query {
-            key: key.clone(),
+            key,
             current_request: Rc::new(Cell::new(None)),
-            observers: Rc::new(RefCell::new(HashMap::new())),
+            cancel_token: Rc::new(RefCell::new(None)),
+            mocked: Rc::new(Cell::new(false)),
+            shut_down: Rc::new(Cell::new(false)),
+            disposed: Rc::new(Cell::new(false)),
+            execute_queued: Rc::new(Cell::new(false)),
+            refetch_pending: Rc::new(Cell::new(false)),
+            last_access: Rc::new(Cell::new(next_access())),
+            observers: Rc::new(RefCell::new(Vec::new())),
+            state_listeners: Rc::new(RefCell::new(SlotMap::with_key())),
             state: Rc::new(RefCell::new(QueryState::Created)),
+            fetch_source: Rc::new(Cell::new(None)),
+            data_version: Rc::new(Cell::new(0)),
+            errors: Rc::new(RefCell::new(VecDeque::new())),
+            error_history_size,
             garbage_collector: Rc::new(RefCell::new(None)),
         };
 
@@ -78,27 +119,147 @@ where
         query
     }
 
+    /// Sets the initial data, without notifying anyone.
+    /// Only valid before the query is added to the cache.
+    pub(crate) fn seed(&self, data: QueryData<V>) {
+        *self.state.borrow_mut() = QueryState::Loaded(data);
+        self.fetch_source.set(Some(FetchSource::Initial));
+    }
+
+    /// Sets the state and where it came from, without notifying.
+    /// Only valid before the query is added to the cache.
+    pub(crate) fn seed_state(&self, state: QueryState<V>, source: Option<FetchSource>) {
+        *self.state.borrow_mut() = state;
+        self.fetch_source.set(source);
+    }
+
+    /// Sets the state of the query and notifies all observers.
+    /// If the new state is [`QueryState::Invalid`], the query is refetched.
     pub fn set_state(&self, state: QueryState<V>) {
-        // Notify observers.
+        let invalid = matches!(state, QueryState::Invalid(_));
+
+        self.set_state_without_refetch(state);
+
+        if invalid {
+            // A fetch would change the state while frozen, so the refetch waits as well.
+            let cache = use_query_client().cache;
+            if cache.is_frozen() {
+                let query = self.clone();
+                cache.defer(move || query.execute());
+            } else {
+                self.execute();
+            }
+        }
+    }
+
+    /// Updates the state and notifies observers, without refetching invalid data.
+    pub(crate) fn set_state_without_refetch(&self, state: QueryState<V>) {
+        self.update_state_with(state, false)
+    }
+
+    /// Updates the state whose data is unchanged, e.g. when a refetch starts.
+    /// Observers are notified, but views of the data don't need to update, see [`Query::data_version`].
+    pub(crate) fn set_state_keeping_data(&self, state: QueryState<V>) {
+        self.update_state_with(state, true)
+    }
+
+    /// Changes whenever the data may have changed.
+    pub(crate) fn data_version(&self) -> u64 {
+        self.data_version.get()
+    }
+
+    fn update_state_with(&self, state: QueryState<V>, data_kept: bool) {
+        // An evicted query is no longer part of the cache, e.g. a request that completes after the eviction.
+        // Its observers may belong to a disposed owner.
+        if self.disposed.get() {
+            return;
+        }
+
+        // While the cache is frozen, only the state is updated. Notifications are sent once it's unfrozen.
+        let cache = use_query_client().cache;
+        if cache.is_frozen() {
+            if !data_kept {
+                self.data_version.set(self.data_version.get() + 1);
+            }
+            *self.state.borrow_mut() = state;
+            let query = self.clone();
+            cache.defer(move || query.notify_state());
+            return;
+        }
+
+        // Store first, observers may read the query while they're notified.
+        *self.state.borrow_mut() = state.clone();
+        if !data_kept {
+            self.data_version.set(self.data_version.get() + 1);
+        }
+
+        // Notify observers, in subscription order.
         let observers = self.observers.try_borrow().expect("set state borrow");
-        for observer in observers.values() {
+        for (_, observer) in observers.iter() {
             observer.notify(state.clone())
         }
+        drop(observers);
+        self.notify_state_listeners(&state);
 
-        let invalid = matches!(state, QueryState::Invalid(_));
+        // Notify cache. This has to be at the end due to sending the entire query in the notif.
+        use_query_client()
+            .cache
+            .notify(CacheNotification::UpdatedState(self.clone()));
+    }
 
-        *self.state.borrow_mut() = state;
+    fn notify_state(&self) {
+        if self.disposed.get() {
+            return;
+        }
+        let state = self.get_state();
+        let observers = self.observers.try_borrow().expect("notify state borrow");
+        for (_, observer) in observers.iter() {
+            observer.notify(state.clone())
+        }
+        drop(observers);
+        self.notify_state_listeners(&state);
 
-        // Notify cache. This has to be at the end due to sending the entire query in the notif.
         use_query_client()
             .cache
             .notify(CacheNotification::UpdatedState(self.clone()));
+    }
 
-        if invalid {
-            self.execute();
+    fn notify_state_listeners(&self, state: &QueryState<V>) {
+        let listeners = self
+            .state_listeners
+            .try_borrow()
+            .expect("notify state listeners borrow");
+        for listener in listeners.values() {
+            listener(state);
         }
     }
 
+    /// Listens for state changes, without subscribing as an observer.
+    /// Listeners are dropped once the query is evicted.
+    pub(crate) fn add_state_listener(
+        &self,
+        listener: impl Fn(&QueryState<V>) + 'static,
+    ) -> ListenerKey {
+        self.state_listeners
+            .try_borrow_mut()
+            .expect("add_state_listener borrow_mut")
+            .insert(Box::new(listener))
+    }
+
+    pub(crate) fn remove_state_listener(&self, key: ListenerKey) {
+        self.state_listeners
+            .try_borrow_mut()
+            .expect("remove_state_listener borrow_mut")
+            .remove(key);
+    }
+
+    /// Sets the state, recording where the new data came from.
+    pub(crate) fn set_state_from(&self, state: QueryState<V>, source: FetchSource) {
+        self.set_fetch_source(Some(source));
+        self.set_state(state);
+    }
+
+    /// Updates the state of the query in place and notifies all observers.
     pub fn update_state(&self, update_fn: impl FnOnce(&mut QueryState<V>)) {
         let mut state = self.state.take();
         update_fn(&mut state);
@@ -128,20 +289,35 @@ where
     }
 
     /// Marks the resource as invalid, which will cause it to be refetched on next read.
+    /// If a request is in flight, its data may already be outdated, so it is invalidated once it arrives.
+    /// Mocked queries are never invalidated.
     pub fn mark_invalid(&self) -> bool {
-        let mut updated = false;
-        self.maybe_map_state(|state| {
-            if let QueryState::Loaded(data) = state {
-                updated = true;
-                Ok(QueryState::Invalid(data))
-            } else {
-                Err(state)
+        if self.is_mocked() {
+            return false;
+        }
+        if self.is_fetching() {
+            self.refetch_pending.set(true);
+            return true;
+        }
+        match self.state.take() {
+            QueryState::Loaded(data) => {
+                let invalid = QueryState::Invalid(data);
+                if self.observers.borrow().is_empty() {
+                    // Nothing can fetch an unobserved query, it is refetched once an observer mounts.
+                    self.set_state_without_refetch(invalid);
+                } else {
+                    self.set_state(invalid);
+                }
+                true
             }
-        });
-        updated
+            state => {
+                *self.state.borrow_mut() = state;
+                false
+            }
+        }
     }
 
-    pub fn subscribe(&self, observer: &QueryObserver<K, V>) {
+    pub(crate) fn subscribe(&self, observer: &QueryObserver<K, V>) {
         let observer_id = observer.get_id();
         let mut observers = self
             .observers
@@ -149,8 +325,10 @@ where
             .expect("subscribe borrow_mut");
 
         // Check if the observer is already subscribed to avoid duplicate subscriptions
-        if let std::collections::hash_map::Entry::Vacant(e) = observers.entry(observer_id) {
-            e.insert(observer.clone());
+        if !observers.iter().any(|(id, _)| *id == observer_id) {
+            observers.push((observer_id, observer.clone()));
+            // Cache observers may read the observers of this query.
+            drop(observers);
             self.disable_gc();
             self.update_gc_time(observer.get_options().gc_time);
 
@@ -162,27 +340,42 @@ where
                         options: observer.get_options().clone(),
                     },
                 ));
+
+            // Late subscribers don't miss a request that is already in flight.
+            // Otherwise the observer is up to date until the next state change.
+            if self.is_fetching() {
+                observer.notify(self.get_state());
+            }
         }
     }
 
-    pub fn unsubscribe(&self, observer: &QueryObserver<K, V>) {
+    pub(crate) fn unsubscribe(&self, observer: &QueryObserver<K, V>) {
         let mut observers = self
             .observers
             .try_borrow_mut()
             .expect("unsubscribe borrow_mut");
-        if observers.remove(&observer.get_id()).is_some() {
+        let observer_id = observer.get_id();
+        let removed = observers
+            .iter()
+            .position(|(id, _)| *id == observer_id)
+            .map(|index| observers.remove(index))
+            .is_some();
+        let is_empty = observers.is_empty();
+        // Cache observers may read the observers of this query.
+        drop(observers);
+
+        if removed {
             use_query_client()
                 .cache
                 .notify::<K, V>(CacheNotification::ObserverRemoved(self.key.clone()))
         }
 
-        if observers.is_empty() {
-            drop(observers);
+        if is_empty {
             self.enable_gc();
         }
     }
 
-    pub fn update_gc_time(&self, gc_time: Option<Duration>) {
+    pub(crate) fn update_gc_time(&self, gc_time: Option<Duration>) {
         self.garbage_collector
             .borrow()
             .as_ref()
@@ -190,7 +383,10 @@ where
             .update_gc_time(gc_time);
     }
 
-    pub fn enable_gc(&self) {
+    pub(crate) fn enable_gc(&self) {
+        if self.shut_down.get() {
+            return;
+        }
         self.garbage_collector
             .borrow()
             .as_ref()
@@ -198,7 +394,7 @@ where
             .enable_gc();
     }
 
-    pub fn disable_gc(&self) {
+    pub(crate) fn disable_gc(&self) {
         self.garbage_collector
             .borrow()
             .as_ref()
@@ -206,37 +402,125 @@ where
             .disable_gc();
     }
 
+    /// Whether the query has no observers and its gc time has elapsed, i.e. it should have been evicted.
+    pub(crate) fn is_gc_overdue(&self) -> bool {
+        self.observers.borrow().is_empty()
+            && self
+                .garbage_collector
+                .borrow()
+                .as_ref()
+                .is_some_and(|gc| gc.is_overdue())
+    }
+
+    /// Returns a clone of the current state.
     pub fn get_state(&self) -> QueryState<V> {
         self.state.borrow().clone()
     }
 
-    // Useful to avoid clones.
+    /// Marks the query as accessed, so it's evicted last when the cache is full.
+    pub(crate) fn touch(&self) {
+        self.last_access.set(next_access());
+    }
+
+    pub(crate) fn last_access(&self) -> u64 {
+        self.last_access.get()
+    }
+
+    /// Reads the current state without cloning it. Useful to avoid clones.
     pub fn with_state<T>(&self, func: impl FnOnce(&QueryState<V>) -> T) -> T {
         let state = self.state.borrow();
         func(&state)
     }
 
-    /**
+    /*
      * Execution and Cancellation.
      */
 
+    /// Fetches the query using the fetcher of its most recent observer, ignoring cache only and disabled observers.
+    /// Does nothing if the query has no observer with a fetcher, or a request is already in flight,
+    /// unless an observer sets [`cancel_previous_on_refetch`](crate::QueryOptions::cancel_previous_on_refetch).
     pub fn execute(&self) {
         let observers = self.observers.try_borrow().expect("execute borrow");
-        let fetcher = observers.values().find_map(|f| f.get_fetcher());
+        // Prefer the most recent observer, its fetcher has the most up to date captures.
+        let fetcher = observers
+            .iter()
+            .filter(|(_, observer)| observer.get_options().fetches())
+            .filter_map(|(id, observer)| {
+                let infallible = observer.is_infallible();
+                observer
+                    .get_fetcher()
+                    .map(|fetcher| (id, fetcher, infallible))
+            })
+            .max_by_key(|(id, _, _)| **id)
+            .map(|(_, fetcher, infallible)| (fetcher, infallible));
 
-        if let Some(fetcher) = fetcher {
-            if !query_is_suppressed() {
-                spawn_local(execute_query(self.clone(), move |k| fetcher(k)));
+        if let Some((fetcher, infallible)) = fetcher {
+            // Requests would fail while offline, they are made once back online instead.
+            if !query_is_suppressed() && use_query_client().online.get_untracked() {
+                // Latest wins, replace the in-flight request instead of dropping this execution.
+                if self.is_fetching() && self.any_observer_option(|o| o.cancel_previous_on_refetch)
+                {
+                    self.cancel();
+                }
+                spawn_local(execute_with_dedup(
+                    self.clone(),
+                    move |k| fetcher(k),
+                    infallible,
+                ));
             }
         }
     }
 
+    /// Executes the query when the network connection is restored,
+    /// if it has no data, e.g. because the execution was skipped while offline,
+    /// or if it is stale for an observer that enabled [`refetch_on_reconnect`](crate::QueryOptions::refetch_on_reconnect).
+    pub(crate) fn execute_on_reconnect(&self) {
+        let refetch = {
+            let observers = self.observers.borrow();
+            !observers.is_empty()
+                && (self.needs_initial_execute()
+                    || observers.iter().any(|(_, observer)| {
+                        observer.get_options().refetch_on_reconnect && observer.is_stale(self)
+                    }))
+        };
+        if refetch {
+            self.execute();
+        }
+    }
+
+    /// Executes the query again, e.g. after it failed.
+    /// A request that is waiting to retry is replaced, so the new request starts right away instead of after the remaining retry delay.
+    pub(crate) fn retry(&self) {
+        if self.is_fetching() {
+            self.cancel();
+        }
+        self.execute();
+    }
+
+    /// Executes the query once the current task completes.
+    /// Executions requested in the same tick are coalesced, e.g. by observers that mount together,
+    /// so the most recent fetcher is used regardless of mount order.
+    pub(crate) fn execute_coalesced(&self) {
+        if self.execute_queued.replace(true) {
+            return;
+        }
+        let query = self.clone();
+        crate::use_query::queue_microtask(move || {
+            query.execute_queued.set(false);
+            query.execute();
+        });
+    }
+
     // Only scenario where two requests can exist at the same time is the first is cancelled.
-    pub fn new_execution(&self) -> Option<oneshot::Receiver<()>> {
+    pub(crate) fn new_execution(&self) -> Option<oneshot::Receiver<()>> {
+        if self.is_mocked() || self.is_shut_down() {
+            return None;
+        }
         let current_request = self.current_request.take();
         if current_request.is_none() {
             let (sender, receiver) = oneshot::channel();
             self.current_request.set(Some(sender));
+            *self.cancel_token.borrow_mut() = Some(CancelToken::new());
             Some(receiver)
         } else {
             self.current_request.set(current_request);
@@ -244,11 +528,84 @@ where
         }
     }
 
-    pub fn finalize_execution(&self) {
+    /// Whether the data was fetched within `ttl`. Invalid data never counts as just fetched.
+    pub(crate) fn is_recently_fetched(&self, ttl: Duration) -> bool {
+        self.get_fetch_source() == Some(FetchSource::Network)
+            && self.with_state(|state| match state {
+                QueryState::Loaded(data) => !time_until_stale(data.updated_at, ttl).is_zero(),
+                _ => false,
+            })
+    }
+
+    /// Whether an observer provides a fetcher, i.e. the query can be executed.
+    pub(crate) fn has_fetcher(&self) -> bool {
+        self.observers.borrow().iter().any(|(_, observer)| {
+            observer.get_options().fetches() && observer.get_fetcher().is_some()
+        })
+    }
+
+    /// Replaces the fetcher of the observers that provide one.
+    pub(crate) fn set_fetcher<F, Fu>(&self, fetcher: F)
+    where
+        F: Fn(K) -> Fu + 'static,
+        Fu: Future<Output = Result<V, crate::QueryError>> + 'static,
+    {
+        let fetcher = Rc::new(fetcher);
+        let observers = self.observers.try_borrow().expect("set fetcher borrow");
+        for (_, observer) in observers.iter() {
+            if observer.get_fetcher().is_some() {
+                let fetcher = fetcher.clone();
+                observer.set_fetcher(move |key| fetcher(key));
+            }
+        }
+    }
+
+    /// Whether a request is currently in flight for this query.
+    pub fn is_fetching(&self) -> bool {
+        let current_request = self.current_request.take();
+        let is_fetching = current_request.is_some();
+        self.current_request.set(current_request);
+        is_fetching
+    }
+
+    // Keeps the last good data, if any.
+    fn set_error(&self, error: crate::QueryError) {
+        {
+            let mut errors = self.errors.borrow_mut();
+            errors.push_back((crate::Instant::now(), error.clone()));
+            while errors.len() > self.error_history_size {
+                errors.pop_front();
+            }
+        }
+        let data = self.with_state(|state| state.query_data().cloned());
+        self.set_state(QueryState::Error { data, error });
+    }
+
+    pub(crate) fn finalize_execution(&self) {
         self.current_request.set(None);
+        self.cancel_token.borrow_mut().take();
+    }
+
+    /// Whether the data is pinned by [`QueryClient::mock_query()`](crate::QueryClient::mock_query).
+    pub fn is_mocked(&self) -> bool {
+        self.mocked.get()
+    }
+
+    pub(crate) fn set_mocked(&self, mocked: bool) {
+        self.mocked.set(mocked);
+    }
+
+    /// The cancel token of the in-flight request, if any.
+    pub fn get_cancel_token(&self) -> Option<CancelToken> {
+        self.cancel_token.borrow().clone()
     }
 
+    /// Cancels the in-flight request, if any.
+    /// Returns true if a request was cancelled.
     pub fn cancel(&self) -> bool {
+        if let Some(token) = self.cancel_token.borrow_mut().take() {
+            token.cancel();
+        }
         if let Some(current_request) = self.current_request.take() {
             let cancellation = current_request.send(());
             if cancellation.is_err() {
@@ -260,25 +617,95 @@ where
         }
     }
 
+    /// Cancels the in-flight request and clears all timers, once the owner of the client is disposed.
+    /// A cancelled request no longer touches the cache, and the query is never executed again.
+    pub(crate) fn shut_down(&self) {
+        self.shut_down.set(true);
+        self.cancel();
+        self.disable_gc();
+        for (_, observer) in self.observers.borrow().iter() {
+            observer.clear_refetch_interval();
+        }
+    }
+
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.shut_down.get()
+    }
+
+    /// Whether the query has no data, is invalid, or is stale.
     pub fn needs_execute(&self) -> bool {
-        self.with_state(|s| matches!(s, QueryState::Created))
-            || self.with_state(|s| matches!(s, QueryState::Invalid(_)))
-            || self.is_stale()
+        self.needs_initial_execute() || self.is_stale()
     }
 
+    /// Whether a prefetch should fetch the query.
+    /// Without observers, e.g. a query only ever prefetched, staleness is decided by the default stale time.
+    #[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+    pub(crate) fn needs_prefetch(&self, default_stale_time: Option<Duration>) -> bool {
+        if self.observer_count() == 0 {
+            self.needs_initial_execute() || self.is_stale_for(default_stale_time)
+        } else {
+            self.needs_execute()
+        }
+    }
+
+    /// Whether the query has no valid data, regardless of staleness.
+    pub(crate) fn needs_initial_execute(&self) -> bool {
+        self.with_state(|s| {
+            matches!(
+                s,
+                QueryState::Created | QueryState::Invalid(_) | QueryState::Error { .. }
+            )
+        })
+    }
+
+    /// Executes the query if it [needs to](Self::needs_execute).
     pub fn ensure_execute(&self) {
         if self.needs_execute() {
             self.execute();
         }
     }
 
+    /// Whether the data is stale, using the minimum stale time of all observers.
+    /// Observers with an isolated stale time are excluded, they decide staleness themselves.
     pub fn is_stale(&self) -> bool {
-        let stale_time = self
-            .observers
-            .borrow()
-            .iter()
-            .flat_map(|(_, o)| o.get_options().stale_time)
-            .min();
+        let (stale_time, jitter) = {
+            let observers = self.observers.borrow();
+            let shared = || {
+                observers
+                    .iter()
+                    .map(|(_, o)| o.get_options())
+                    .filter(|o| !o.isolated_stale_time)
+            };
+            (
+                shared().flat_map(|o| o.stale_time).min(),
+                shared().flat_map(|o| o.stale_time_jitter).max(),
+            )
+        };
+        self.is_stale_for(self.jittered_stale_time(stale_time, jitter))
+    }
+
+    /// Extends the stale time by a fraction of `jitter`, derived from the key.
+    /// Queries loaded together go stale at different times, while each deadline is stable across reads.
+    pub(crate) fn jittered_stale_time(
+        &self,
+        stale_time: Option<Duration>,
+        jitter: Option<Duration>,
+    ) -> Option<Duration> {
+        use std::hash::Hasher;
+
+        let (stale_time, jitter) = match (stale_time, jitter) {
+            (Some(stale_time), Some(jitter)) => (stale_time, jitter),
+            _ => return stale_time,
+        };
+        // DefaultHasher::new is unseeded, so the fraction is the same for every read.
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.key.hash(&mut hasher);
+        let fraction = hasher.finish() as f64 / u64::MAX as f64;
+        Some(stale_time + jitter.mul_f64(fraction))
+    }
+
+    /// Whether the query is stale according to the given stale time.
+    pub(crate) fn is_stale_for(&self, stale_time: Option<Duration>) -> bool {
         let updated_at = self.with_state(|s| s.updated_at());
 
         match (updated_at, stale_time) {
@@ -289,15 +716,128 @@ where
         }
     }
 
+    /// Overrides the timing options of the active observers and the gc time.
+    pub(crate) fn set_timing(&self, options: &crate::QueryOptions<V>) {
+        for (_, observer) in self.observers.borrow().iter() {
+            observer.set_timing(
+                options.stale_time,
+                options.gc_time,
+                options.refetch_interval,
+            );
+        }
+        self.garbage_collector
+            .borrow()
+            .as_ref()
+            .expect("set_timing borrow")
+            .set_gc_time(options.gc_time);
+    }
+
+    /// The options of the active observers, in subscription order.
+    pub(crate) fn observer_options(&self) -> Vec<crate::QueryOptions<V>> {
+        self.observers
+            .borrow()
+            .iter()
+            .map(|(_, o)| o.get_options().clone())
+            .collect()
+    }
+
+    /// The retries and retry delay of the most recent observer that fetches.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn retry_options(&self) -> (u32, Option<std::rc::Rc<dyn Fn(u32) -> Duration>>) {
+        self.observers
+            .borrow()
+            .iter()
+            .filter(|(_, o)| o.get_options().fetches())
+            .max_by_key(|(id, _)| *id)
+            .map(|(_, o)| {
+                let options = o.get_options();
+                (options.retry.unwrap_or(0), options.retry_delay.clone())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The cached value, if the fetched data equals it according to the
+    /// [`structural_sharing`](crate::QueryOptions::structural_sharing) of the most recent observer that sets it.
+    pub(crate) fn share_structure(&self, data: &V) -> Option<V> {
+        let equal = self
+            .observers
+            .borrow()
+            .iter()
+            .filter_map(|(id, o)| o.get_options().structural_sharing.clone().map(|f| (id, f)))
+            .max_by_key(|(id, _)| *id)
+            .map(|(_, f)| f);
+        equal.and_then(|equal| {
+            self.with_state(|state| {
+                state
+                    .query_data()
+                    .filter(|cached| equal(&cached.data, data))
+                    .map(|cached| cached.data.clone())
+            })
+        })
+    }
+
+    /// The fetch timeout of the most recent observer that fetches.
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.observers
+            .borrow()
+            .iter()
+            .filter(|(_, o)| o.get_options().fetches())
+            .max_by_key(|(id, _)| *id)
+            .and_then(|(_, o)| o.get_options().timeout)
+    }
+
+    /// Whether any observer has the given option enabled.
+    pub(crate) fn any_observer_option(
+        &self,
+        option: impl Fn(&crate::QueryOptions<V>) -> bool,
+    ) -> bool {
+        self.observers
+            .borrow()
+            .iter()
+            .any(|(_, o)| option(&o.get_options()))
+    }
+
+    /// The time the current data was last updated, if present.
     pub fn get_updated_at(&self) -> Option<crate::Instant> {
         self.with_state(|s| s.updated_at())
     }
 
+    /// The errors of the most recent failed fetches with the time they occurred, oldest first.
+    /// Bounded by [`QueryClientConfig::error_history_size`](crate::QueryClientConfig::error_history_size), older errors are dropped.
+    pub fn get_error_history(&self) -> Vec<(crate::Instant, crate::QueryError)> {
+        self.errors.borrow().iter().cloned().collect()
+    }
+
+    /// Where the current data came from, if present.
+    pub fn get_fetch_source(&self) -> Option<FetchSource> {
+        self.fetch_source.get()
+    }
+
+    pub(crate) fn set_fetch_source(&self, source: Option<FetchSource>) {
+        self.fetch_source.set(source);
+    }
+
+    /// The key of the query.
     pub fn get_key(&self) -> &K {
         &self.key
     }
 
-    pub fn get_gc(&self) -> Option<GarbageCollector<K, V>> {
+    /// The number of active observers, e.g. mounted [`use_query()`](crate::use_query()) instances.
+    pub fn observer_count(&self) -> usize {
+        self.observers.borrow().len()
+    }
+
+    /// When the query is evicted from the cache once it has no observers.
+    /// None if it's never evicted.
+    pub fn evicts_at(&self) -> Option<crate::Instant> {
+        self.garbage_collector
+            .borrow()
+            .as_ref()
+            .and_then(|gc| gc.evicts_at())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn get_gc(&self) -> Option<GarbageCollector<K, V>> {
         self.garbage_collector.borrow().clone()
     }
 }
@@ -307,7 +847,10 @@ where
     K: crate::QueryKey + 'static,
     V: crate::QueryValue + 'static,
 {
-    pub fn dispose(&self) {
+    pub(crate) fn dispose(&self) {
+        self.disposed.set(true);
+        // No state changes follow, e.g. a wait for a request to settle is resolved.
+        self.state_listeners.take();
         #[cfg(debug_assertions)]
         if !self.observers.borrow().is_empty() {
             logging::debug_warn!("Query has active observers");
@@ -315,62 +858,255 @@ where
     }
 }
 
+thread_local! {
+    static NEXT_ACCESS: Cell<u64> = const { Cell::new(0) };
+}
+
+// Increases on every access, unlike a timestamp that may repeat within a millisecond.
+pub(crate) fn next_access() -> u64 {
+    NEXT_ACCESS.with(|access| {
+        let next = access.get() + 1;
+        access.set(next);
+        next
+    })
+}
+
+// Only used to prefetch on the client.
+#[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
 pub async fn execute_query<K, V, Fu>(query: Query<K, V>, fetcher: impl Fn(K) -> Fu)
 where
     K: crate::QueryKey + 'static,
     V: crate::QueryValue + 'static,
     Fu: Future<Output = V>,
+{
+    use futures::FutureExt;
+
+    execute_with_dedup(query, move |key| fetcher(key).map(Ok), true).await
+}
+
+pub async fn execute_fallible_query<K, V, Fu>(query: Query<K, V>, fetcher: impl Fn(K) -> Fu)
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    Fu: Future<Output = Result<V, crate::QueryError>>,
+{
+    execute_with_dedup(query, fetcher, false).await
+}
+
+async fn execute_with_dedup<K, V, Fu>(
+    query: Query<K, V>,
+    fetcher: impl Fn(K) -> Fu,
+    infallible: bool,
+) where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    Fu: Future<Output = Result<V, crate::QueryError>>,
+{
+    let dedup_ttl = use_query_client().config.dedup_ttl;
+    if dedup_ttl.is_some_and(|ttl| query.is_recently_fetched(ttl)) {
+        return;
+    }
+
+    execute_without_dedup(query, fetcher, infallible).await
+}
+
+// Fetches even if the data was just fetched, e.g. to load another page.
+// An `infallible` fetcher can only time out, which reverts the state like a cancellation instead of failing the query.
+pub(crate) async fn execute_without_dedup<K, V, Fu>(
+    query: Query<K, V>,
+    fetcher: impl Fn(K) -> Fu,
+    infallible: bool,
+) where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    Fu: Future<Output = Result<V, crate::QueryError>>,
 {
     if !crate::query_is_suppressed() {
         match query.new_execution() {
             None => {}
             Some(cancellation) => {
+                let token = query.get_cancel_token();
                 match query.get_state() {
                     // First load.
-                    QueryState::Created => {
+                    // Loading if a cancelled request has not reverted the state yet.
+                    // Or retrying a first load that failed.
+                    QueryState::Created
+                    | QueryState::Loading
+                    | QueryState::Error { data: None, .. } => {
                         query.set_state(QueryState::Loading);
-                        let fetch = std::pin::pin!(fetcher(query.key.clone()));
-                        match execute_with_cancellation(fetch, cancellation).await {
-                            Ok(data) => {
+                        // Owned by the cancellation, so it's dropped before the state is reverted.
+                        let fetch = Box::pin(fetch_with_retry(&query, &fetcher, token.clone()));
+                        match execute_unless_cancelled(fetch, cancellation, token).await {
+                            // Timed out, nothing was loaded.
+                            Ok(Err(_)) if infallible => query.set_state(QueryState::Created),
+                            Ok(Err(error)) => query.set_error(error),
+                            Ok(Ok(data)) => {
                                 let data = QueryData::now(data);
-                                query.set_state(QueryState::Loaded(data));
+                                query
+                                    .set_state_from(QueryState::Loaded(data), FetchSource::Network);
                             }
+                            // A newer request replaced this one, and owns the state now.
+                            // Or the client is gone, and the state no longer matters.
+                            Err(_) if query.is_fetching() || query.is_shut_down() => return,
                             Err(_) => {
-                                query.set_state(QueryState::Created);
+                                // Unless a newer request already loaded it.
+                                if query.with_state(|state| matches!(state, QueryState::Loading)) {
+                                    query.set_state(QueryState::Created);
+                                }
                             }
                         }
                     }
                     // Subsequent loads.
-                    QueryState::Loaded(data) | QueryState::Invalid(data) => {
-                        query.set_state(QueryState::Fetching(data));
-                        let fetch = std::pin::pin!(fetcher(query.key.clone()));
-                        match execute_with_cancellation(fetch, cancellation).await {
-                            Ok(data) => {
-                                let data = QueryData::now(data);
-                                query.set_state(QueryState::Loaded(data));
+                    // Fetching if a cancelled request has not reverted the state yet.
+                    QueryState::Loaded(data)
+                    | QueryState::Invalid(data)
+                    | QueryState::Fetching(data)
+                    | QueryState::Error {
+                        data: Some(data), ..
+                    } => {
+                        query.set_state_keeping_data(QueryState::Fetching(data));
+                        // Owned by the cancellation, so it's dropped before the state is reverted.
+                        let fetch = Box::pin(fetch_with_retry(&query, &fetcher, token.clone()));
+                        match execute_unless_cancelled(fetch, cancellation, token).await {
+                            Ok(Err(error)) => {
+                                let invalidate_on_error =
+                                    query.any_observer_option(|o| o.invalidate_on_error);
+                                let data = query.with_state(|state| state.query_data().cloned());
+                                match data {
+                                    // Avoid refetching immediately, the next read will retry.
+                                    Some(data) if invalidate_on_error => {
+                                        query.set_state_without_refetch(QueryState::Invalid(data));
+                                    }
+                                    // Timed out, keep the latest data as is.
+                                    Some(data) if infallible => {
+                                        query.set_state(QueryState::Loaded(data));
+                                    }
+                                    _ => query.set_error(error),
+                                }
                             }
+                            Ok(Ok(data)) => match query.share_structure(&data) {
+                                // Only the update time changes.
+                                Some(cached) => {
+                                    query.set_fetch_source(Some(FetchSource::Network));
+                                    query.set_state_keeping_data(QueryState::Loaded(
+                                        QueryData::now(cached),
+                                    ));
+                                }
+                                None => {
+                                    let data = QueryData::now(data);
+                                    query.set_state_from(
+                                        QueryState::Loaded(data),
+                                        FetchSource::Network,
+                                    );
+                                }
+                            },
+                            // A newer request replaced this one, and owns the state now.
+                            // Or the client is gone, and the state no longer matters.
+                            Err(_) if query.is_fetching() || query.is_shut_down() => return,
                             Err(_) => {
-                                query.maybe_map_state(|state| {
-                                    if let QueryState::Fetching(data) = state {
-                                        Ok(QueryState::Loaded(data))
-                                    } else {
-                                        Err(state)
+                                let current_state = query.state.take();
+                                match current_state {
+                                    QueryState::Fetching(data) => {
+                                        query.set_state(QueryState::Loaded(data));
+                                    }
+                                    state => {
+                                        *query.state.borrow_mut() = state;
                                     }
-                                });
+                                }
                             }
                         }
                     }
-                    QueryState::Loading | QueryState::Fetching(_) => {
-                        logging::debug_warn!("Query is already loading, this is likely a bug.");
-                        debug_assert!(false, "Query is already loading, this is likely a bug.");
-                    }
                 }
                 query.finalize_execution();
+                if query.refetch_pending.take() {
+                    query.mark_invalid();
+                }
             }
         }
     }
 }
 
+// Retries failed fetches with the options of the most recent observer.
+// Each attempt has its own timeout, a timed out attempt is retried like a failed one.
+// Runs within the cancellation, so a cancelled request also stops waiting for a retry.
+async fn fetch_with_retry<K, V, Fu>(
+    query: &Query<K, V>,
+    fetcher: &impl Fn(K) -> Fu,
+    token: Option<CancelToken>,
+) -> Result<V, crate::QueryError>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    Fu: Future<Output = Result<V, crate::QueryError>>,
+{
+    let (retries, retry_delay) = query.retry_options();
+    let timeout = query.timeout();
+    let mut attempt = 0;
+    loop {
+        let result = fetch_with_timeout(fetcher(query.key.clone()), timeout).await;
+        if result.is_ok()
+            || attempt >= retries
+            || token.as_ref().is_some_and(|token| token.is_cancelled())
+        {
+            return result;
+        }
+        attempt += 1;
+        let delay = match retry_delay.as_ref() {
+            Some(retry_delay) => retry_delay(attempt),
+            None => default_retry_delay(attempt),
+        };
+        crate::use_query::sleep(delay).await;
+        // Cancelled while waiting to retry.
+        if token.as_ref().is_some_and(|token| token.is_cancelled()) {
+            return result;
+        }
+    }
+}
+
+// Fails once the timeout elapses, dropping the fetch.
+// Runs within the cancellation, so a cancelled request also clears the timeout.
+async fn fetch_with_timeout<V, Fu>(
+    fetch: Fu,
+    timeout: Option<Duration>,
+) -> Result<V, crate::QueryError>
+where
+    Fu: Future<Output = Result<V, crate::QueryError>>,
+{
+    use futures::future::Either;
+
+    let Some(timeout) = timeout else {
+        return fetch.await;
+    };
+    let timer = crate::use_query::sleep(timeout);
+    match futures::future::select(Box::pin(fetch), Box::pin(timer)).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(crate::QueryError::new(crate::QueryTimeout(timeout))),
+    }
+}
+
+pub(crate) fn default_retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1)
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(Duration::from_secs(30))
+}
+
+// The result of a cancelled request is discarded, even if it completed, e.g. on the server where requests can't be interrupted.
+async fn execute_unless_cancelled<V, Fu>(
+    fut: Fu,
+    cancellation: oneshot::Receiver<()>,
+    token: Option<CancelToken>,
+) -> Result<V, ()>
+where
+    Fu: std::future::Future<Output = V> + Unpin,
+{
+    let result = execute_with_cancellation(fut, cancellation).await;
+    if token.is_some_and(|token| token.is_cancelled()) {
+        Err(())
+    } else {
+        result
+    }
+}
+
 #[cfg(any(feature = "hydrate", feature = "csr"))]
 async fn execute_with_cancellation<V, Fu>(
     fut: Fu,
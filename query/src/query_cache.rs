@@ -1,7 +1,8 @@
 use std::{
     any::{Any, TypeId},
-    cell::RefCell,
-    collections::{hash_map::Entry, HashMap},
+    cell::{Cell, RefCell},
+    collections::{hash_map::Entry, HashMap, HashSet},
+    marker::PhantomData,
     rc::Rc,
 };
 
@@ -10,9 +11,10 @@ use slotmap::SlotMap;
 
 use crate::{
     cache_observer::{CacheEvent, CacheObserver},
+    checkpoint::{CacheSnapshot, Checkpoint},
     query::Query,
-    query_persister::QueryPersister,
-    QueryKey, QueryOptions, QueryValue,
+    query_persister::{PersistQueryData, QueryPersister},
+    CacheHasher, FetchSource, QueryData, QueryKey, QueryOptions, QueryState, QueryValue,
 };
 
 #[derive(Clone)]
@@ -23,17 +25,49 @@ pub struct QueryCache {
     #[allow(clippy::type_complexity)]
     observers: Rc<RefCell<SlotMap<CacheObserverKey, Box<dyn CacheObserver>>>>,
     persister: Rc<RefCell<Option<Rc<dyn QueryPersister>>>>,
+    #[allow(clippy::type_complexity)]
+    gc_evict_listeners: Rc<RefCell<SlotMap<GcEvictListenerKey, Rc<dyn Fn(String)>>>>,
+    #[allow(clippy::type_complexity)]
+    persister_error_listeners:
+        Rc<RefCell<SlotMap<PersisterErrorListenerKey, Rc<dyn Fn(String, SerializationError)>>>>,
+    // The first value type seen for each key type, to warn about keys used with multiple value types.
+    #[cfg(debug_assertions)]
+    value_types: Rc<RefCell<HashMap<TypeId, &'static str>>>,
+    // Nesting depth of frozen sections, and the notifications deferred until they end.
+    frozen: Rc<Cell<usize>>,
+    #[allow(clippy::type_complexity)]
+    deferred: Rc<RefCell<Vec<Box<dyn FnOnce()>>>>,
     size: RwSignal<usize>,
+    max_queries: Rc<Cell<Option<usize>>>,
+    error_history_size: Rc<Cell<usize>>,
+    hasher: CacheHasher,
 }
 
 slotmap::new_key_type! {
+    /// Identifies a callback registered with [`QueryClient::subscribe_events()`](crate::QueryClient::subscribe_events).
     pub struct CacheObserverKey;
+    pub struct GcEvictListenerKey;
+    pub struct PersisterErrorListenerKey;
 }
 
-struct CacheEntry<K, V>(HashMap<K, Query<K, V>>);
+pub(crate) type QueryMap<K, V> = HashMap<K, Query<K, V>, CacheHasher>;
+
+struct CacheEntry<K, V>(QueryMap<K, V>);
 
 // Trait to enable cache introspection among distinct cache entry maps.
-trait CacheEntryTrait: CacheSize + CacheInvalidate + CacheClear + CacheUpdateObserver {
+trait CacheEntryTrait:
+    CacheSize
+    + CacheInvalidate
+    + CacheClear
+    + CacheShutDown
+    + CacheCancel
+    + CacheReconnect
+    + CacheLeastRecentlyUsed
+    + CacheHealth
+    + CacheCheckpoint
+    + CacheCollectOverdue
+    + CacheUpdateObserver
+{
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
@@ -96,6 +130,313 @@ where
     }
 }
 
+trait CacheShutDown {
+    fn shut_down(&self);
+}
+
+impl<K, V> CacheShutDown for CacheEntry<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn shut_down(&self) {
+        for (_, query) in self.0.iter() {
+            query.shut_down();
+        }
+    }
+}
+
+trait CacheCancel {
+    fn cancel_all(&self) -> usize;
+}
+
+impl<K, V> CacheCancel for CacheEntry<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn cancel_all(&self) -> usize {
+        self.0
+            .values()
+            .filter(|query| query.is_fetching())
+            .filter(|query| query.cancel())
+            .count()
+    }
+}
+
+trait CacheReconnect {
+    #[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+    fn refetch_on_reconnect(&self);
+}
+
+impl<K, V> CacheReconnect for CacheEntry<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn refetch_on_reconnect(&self) {
+        for query in self.0.values() {
+            query.execute_on_reconnect();
+        }
+    }
+}
+
+trait CacheHealth {
+    fn stale_count(&self) -> usize;
+    fn oldest_updated_at(&self) -> Option<crate::Instant>;
+}
+
+impl<K, V> CacheHealth for CacheEntry<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn stale_count(&self) -> usize {
+        self.0
+            .values()
+            .filter(|query| {
+                query.with_state(|s| matches!(s, crate::QueryState::Invalid(_))) || query.is_stale()
+            })
+            .count()
+    }
+
+    fn oldest_updated_at(&self) -> Option<crate::Instant> {
+        self.0
+            .values()
+            .filter_map(|query| query.get_updated_at())
+            .min()
+    }
+}
+
+trait CacheCheckpoint {
+    fn checkpoint(&self) -> Box<dyn CacheSnapshot>;
+    // Restoring it evicts the queries of this type, i.e. the type was first used since the checkpoint.
+    fn empty_snapshot(&self) -> Box<dyn CacheSnapshot>;
+}
+
+impl<K, V> CacheCheckpoint for CacheEntry<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn checkpoint(&self) -> Box<dyn CacheSnapshot> {
+        let mut snapshot = EntrySnapshot::<K, V>::default();
+        for (key, query) in self.0.iter() {
+            // Data that can't be serialized is left out, and left as is on restore.
+            match query.with_state(serialize_state) {
+                Some(state) => {
+                    snapshot
+                        .queries
+                        .insert(key.clone(), (state, query.get_fetch_source()));
+                }
+                None => {
+                    snapshot.skipped.insert(key.clone());
+                }
+            }
+        }
+        Box::new(snapshot)
+    }
+
+    fn empty_snapshot(&self) -> Box<dyn CacheSnapshot> {
+        Box::new(EntrySnapshot::<K, V>::default())
+    }
+}
+
+trait CacheCollectOverdue {
+    // Evictions are returned rather than applied, as evicting requires a mutable borrow of the cache.
+    #[allow(clippy::type_complexity)]
+    fn overdue_evictions(&self) -> Vec<Box<dyn FnOnce(&QueryCache) -> bool>>;
+}
+
+impl<K, V> CacheCollectOverdue for CacheEntry<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn overdue_evictions(&self) -> Vec<Box<dyn FnOnce(&QueryCache) -> bool>> {
+        self.0
+            .iter()
+            .filter(|(_, query)| query.is_gc_overdue())
+            .map(|(key, query)| {
+                // The late timeout must not evict a query recreated under the same key.
+                query.disable_gc();
+                let key = key.clone();
+                Box::new(move |cache: &QueryCache| cache.gc_evict_query::<K, V>(&key))
+                    as Box<dyn FnOnce(&QueryCache) -> bool>
+            })
+            .collect()
+    }
+}
+
+trait CacheLeastRecentlyUsed {
+    // The eviction is returned rather than applied, as evicting requires a mutable borrow of the cache.
+    #[allow(clippy::type_complexity)]
+    fn least_recently_used(
+        &self,
+        accessed_before: u64,
+    ) -> Option<(u64, Box<dyn FnOnce(&QueryCache) -> bool>)>;
+}
+
+impl<K, V> CacheLeastRecentlyUsed for CacheEntry<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn least_recently_used(
+        &self,
+        accessed_before: u64,
+    ) -> Option<(u64, Box<dyn FnOnce(&QueryCache) -> bool>)> {
+        self.0
+            .iter()
+            .filter(|(_, query)| query.observer_count() == 0)
+            .filter(|(_, query)| query.last_access() < accessed_before)
+            .min_by_key(|(_, query)| query.last_access())
+            .map(|(key, query)| {
+                let key = key.clone();
+                (
+                    query.last_access(),
+                    Box::new(move |cache: &QueryCache| cache.evict_query::<K, V>(&key))
+                        as Box<dyn FnOnce(&QueryCache) -> bool>,
+                )
+            })
+    }
+}
+
+// The data is kept serialized, so taking a snapshot costs about as much as persisting the cache.
+struct EntrySnapshot<K, V> {
+    queries: HashMap<K, (QueryState<String>, Option<FetchSource>)>,
+    skipped: HashSet<K>,
+    value: PhantomData<V>,
+}
+
+impl<K, V> Default for EntrySnapshot<K, V> {
+    fn default() -> Self {
+        Self {
+            queries: HashMap::new(),
+            skipped: HashSet::new(),
+            value: PhantomData,
+        }
+    }
+}
+
+// In-flight requests are not part of the snapshot. Returns None if the data fails to serialize.
+fn serialize_state<V>(state: &QueryState<V>) -> Option<QueryState<String>>
+where
+    V: QueryValue,
+{
+    let serialize = |data: &QueryData<V>| {
+        leptos::Serializable::ser(&data.data)
+            .ok()
+            .map(|serialized| QueryData {
+                data: serialized,
+                updated_at: data.updated_at,
+            })
+    };
+    Some(match state {
+        QueryState::Created | QueryState::Loading => QueryState::Created,
+        QueryState::Loaded(data) | QueryState::Fetching(data) => {
+            QueryState::Loaded(serialize(data)?)
+        }
+        QueryState::Invalid(data) => QueryState::Invalid(serialize(data)?),
+        QueryState::Error { data, error } => QueryState::Error {
+            data: match data {
+                Some(data) => Some(serialize(data)?),
+                None => None,
+            },
+            error: error.clone(),
+        },
+    })
+}
+
+// Returns None if the data fails to deserialize.
+fn deserialize_state<V>(state: &QueryState<String>) -> Option<QueryState<V>>
+where
+    V: QueryValue,
+{
+    let deserialize = |data: &QueryData<String>| {
+        leptos::Serializable::de(&data.data)
+            .ok()
+            .map(|deserialized| QueryData {
+                data: deserialized,
+                updated_at: data.updated_at,
+            })
+    };
+    Some(match state {
+        QueryState::Created | QueryState::Loading => QueryState::Created,
+        QueryState::Loaded(data) | QueryState::Fetching(data) => {
+            QueryState::Loaded(deserialize(data)?)
+        }
+        QueryState::Invalid(data) => QueryState::Invalid(deserialize(data)?),
+        QueryState::Error { data, error } => QueryState::Error {
+            data: match data {
+                Some(data) => Some(deserialize(data)?),
+                None => None,
+            },
+            error: error.clone(),
+        },
+    })
+}
+
+impl<K, V> CacheSnapshot for EntrySnapshot<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn restore(&self, query_cache: &QueryCache) {
+        // Observers are notified once the cache is no longer borrowed, as they may read it.
+        let (evicted, created, restored) = query_cache.use_cache::<K, V, _>(|cache| {
+            // Queries created since the checkpoint, unless they are still observed.
+            let evicted = cache
+                .iter()
+                .filter(|(key, query)| {
+                    !self.queries.contains_key(key)
+                        && !self.skipped.contains(key)
+                        && query.observer_count() == 0
+                })
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>()
+                .into_iter()
+                .filter_map(|key| cache.remove(&key))
+                .collect::<Vec<_>>();
+
+            let mut created = Vec::new();
+            let mut restored = Vec::new();
+            for (key, (state, source)) in self.queries.iter() {
+                // Data that fails to deserialize is left as is, like data that failed to serialize.
+                let Some(state) = deserialize_state::<V>(state) else {
+                    continue;
+                };
+                match cache.get(key) {
+                    Some(query) => restored.push((query.clone(), state, *source)),
+                    // Evicted since the checkpoint.
+                    None => {
+                        let query = with_owner(query_cache.owner, || {
+                            Query::new(key.clone(), query_cache.error_history_size())
+                        });
+                        query.seed_state(state, *source);
+                        cache.insert(key.clone(), query.clone());
+                        created.push(query);
+                    }
+                }
+            }
+
+            (evicted, created, restored)
+        });
+
+        for query in evicted {
+            query_cache.notify_query_eviction(query.get_key());
+            query.dispose();
+        }
+        for query in created {
+            query_cache.notify_new_query(query);
+        }
+        for (query, state, source) in restored {
+            query.set_fetch_source(source);
+            query.set_state_without_refetch(state);
+        }
+    }
+}
+
 // Update an observer with all existing cache entries, upon subscription.
 trait CacheUpdateObserver {
     fn update_observer(&self, observer: &dyn CacheObserver);
@@ -115,17 +456,50 @@ where
 }
 
 impl QueryCache {
-    pub fn new(owner: Owner) -> Self {
-        Self {
+    pub fn new(owner: Owner, hasher: CacheHasher) -> Self {
+        let cache = Self {
             owner,
             cache: Rc::new(RefCell::new(HashMap::new())),
             observers: Rc::new(RefCell::new(SlotMap::with_key())),
             size: RwSignal::new(0),
+            max_queries: Rc::new(Cell::new(None)),
+            error_history_size: Rc::new(Cell::new(0)),
             persister: Rc::new(RefCell::new(None)),
+            gc_evict_listeners: Rc::new(RefCell::new(SlotMap::with_key())),
+            persister_error_listeners: Rc::new(RefCell::new(SlotMap::with_key())),
+            #[cfg(debug_assertions)]
+            value_types: Rc::new(RefCell::new(HashMap::new())),
+            frozen: Rc::new(Cell::new(0)),
+            deferred: Rc::new(RefCell::new(Vec::new())),
+            hasher,
+        };
+
+        // In-flight requests may outlive the owner, e.g. on app teardown.
+        // Cancel them so they don't touch the disposed cache.
+        {
+            let cache = cache.clone();
+            with_owner(owner, move || {
+                on_cleanup(move || cache.shut_down_all_queries())
+            });
         }
+
+        cache
     }
 
     pub fn get_or_create_query<K, V>(&self, key: K) -> Query<K, V>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.get_or_create_query_with(key, || None)
+    }
+
+    // Initial data is only computed if the query is created.
+    pub fn get_or_create_query_with<K, V>(
+        &self,
+        key: K,
+        initial_data: impl FnOnce() -> Option<QueryData<V>>,
+    ) -> Query<K, V>
     where
         K: QueryKey + 'static,
         V: QueryValue + 'static,
@@ -135,27 +509,29 @@ impl QueryCache {
         let mut created = false;
 
         let query = self.use_cache(|cache| {
-            let entry = cache.entry(key.clone());
+            // Lookup by reference first, so the key is only cloned when a query is created.
+            if let Some(query) = cache.get(&key) {
+                query.touch();
+                return query.clone();
+            }
 
-            let query = match entry {
-                Entry::Occupied(entry) => {
-                    let entry = entry.into_mut();
-                    entry
-                }
-                Entry::Vacant(entry) => {
-                    let query = with_owner(query_cache.owner, || Query::new(key));
-                    query_cache.notify_new_query(query.clone());
-                    created = true;
-                    entry.insert(query)
-                }
-            };
-            query.clone()
+            let query = with_owner(query_cache.owner, || {
+                Query::new(key.clone(), query_cache.error_history_size())
+            });
+            if let Some(data) = initial_data() {
+                query.seed(data);
+            }
+            query_cache.notify_new_query(query.clone());
+            created = true;
+            cache.insert(key, query.clone());
+            query
         });
 
         #[cfg(any(feature = "hydrate", feature = "csr"))]
         if created {
             if let Some(persister) = self.persister.borrow().clone() {
                 let query = query.clone();
+                let query_cache = query_cache.clone();
                 spawn_local({
                     async move {
                         let key = crate::cache_observer::make_cache_key(query.get_key());
@@ -167,28 +543,7 @@ impl QueryCache {
                         }
 
                         if let Some(serialized) = result {
-                            match serialized.try_into() {
-                                Ok(data) => {
-                                    // If the query is currently fetching, then we should preserve the fetching state.
-                                    if query.with_state(|s| {
-                                        matches!(
-                                            s,
-                                            crate::QueryState::Loading
-                                                | crate::QueryState::Fetching(_)
-                                        )
-                                    }) {
-                                        query.set_state(crate::QueryState::Fetching(data));
-                                    } else {
-                                        query.set_state(crate::QueryState::Loaded(data));
-                                    }
-                                }
-                                Err(e) => {
-                                    logging::debug_warn!(
-                                        "Error deserializing query state: {:?}",
-                                        e
-                                    );
-                                }
-                            }
+                            query_cache.restore_persisted(&query, serialized);
                         }
                     }
                 });
@@ -198,20 +553,77 @@ impl QueryCache {
         // It's necessary to delay the size update until we are out of the borrow, to avoid borrow errors.
         if created {
             self.size.update(|size| *size += 1);
+            self.enforce_max_queries(query.last_access());
         }
 
         query
     }
 
-    pub fn get_query<K, V>(&self, key: &K) -> Option<Query<K, V>>
+    // Accepts a borrowed form of the key, e.g. &str for String keys, to avoid cloning the key for lookups.
+    pub fn get_query<K, V, Q>(&self, key: &Q) -> Option<Query<K, V>>
+    where
+        K: QueryKey + std::borrow::Borrow<Q> + 'static,
+        V: QueryValue + 'static,
+        Q: std::hash::Hash + Eq + ?Sized,
+    {
+        self.use_cache_option(move |cache| {
+            let query = cache.get(key)?;
+            query.touch();
+            Some(query.clone())
+        })
+    }
+
+    // Reads without subscribing an observer or touching the gc.
+    pub fn get_query_state<K, V>(&self, key: &K) -> Option<QueryState<V>>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.use_cache_option(move |cache: &QueryMap<K, V>| {
+            cache.get(key).map(|query| {
+                query.touch();
+                query.get_state()
+            })
+        })
+    }
+
+    // The state to restore after an optimistic update, None if the query does not exist.
+    pub fn snapshot_query<K, V>(&self, key: &K) -> Option<QueryState<V>>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.get_query_state(key)
+    }
+
+    // Notifies observers and the cache observers, e.g. the persister, without refetching.
+    // Creates the query if it was evicted since the snapshot.
+    pub fn restore_query<K, V>(&self, key: K, snapshot: QueryState<V>)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.get_or_create_query::<K, V>(key)
+            .set_state_without_refetch(snapshot);
+    }
+
+    pub fn get_query_data<K, V>(&self, key: &K) -> Option<V>
     where
         K: QueryKey + 'static,
         V: QueryValue + 'static,
     {
-        self.use_cache_option(move |cache| cache.get(key).cloned())
+        self.use_cache_option(move |cache: &QueryMap<K, V>| {
+            let query = cache.get(key)?;
+            query.touch();
+            query.with_state(|state| state.data().cloned())
+        })
     }
 
-    pub fn get_query_signal<K, V>(&self, key: impl Fn() -> K + 'static) -> Memo<Query<K, V>>
+    pub fn get_query_signal<K, V>(
+        &self,
+        key: impl Fn() -> K + 'static,
+        initial_data: impl Fn() -> Option<QueryData<V>> + 'static,
+    ) -> Memo<Query<K, V>>
     where
         K: QueryKey + 'static,
         V: QueryValue + 'static,
@@ -219,9 +631,11 @@ impl QueryCache {
         let client = self.clone();
 
         // This memo is crucial to avoid crazy amounts of lookups.
+        // The lookup only re-runs when a signal read by `key` changes, and since queries compare by key,
+        // dependents are only notified when the key itself changes. See `query_signal_does_not_churn`.
         create_memo(move |_| {
             let key = key();
-            client.get_or_create_query(key)
+            client.get_or_create_query_with(key, &initial_data)
         })
     }
 
@@ -265,6 +679,171 @@ impl QueryCache {
         }
     }
 
+    // Sets the data restored from the persister.
+    // Data that fails to deserialize, e.g. after a schema change, is reported to the persister error listeners.
+    #[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+    pub fn restore_persisted<K, V>(&self, query: &Query<K, V>, serialized: PersistQueryData)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        match serialized.try_into() {
+            Ok(data) => {
+                // If the query is currently fetching, then we should preserve the fetching state.
+                if query.with_state(|s| {
+                    matches!(
+                        s,
+                        crate::QueryState::Loading | crate::QueryState::Fetching(_)
+                    )
+                }) {
+                    query.set_state_from(
+                        crate::QueryState::Fetching(data),
+                        crate::FetchSource::Persister,
+                    );
+                } else {
+                    query.set_state_from(
+                        crate::QueryState::Loaded(data),
+                        crate::FetchSource::Persister,
+                    );
+                }
+            }
+            Err(e) => {
+                logging::debug_warn!("Error deserializing query state: {:?}", &e);
+                let listeners = self
+                    .persister_error_listeners
+                    .try_borrow()
+                    .expect("restore_persisted borrow")
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let key = crate::cache_observer::make_cache_key(query.get_key());
+                for listener in listeners {
+                    listener(key.clone(), e.clone());
+                }
+            }
+        }
+    }
+
+    pub fn add_persister_error_listener(
+        &self,
+        listener: impl Fn(String, SerializationError) + 'static,
+    ) -> PersisterErrorListenerKey {
+        self.persister_error_listeners
+            .try_borrow_mut()
+            .expect("add_persister_error_listener borrow mut")
+            .insert(Rc::new(listener))
+    }
+
+    pub fn remove_persister_error_listener(&self, key: PersisterErrorListenerKey) -> bool {
+        self.persister_error_listeners
+            .try_borrow_mut()
+            .expect("remove_persister_error_listener borrow mut")
+            .remove(key)
+            .is_some()
+    }
+
+    // Evicts a query whose gc time has elapsed, telling the gc evict listeners first.
+    pub fn gc_evict_query<K, V>(&self, key: &K) -> bool
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        let listeners = self
+            .gc_evict_listeners
+            .try_borrow()
+            .expect("gc_evict_query borrow")
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        if !listeners.is_empty() {
+            let cache_key = crate::cache_observer::make_cache_key(key);
+            for listener in listeners {
+                listener(cache_key.clone());
+            }
+        }
+        self.evict_query::<K, V>(key)
+    }
+
+    pub fn set_max_queries(&self, max_queries: Option<usize>) {
+        self.max_queries.set(max_queries);
+    }
+
+    pub fn set_error_history_size(&self, error_history_size: usize) {
+        self.error_history_size.set(error_history_size);
+    }
+
+    pub(crate) fn error_history_size(&self) -> usize {
+        self.error_history_size.get()
+    }
+
+    // Evicts the least recently accessed queries without observers, until the cache is within its limit.
+    // Queries accessed since `accessed_before`, e.g. the query that was just created, are kept.
+    fn enforce_max_queries(&self, accessed_before: u64) {
+        let Some(max_queries) = self.max_queries.get() else {
+            return;
+        };
+        while self.size.get_untracked() > max_queries {
+            let eviction = RefCell::try_borrow(&self.cache)
+                .expect("enforce_max_queries borrow")
+                .values()
+                .filter_map(|cache| cache.least_recently_used(accessed_before))
+                .min_by_key(|(last_access, _)| *last_access);
+            match eviction {
+                Some((_, evict)) => {
+                    evict(self);
+                }
+                // Every other query is observed.
+                None => break,
+            }
+        }
+    }
+
+    // Evicts the queries whose gc time has elapsed, but whose timeout hasn't fired yet.
+    pub fn collect_overdue_garbage(&self) -> usize {
+        let evictions = RefCell::try_borrow(&self.cache)
+            .expect("collect_overdue_garbage borrow")
+            .values()
+            .flat_map(|cache| cache.overdue_evictions())
+            .collect::<Vec<_>>();
+        evictions
+            .into_iter()
+            .map(|evict| evict(self))
+            .filter(|evicted| *evicted)
+            .count()
+    }
+
+    pub fn add_gc_evict_listener(&self, listener: impl Fn(String) + 'static) -> GcEvictListenerKey {
+        self.gc_evict_listeners
+            .try_borrow_mut()
+            .expect("add_gc_evict_listener borrow mut")
+            .insert(Rc::new(listener))
+    }
+
+    pub fn remove_gc_evict_listener(&self, key: GcEvictListenerKey) -> bool {
+        self.gc_evict_listeners
+            .try_borrow_mut()
+            .expect("remove_gc_evict_listener borrow mut")
+            .remove(key)
+            .is_some()
+    }
+
+    // Returns how many queries were invalidated, excluding those that were already invalid.
+    pub fn invalidate_queries_where<K, V>(&self, predicate: impl Fn(&K) -> bool) -> usize
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.use_cache_option(|cache: &QueryMap<K, V>| {
+            let invalidated = cache
+                .iter()
+                .filter(|(key, _)| predicate(key))
+                .filter(|(_, query)| query.mark_invalid())
+                .count();
+            Some(invalidated)
+        })
+        .unwrap_or_default()
+    }
+
     pub fn invalidate_all_queries(&self) {
         for cache in RefCell::try_borrow(&self.cache)
             .expect("invalidate_all_queries borrow")
@@ -274,6 +853,127 @@ impl QueryCache {
         }
     }
 
+    pub fn shut_down_all_queries(&self) {
+        for cache in RefCell::try_borrow(&self.cache)
+            .expect("shut_down_all_queries borrow")
+            .values()
+        {
+            cache.shut_down();
+        }
+    }
+
+    // Returns how many queries were updated, i.e. for which `update` returned true.
+    pub fn update_queries<K, V>(&self, update: impl Fn(&K, &mut QueryState<V>) -> bool) -> usize
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.use_cache_option(|cache: &QueryMap<K, V>| {
+            let updated = cache
+                .iter()
+                .filter(|(key, query)| {
+                    let previous_source = query.get_fetch_source();
+                    query.set_fetch_source(Some(FetchSource::Manual));
+                    let updated = query.maybe_map_state(|mut state| {
+                        if update(key, &mut state) {
+                            Ok(state)
+                        } else {
+                            Err(state)
+                        }
+                    });
+                    if !updated {
+                        query.set_fetch_source(previous_source);
+                    }
+                    updated
+                })
+                .count();
+            Some(updated)
+        })
+        .unwrap_or_default()
+    }
+
+    // Returns how many requests were cancelled.
+    pub fn cancel_queries_where<K, V>(&self, predicate: impl Fn(&K) -> bool) -> usize
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.use_cache_option(|cache: &QueryMap<K, V>| {
+            let cancelled = cache
+                .iter()
+                .filter(|(key, _)| predicate(key))
+                .filter(|(_, query)| query.cancel())
+                .count();
+            Some(cancelled)
+        })
+        .unwrap_or_default()
+    }
+
+    pub fn cancel_all_fetches(&self) -> usize {
+        RefCell::try_borrow(&self.cache)
+            .expect("cancel_all_fetches borrow")
+            .values()
+            .map(|cache| cache.cancel_all())
+            .sum()
+    }
+
+    // Only the client listens for the connection to be restored.
+    #[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+    pub fn refetch_on_reconnect(&self) {
+        for cache in RefCell::try_borrow(&self.cache)
+            .expect("refetch_on_reconnect borrow")
+            .values()
+        {
+            cache.refetch_on_reconnect();
+        }
+    }
+
+    pub fn stale_queries_count(&self) -> usize {
+        RefCell::try_borrow(&self.cache)
+            .expect("stale_queries_count borrow")
+            .values()
+            .map(|cache| cache.stale_count())
+            .sum()
+    }
+
+    pub fn oldest_updated_at(&self) -> Option<crate::Instant> {
+        RefCell::try_borrow(&self.cache)
+            .expect("oldest_updated_at borrow")
+            .values()
+            .filter_map(|cache| cache.oldest_updated_at())
+            .min()
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        let snapshots = RefCell::try_borrow(&self.cache)
+            .expect("checkpoint borrow")
+            .iter()
+            .map(|(type_key, cache)| (*type_key, cache.checkpoint()))
+            .collect();
+        Checkpoint { snapshots }
+    }
+
+    pub fn restore(&self, checkpoint: Checkpoint) {
+        // Types first used since the checkpoint are restored to an empty snapshot.
+        let emptied = RefCell::try_borrow(&self.cache)
+            .expect("restore borrow")
+            .iter()
+            .filter(|(type_key, _)| !checkpoint.snapshots.contains_key(type_key))
+            .map(|(_, cache)| cache.empty_snapshot())
+            .collect::<Vec<_>>();
+
+        for snapshot in emptied.iter().chain(checkpoint.snapshots.values()) {
+            snapshot.restore(self);
+        }
+
+        let size = RefCell::try_borrow(&self.cache)
+            .expect("restore borrow")
+            .values()
+            .map(|cache| cache.size())
+            .sum();
+        self.size.set(size);
+    }
+
     pub fn clear_all_queries(&self) {
         let mut caches =
             RefCell::try_borrow_mut(&self.cache).expect("clear_all_queries borrow mut");
@@ -301,7 +1001,7 @@ impl QueryCache {
     where
         K: QueryKey + 'static,
         V: QueryValue + 'static,
-        F: FnOnce(&HashMap<K, Query<K, V>>) -> Option<R>,
+        F: FnOnce(&QueryMap<K, V>) -> Option<R>,
         R: 'static,
     {
         let cache = RefCell::try_borrow(&self.cache).expect("use_cache_option borrow");
@@ -318,7 +1018,7 @@ impl QueryCache {
     where
         K: QueryKey + 'static,
         V: QueryValue + 'static,
-        F: FnOnce(&mut HashMap<K, Query<K, V>>) -> Option<R>,
+        F: FnOnce(&mut QueryMap<K, V>) -> Option<R>,
         R: 'static,
     {
         let mut cache = RefCell::try_borrow_mut(&self.cache).expect("use_cache_option_mut borrow");
@@ -331,7 +1031,7 @@ impl QueryCache {
         func(&mut cache.0)
     }
 
-    pub fn use_cache<K, V, R>(&self, func: impl FnOnce(&mut HashMap<K, Query<K, V>>) -> R) -> R
+    pub fn use_cache<K, V, R>(&self, func: impl FnOnce(&mut QueryMap<K, V>) -> R) -> R
     where
         K: QueryKey + 'static,
         V: QueryValue + 'static,
@@ -343,7 +1043,10 @@ impl QueryCache {
         let cache: &mut Box<dyn CacheEntryTrait> = match cache.entry(type_key) {
             Entry::Occupied(o) => o.into_mut(),
             Entry::Vacant(v) => {
-                let wrapped: CacheEntry<K, V> = CacheEntry(HashMap::new());
+                #[cfg(debug_assertions)]
+                self.check_value_type::<K, V>();
+                let wrapped: CacheEntry<K, V> =
+                    CacheEntry(HashMap::with_hasher(self.hasher.clone()));
                 v.insert(Box::new(wrapped))
             }
         };
@@ -356,6 +1059,24 @@ impl QueryCache {
         func(&mut cache.0)
     }
 
+    // Returns the first value type of the key type, if it differs from `V`.
+    #[cfg(debug_assertions)]
+    pub(crate) fn check_value_type<K: 'static, V: 'static>(&self) -> Option<&'static str> {
+        let value_type = std::any::type_name::<V>();
+        let mut value_types = self.value_types.borrow_mut();
+        let first_value_type = *value_types.entry(TypeId::of::<K>()).or_insert(value_type);
+        if first_value_type != value_type {
+            logging::debug_warn!(
+                "Query key type {} is used with multiple value types: {} and {}. Each value type has a separate cache, so these queries do not share data.",
+                std::any::type_name::<K>(),
+                first_value_type,
+                value_type
+            );
+            return Some(first_value_type);
+        }
+        None
+    }
+
     pub fn use_cache_entry<K, V>(
         &self,
         key: K,
@@ -366,14 +1087,14 @@ impl QueryCache {
     {
         let query_cache = self;
 
-        let mut created = false;
+        let mut created = None;
 
         self.use_cache(|cache| match cache.entry(key) {
             Entry::Vacant(entry) => {
                 if let Some(query) = func((query_cache.owner, None)) {
                     entry.insert(query.clone());
                     // Report insert.
-                    created = true;
+                    created = Some(query.last_access());
                     self.notify_new_query(query)
                 }
             }
@@ -386,8 +1107,9 @@ impl QueryCache {
         });
 
         // It's necessary to delay the size update until we are out of the borrow, to avoid borrow errors.
-        if created {
+        if let Some(last_access) = created {
             self.size.update(|size| *size += 1);
+            self.enforce_max_queries(last_access);
         }
     }
 
@@ -451,6 +1173,30 @@ impl QueryCache {
         self.notify_observers(event);
     }
 
+    pub fn freeze(&self) {
+        self.frozen.set(self.frozen.get() + 1);
+    }
+
+    // Runs the deferred notifications once the outermost frozen section ends.
+    pub fn unfreeze(&self) {
+        let depth = self.frozen.get().saturating_sub(1);
+        self.frozen.set(depth);
+        if depth == 0 {
+            let deferred = self.deferred.take();
+            for notify in deferred {
+                notify();
+            }
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.get() > 0
+    }
+
+    pub fn defer(&self, notify: impl FnOnce() + 'static) {
+        self.deferred.borrow_mut().push(Box::new(notify));
+    }
+
     pub fn notify_observers(&self, notification: CacheEvent) {
         let observers = self
             .observers
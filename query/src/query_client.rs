@@ -1,10 +1,21 @@
 use crate::{query_observer::ListenerKey, *};
 use leptos::*;
-use std::{borrow::Borrow, cell::Cell, collections::HashMap, future::Future, rc::Rc};
+use std::{
+    borrow::Borrow,
+    cell::{Cell, RefCell},
+    future::Future,
+    rc::Rc,
+};
 
 use self::{
-    cache_observer::CacheObserver, query::Query, query_cache::QueryCache,
-    query_observer::QueryObserver, query_persister::QueryPersister,
+    cache_observer::{CacheEvent, CacheObserver, CacheObserverKey},
+    fetching_count::FetchingCount,
+    focus_throttle::FocusThrottle,
+    query::Query,
+    query_cache::{QueryCache, QueryMap},
+    query_observer::QueryObserver,
+    query_persister::QueryPersister,
+    tag_index::TagIndex,
 };
 
 /// Provides a Query Client to the current scope.
@@ -19,6 +30,13 @@ pub fn provide_query_client_with_options(options: DefaultQueryOptions) {
     provide_context(QueryClient::new(owner, options));
 }
 
+/// Provides a Query Client to the current scope with custom options and client wide settings.
+pub fn provide_query_client_with_config(options: DefaultQueryOptions, config: QueryClientConfig) {
+    let owner = Owner::current().expect("Owner to be present");
+
+    provide_context(QueryClient::new_with_config(owner, options, config));
+}
+
 /// Provides a Query Client to the current scope with custom options and a persister.
 pub fn provide_query_client_with_options_and_persister(
     options: DefaultQueryOptions,
@@ -33,6 +51,22 @@ pub fn provide_query_client_with_options_and_persister(
     provide_context(client);
 }
 
+/// Provides a Query Client to the current scope with custom options and a custom hasher for the cache.
+/// Useful for keys that are expensive to hash, e.g. with `ahash::RandomState`.
+pub fn provide_query_client_with_options_and_hasher(
+    options: DefaultQueryOptions,
+    hasher: impl std::hash::BuildHasher + 'static,
+) {
+    let owner = Owner::current().expect("Owner to be present");
+
+    provide_context(QueryClient::new_with_hasher(
+        owner,
+        options,
+        QueryClientConfig::default(),
+        CacheHasher::new(hasher),
+    ));
+}
+
 /// Retrieves a Query Client from the current scope.
 pub fn use_query_client() -> QueryClient {
     use_context::<QueryClient>().expect("Query Client Missing.")
@@ -54,14 +88,97 @@ pub fn use_query_client() -> QueryClient {
 pub struct QueryClient {
     pub(crate) cache: QueryCache,
     pub(crate) default_options: DefaultQueryOptions,
+    pub(crate) config: QueryClientConfig,
+    tag_index: TagIndex,
+    fetching_count: FetchingCount,
+    #[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+    pub(crate) focus_throttle: FocusThrottle,
+    pub(crate) online: RwSignal<bool>,
+    request_context: Rc<RefCell<Option<Box<dyn std::any::Any>>>>,
 }
 
 impl QueryClient {
     /// Creates a new Query Client.
     pub fn new(owner: Owner, default_options: DefaultQueryOptions) -> Self {
+        Self::new_with_config(owner, default_options, QueryClientConfig::default())
+    }
+
+    /// Creates a new Query Client with client wide settings.
+    pub fn new_with_config(
+        owner: Owner,
+        default_options: DefaultQueryOptions,
+        config: QueryClientConfig,
+    ) -> Self {
+        Self::new_with_hasher(owner, default_options, config, CacheHasher::default())
+    }
+
+    /// Creates a new Query Client, using the given hasher for the query maps in the cache.
+    pub fn new_with_hasher(
+        owner: Owner,
+        default_options: DefaultQueryOptions,
+        config: QueryClientConfig,
+        hasher: CacheHasher,
+    ) -> Self {
+        let cache = QueryCache::new(owner, hasher);
+        cache.set_max_queries(config.max_queries);
+        cache.set_error_history_size(config.error_history_size);
+        let tag_index = TagIndex::default();
+        cache.register_observer(tag_index.clone());
+        let fetching_count = with_owner(owner, FetchingCount::new);
+        cache.register_observer(fetching_count.clone());
+
+        #[cfg(any(feature = "csr", feature = "hydrate"))]
+        if config.gc_on_focus {
+            let cache = cache.clone();
+            with_owner(owner, move || {
+                let handles = ["focus", "visibilitychange"].map(|event| {
+                    let cache = cache.clone();
+                    window_event_listener_untyped(event, move |_| {
+                        cache.collect_overdue_garbage();
+                    })
+                });
+                on_cleanup(move || handles.into_iter().for_each(|handle| handle.remove()));
+            });
+        }
+
+        let online = with_owner(owner, || {
+            RwSignal::new({
+                #[cfg(any(feature = "csr", feature = "hydrate"))]
+                {
+                    window().navigator().on_line()
+                }
+                #[cfg(not(any(feature = "csr", feature = "hydrate")))]
+                {
+                    true
+                }
+            })
+        });
+        #[cfg(any(feature = "csr", feature = "hydrate"))]
+        {
+            let cache = cache.clone();
+            with_owner(owner, move || {
+                let handles = [("online", true), ("offline", false)].map(|(event, is_online)| {
+                    let cache = cache.clone();
+                    window_event_listener_untyped(event, move |_| {
+                        online.set(is_online);
+                        if is_online {
+                            cache.refetch_on_reconnect();
+                        }
+                    })
+                });
+                on_cleanup(move || handles.into_iter().for_each(|handle| handle.remove()));
+            });
+        }
+
         Self {
-            cache: QueryCache::new(owner),
+            cache,
             default_options,
+            config,
+            tag_index,
+            fetching_count,
+            focus_throttle: FocusThrottle::new(config.focus_throttle),
+            online,
+            request_context: Rc::new(RefCell::new(None)),
         }
     }
 
@@ -95,10 +212,106 @@ impl QueryClient {
         }
     }
 
-    /// Prefetch a query and store it in cache.
-    /// If the entry already exists it will still be refetched.
+    /// Fetch a query if it has no fresh data, and returns its data.
+    /// Useful for loaders, e.g. to ensure the data of a route before rendering it.
+    ///
+    /// Unlike [`fetch_query()`](Self::fetch_query), fresh cached data is returned without refetching.
+    /// If a request is already in flight, it is awaited instead of starting another one.
+    /// Returns [`None`](Option::None) if the fetch was cancelled.
+    pub async fn fetch_query_data<K, V, Fu>(
+        &self,
+        key: K,
+        fetcher: impl Fn(K) -> Fu + 'static,
+    ) -> Option<V>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+        Fu: Future<Output = V> + 'static,
+    {
+        let query = self.cache.get_or_create_query::<K, V>(key);
+        self.fetch_unless_fresh(&query, fetcher).await;
+
+        query.with_state(|state| state.data().cloned())
+    }
+
+    /// Returns the data of a query, fetching it only if it has no fresh data.
+    /// If a request is already in flight, it is awaited instead of starting another one.
+    /// Useful for route loaders, also on the server.
+    ///
+    /// Unlike [`fetch_query_data()`](Self::fetch_query_data), this always resolves with a value.
+    /// If the request is cancelled or fails without data, `fetcher` is called directly, bypassing the cache.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// async fn get_user(id: u32) -> String {
+    ///     todo!()
+    /// }
+    ///
+    /// async fn load_profile(id: u32) -> String {
+    ///     use_query_client().ensure_query_data(id, get_user).await
+    /// }
+    /// ```
+    pub async fn ensure_query_data<K, V, Fu>(
+        &self,
+        key: K,
+        fetcher: impl Fn(K) -> Fu + 'static,
+    ) -> V
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+        Fu: Future<Output = V> + 'static,
+    {
+        let query = self.cache.get_or_create_query::<K, V>(key.clone());
+        self.fetch_unless_fresh(&query, &fetcher).await;
+
+        match query.with_state(|state| state.data().cloned()) {
+            Some(data) => data,
+            None => fetcher(key).await,
+        }
+    }
+
+    // Awaits the request in flight, or fetches the query if it has no fresh data.
+    async fn fetch_unless_fresh<K, V, Fu>(&self, query: &Query<K, V>, fetcher: impl Fn(K) -> Fu)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+        Fu: Future<Output = V>,
+    {
+        if !settle_in_flight(query).await && query.needs_prefetch(self.default_options.stale_time) {
+            query::execute_query(query.clone(), fetcher).await;
+        }
+    }
+
+    // Like fetch_unless_fresh(), with a fetcher that can fail.
+    async fn try_fetch_unless_fresh<K, V, Fu>(&self, query: &Query<K, V>, fetcher: impl Fn(K) -> Fu)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+        Fu: Future<Output = Result<V, QueryError>>,
+    {
+        if !settle_in_flight(query).await && query.needs_prefetch(self.default_options.stale_time) {
+            query::execute_fallible_query(query.clone(), fetcher).await;
+        }
+    }
+
+    /// Whether the browser is online, e.g. to show a connectivity banner. Always true outside of the client.
+    ///
+    /// While offline, queries are not fetched in the background, as the requests would fail anyway.
+    /// Once back online, queries that were left without data are fetched,
+    /// and stale queries are refetched if an observer enabled [`QueryOptions::refetch_on_reconnect`].
+    pub fn is_online(&self) -> Signal<bool> {
+        self.online.into()
+    }
+
+    /// Prefetch a query and store it in cache, e.g. on link hover so the data is ready when the user navigates.
+    /// Resolves once the fetch has completed. No observer is registered, so the query is garbage collected if it stays unused.
     ///
-    /// If you need the result opt for [`fetch_query()`](Self::fetch_query)
+    /// Queries that are already fresh are skipped. The stale time of the query's observers is used,
+    /// or the client's default stale time if it has none.
+    ///
+    /// If you need the result opt for [`fetch_query_data()`](Self::fetch_query_data)
     pub async fn prefetch_query<K, V, Fu>(&self, key: K, fetcher: impl Fn(K) -> Fu + 'static)
     where
         K: QueryKey + 'static,
@@ -109,7 +322,9 @@ impl QueryClient {
         {
             let query = self.cache.get_or_create_query::<K, V>(key);
 
-            query::execute_query(query.clone(), fetcher).await;
+            if query.needs_prefetch(self.default_options.stale_time) {
+                query::execute_query(query.clone(), fetcher).await;
+            }
         }
         #[cfg(not(any(feature = "hydrate", feature = "csr")))]
         {
@@ -118,6 +333,93 @@ impl QueryClient {
         }
     }
 
+    /// Prefetch multiple queries concurrently and store them in cache.
+    /// Resolves once every fetch has completed, including requests that were already in flight.
+    ///
+    /// Like [`prefetch_query()`](Self::prefetch_query), queries that are already fresh are skipped,
+    /// and queries that are currently being fetched are awaited instead of fetched again.
+    /// Also works on the server, like [`ensure_query_data()`](Self::ensure_query_data).
+    pub async fn prefetch_queries<K, V, Fu>(
+        &self,
+        keys: Vec<K>,
+        fetcher: impl Fn(K) -> Fu + 'static,
+    ) where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+        Fu: Future<Output = V> + 'static,
+    {
+        let queries = keys
+            .into_iter()
+            .map(|key| self.cache.get_or_create_query::<K, V>(key))
+            .collect::<Vec<_>>();
+        let executions = queries
+            .iter()
+            .map(|query| self.fetch_unless_fresh(query, &fetcher))
+            .collect::<Vec<_>>();
+
+        futures::future::join_all(executions).await;
+    }
+
+    /// Like [`prefetch_queries()`](Self::prefetch_queries), with a fetcher that can fail, like the fetcher of [`use_query_result()`](crate::use_query_result()).
+    ///
+    /// Returns each key with the error of its fetch, if it failed, in the order of `keys`.
+    /// A fresh query that was skipped is a success, and a query whose request was already in flight has the result of that request.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// async fn get_user(id: u32) -> Result<String, String> {
+    ///     todo!()
+    /// }
+    ///
+    /// async fn load_team(ids: Vec<u32>) -> Vec<u32> {
+    ///     let results = use_query_client().prefetch_queries_result(ids, get_user).await;
+    ///     // The users that failed to load.
+    ///     results
+    ///         .into_iter()
+    ///         .filter(|(_, result)| result.is_err())
+    ///         .map(|(id, _)| id)
+    ///         .collect()
+    /// }
+    /// ```
+    pub async fn prefetch_queries_result<K, V, E, Fu>(
+        &self,
+        keys: Vec<K>,
+        fetcher: impl Fn(K) -> Fu + 'static,
+    ) -> Vec<(K, Result<(), QueryError>)>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+        E: std::fmt::Debug + std::fmt::Display + Send + Sync + 'static,
+        Fu: Future<Output = Result<V, E>> + 'static,
+    {
+        use futures::FutureExt;
+
+        let fetcher = move |key| fetcher(key).map(|result| result.map_err(QueryError::new));
+        let queries = keys
+            .into_iter()
+            .map(|key| self.cache.get_or_create_query::<K, V>(key))
+            .collect::<Vec<_>>();
+        let executions = queries
+            .iter()
+            .map(|query| self.try_fetch_unless_fresh(query, &fetcher))
+            .collect::<Vec<_>>();
+
+        futures::future::join_all(executions).await;
+
+        queries
+            .into_iter()
+            .map(|query| {
+                let result = query.with_state(|state| match state.error() {
+                    Some(error) => Err(error.clone()),
+                    None => Ok(()),
+                });
+                (query.get_key().clone(), result)
+            })
+            .collect()
+    }
+
     /// Retrieve the current state for an existing query.
     /// If the query does not exist, [`None`](Option::None) will be returned.
     pub fn get_query_state<K, V>(
@@ -136,7 +438,7 @@ impl QueryClient {
             let key = key();
             // Subscribe to inserts/deletions.
             size.track();
-            cache.get_query::<K, V>(&key)
+            cache.get_query::<K, V, _>(&key)
         });
 
         let observer = Rc::new(QueryObserver::no_fetcher(
@@ -177,7 +479,110 @@ impl QueryClient {
         K: QueryKey + 'static,
         V: QueryValue + 'static,
     {
-        self.cache.get_query::<K, V>(key).map(|q| q.get_state())
+        self.cache.get_query_state(key)
+    }
+
+    /// Retrieve the current data of an existing query, e.g. from an event handler.
+    /// If the query does not exist or has no data yet, [`None`](Option::None) will be returned.
+    /// Nothing is subscribed, and the gc of the query is not affected.
+    pub fn get_query_data<K, V>(&self, key: &K) -> Option<V>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache.get_query_data(key)
+    }
+
+    /// Replaces the fetcher of the query's observers, the new fetcher is used from the next fetch on.
+    /// Useful when the fetcher captured outdated values, e.g. an expired auth token.
+    /// Does nothing if the query doesn't exist.
+    pub fn set_query_fetcher<K, V, Fu>(&self, key: &K, fetcher: impl Fn(K) -> Fu + 'static)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+        Fu: Future<Output = V> + 'static,
+    {
+        use futures::FutureExt;
+
+        if let Some(query) = self.cache.get_query::<K, V, K>(key) {
+            query.set_fetcher(move |key| fetcher(key).map(Ok));
+        }
+    }
+
+    /// Sets the context passed to the fetchers of [`use_query_with_context()`](crate::use_query_with_context()), e.g. auth headers.
+    ///
+    /// The new context is used from the next fetch on. Invalidate queries to refetch them with it right away,
+    /// e.g. with [`invalidate_all_queries()`](Self::invalidate_all_queries).
+    pub fn set_request_context<C>(&self, context: C)
+    where
+        C: Clone + 'static,
+    {
+        *self.request_context.borrow_mut() = Some(Box::new(context));
+    }
+
+    /// The current request context, if it was set with the given type.
+    pub fn request_context<C>(&self) -> Option<C>
+    where
+        C: Clone + 'static,
+    {
+        RefCell::borrow(&self.request_context)
+            .as_ref()
+            .and_then(|context| context.downcast_ref::<C>())
+            .cloned()
+    }
+
+    /// The options of each active observer of a query, in subscription order.
+    /// Empty if the query does not exist or is not observed.
+    ///
+    /// Useful to debug the effective options, e.g. staleness uses the minimum stale time of all observers.
+    pub fn observer_options<K, V>(&self, key: &K) -> Vec<QueryOptions<V>>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache
+            .get_query::<K, V, _>(key)
+            .map(|query| query.observer_options())
+            .unwrap_or_default()
+    }
+
+    /// Changes the timing of a live query, e.g. to poll faster while a page is in a certain state.
+    ///
+    /// Only `stale_time`, `gc_time` and `refetch_interval` are applied, to all current observers of the query.
+    /// The refetch interval and gc timeout are re-armed with the new durations.
+    /// Observers created afterwards use their own options again.
+    ///
+    /// Returns false if the query does not exist.
+    pub fn set_query_options<K, V>(&self, key: &K, options: QueryOptions<V>) -> bool
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        if let Some(query) = self.cache.get_query::<K, V, _>(key) {
+            query.set_timing(&options);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// A synchronous snapshot of an existing query, e.g. to check whether it's currently being refetched.
+    /// If the query does not exist, [`None`](Option::None) will be returned.
+    pub fn status<K, V>(&self, key: &K) -> Option<QueryStatus>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache
+            .get_query::<K, V, _>(key)
+            .map(|query| QueryStatus {
+                is_fetching: query.is_fetching(),
+                is_stale: query.with_state(|s| matches!(s, QueryState::Invalid(_)))
+                    || query.is_stale(),
+                updated_at: query.get_updated_at(),
+                observer_count: query.observer_count(),
+                error: query.with_state(|s| s.error().cloned()),
+            })
     }
 
     /// Attempts to invalidate an entry in the Query Cache.
@@ -201,7 +606,7 @@ impl QueryClient {
         V: QueryValue + 'static,
     {
         self.cache
-            .use_cache_option(|cache: &HashMap<K, Query<K, V>>| {
+            .use_cache_option(|cache: &QueryMap<K, V>| {
                 cache
                     .get(Borrow::borrow(&key))
                     .map(|state| state.mark_invalid())
@@ -209,6 +614,49 @@ impl QueryClient {
             .unwrap_or(false)
     }
 
+    /// Invalidates an entry in the Query Cache, and resolves once the triggered refetch has settled.
+    /// Resolves immediately if nothing refetches the query, e.g. it doesn't exist, has no active [`use_query()`](crate::use_query()),
+    /// or the client is offline.
+    /// If the query is already being fetched, waits for that request instead.
+    ///
+    /// Useful to wait for a list to reflect a change before navigating, or in tests.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// async fn save_then_refresh(client: QueryClient) {
+    ///     // Save the todo here.
+    ///     client.invalidate_and_wait::<(), Vec<String>>(&()).await;
+    ///     // The todo list is up to date.
+    /// }
+    /// ```
+    pub async fn invalidate_and_wait<K, V>(&self, key: &K)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        let Some(query) = self.cache.get_query::<K, V, _>(key) else {
+            return;
+        };
+
+        // Listen before invalidating, so no transition is missed.
+        let (sender, receiver) = futures_channel::oneshot::channel::<()>();
+        let listener = SettleListener::new(query.clone(), sender);
+
+        // Skipped fetches never settle, so they're not waited for.
+        let refetching = query.mark_invalid()
+            && query.has_fetcher()
+            && !query_is_suppressed()
+            && self.online.get_untracked()
+            && !query.is_shut_down()
+            && !query.is_mocked();
+        if refetching || query.is_fetching() {
+            let _ = receiver.await;
+        }
+        drop(listener);
+    }
+
     /// Attempts to invalidate multiple entries in the Query Cache with a common <K, V> type.
     /// All matching queries are immediately marked as invalid and active queries are refetched in the background.
     ///
@@ -231,19 +679,18 @@ impl QueryClient {
         V: crate::QueryValue + 'static,
         Q: Borrow<K> + 'static,
     {
-        self.cache
-            .use_cache_option(|cache: &HashMap<K, Query<K, V>>| {
-                let result = keys
-                    .into_iter()
-                    .filter(|key| {
-                        cache
-                            .get(Borrow::borrow(key))
-                            .map(|query| query.mark_invalid())
-                            .unwrap_or(false)
-                    })
-                    .collect::<Vec<_>>();
-                Some(result)
-            })
+        self.cache.use_cache_option(|cache: &QueryMap<K, V>| {
+            let result = keys
+                .into_iter()
+                .filter(|key| {
+                    cache
+                        .get(Borrow::borrow(key))
+                        .map(|query| query.mark_invalid())
+                        .unwrap_or(false)
+                })
+                .collect::<Vec<_>>();
+            Some(result)
+        })
     }
 
     /// Invalidate all queries with a common <K, V> type.
@@ -271,14 +718,95 @@ impl QueryClient {
     where
         K: QueryKey + 'static,
         V: QueryValue + 'static,
+    {
+        self.cache.use_cache_option(|cache: &QueryMap<K, V>| {
+            for q in cache.values() {
+                q.mark_invalid();
+            }
+            Some(())
+        });
+    }
+
+    /// Invalidate all queries with a common <K, V> type whose key matches `predicate`.
+    /// Useful when a key has multiple fields and only some of them identify the changed data.
+    ///
+    /// Returns how many queries were invalidated, excluding those that were already invalid.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+    /// struct TodoFilter {
+    ///     list_id: u32,
+    ///     done: bool,
+    /// }
+    ///
+    /// fn invalidate_list(list_id: u32) {
+    ///     let client = use_query_client();
+    ///     let invalidated = client
+    ///         .invalidate_queries_where::<TodoFilter, Vec<String>>(|filter| filter.list_id == list_id);
+    ///     leptos::logging::log!("Invalidated {invalidated} queries");
+    /// }
+    /// ```
+    pub fn invalidate_queries_where<K, V>(&self, predicate: impl Fn(&K) -> bool) -> usize
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache.invalidate_queries_where::<K, V>(predicate)
+    }
+
+    /// Invalidate all queries with a common <K, V> type whose composite key starts with `prefix`.
+    /// See [`KeyPrefix`] for which keys can be matched.
+    ///
+    /// Returns the keys that were successfully invalidated.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    /// fn invalidate() {
+    ///     let client = use_query_client();
+    ///     // Keys are (user id, page).
+    ///     let invalidated = client.invalidate_by_prefix::<(u32, u32), String, _>(&(0,));
+    /// }
+    /// ```
+    pub fn invalidate_by_prefix<K, V, P>(&self, prefix: &P) -> Vec<K>
+    where
+        K: QueryKey + KeyPrefix<P> + 'static,
+        V: QueryValue + 'static,
     {
         self.cache
-            .use_cache_option(|cache: &HashMap<K, Query<K, V>>| {
-                for q in cache.values() {
-                    q.mark_invalid();
-                }
-                Some(())
-            });
+            .use_cache_option(|cache: &QueryMap<K, V>| {
+                let keys = cache
+                    .iter()
+                    .filter(|(key, _)| key.has_prefix(prefix))
+                    .filter(|(_, query)| query.mark_invalid())
+                    .map(|(key, _)| key.clone())
+                    .collect::<Vec<_>>();
+                Some(keys)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Invalidates every query tagged with `tag`, across all key and value types.
+    /// A query carries the [`tags`](crate::QueryOptions::tags) of its active observers, e.g. mounted [`use_query()`](crate::use_query()) instances.
+    /// Active queries are refetched in the background.
+    ///
+    /// Returns the number of queries that were invalidated.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// fn invalidate_sidebar() {
+    ///     let client = use_query_client();
+    ///     // Queries using QueryOptions::default().set_tags(vec!["sidebar".into()])
+    ///     let invalidated = client.invalidate_by_tag("sidebar");
+    /// }
+    /// ```
+    pub fn invalidate_by_tag(&self, tag: &str) -> usize {
+        self.tag_index.invalidate(tag)
     }
 
     /// Invalidates all queries in the cache.
@@ -318,23 +846,53 @@ impl QueryClient {
         self.cache.size()
     }
 
-    /// A synchronous function that can be used to immediately set a query's data.
-    ///
-    /// If the query does not exist, it will be created.
-    ///
-    /// If you need to fetch the data asynchronously, use [`fetch_query`](Self::fetch_query) or [`prefetch_query`](Self::prefetch_query).
+    /// Returns the number of queries, across all types, whose data is invalid or stale.
+    /// A query is stale according to its active observers, so inactive queries are only counted when invalid.
     ///
-    /// If the updater function returns [`None`](Option::None), the query data will not be updated.
+    /// This is a snapshot, it doesn't track changes.
+    pub fn stale_queries_count(&self) -> usize {
+        self.cache.stale_queries_count()
+    }
+
+    /// Returns when the oldest data in the cache was updated, across all types.
+    /// [`None`](Option::None) if no query has data.
     ///
-    /// If the updater function receives [`None`](Option::None) as input, you can return [`None`](Option::None) to bail out of the update and thus not create a new cache entry.
+    /// This is a snapshot, it doesn't track changes.
     ///
     /// Example:
     /// ```
     /// use leptos_query::*;
+    /// use std::time::Duration;
+    ///
+    /// // E.g. when the app returns from the background.
+    /// fn should_prompt_refresh() -> bool {
+    ///     let client = use_query_client();
+    ///     client
+    ///         .oldest_updated_at()
+    ///         .map_or(false, |updated_at| Instant::now() - updated_at > Duration::from_secs(60 * 60))
+    /// }
+    /// ```
+    pub fn oldest_updated_at(&self) -> Option<Instant> {
+        self.cache.oldest_updated_at()
+    }
+
+    /// A synchronous function that can be used to immediately set a query's data.
+    ///
+    /// If the query does not exist, it will be created.
+    ///
+    /// If you need to fetch the data asynchronously, use [`fetch_query`](Self::fetch_query) or [`prefetch_query`](Self::prefetch_query).
+    ///
+    /// If the updater function returns [`None`](Option::None), the query data will not be updated.
+    ///
+    /// If the updater function receives [`None`](Option::None) as input, you can return [`None`](Option::None) to bail out of the update and thus not create a new cache entry.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+    /// struct MonkeyId(u32);
     ///
-    /// #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
-    /// struct MonkeyId(u32);
-    ///
     /// #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
     /// struct Monkey {
     ///     name: String
@@ -365,10 +923,13 @@ impl QueryClient {
         K: QueryKey + 'static,
         V: QueryValue + 'static,
     {
+        let error_history_size = self.cache.error_history_size();
         self.cache
             .use_cache_entry(key.clone(), move |(owner, entry)| match entry {
                 Some(query) => {
-                    query.maybe_map_state(|state| match state {
+                    let previous_source = query.get_fetch_source();
+                    query.set_fetch_source(Some(FetchSource::Manual));
+                    let updated = query.maybe_map_state(|state| match state {
                         QueryState::Created | QueryState::Loading => {
                             if let Some(result) = updater(None) {
                                 Ok(QueryState::Loaded(QueryData::now(result)))
@@ -397,13 +958,26 @@ impl QueryClient {
                                 Err(state)
                             }
                         }
+                        QueryState::Error { ref data, .. } => {
+                            if let Some(result) = updater(data.as_ref().map(|data| &data.data)) {
+                                Ok(QueryState::Loaded(QueryData::now(result)))
+                            } else {
+                                Err(state)
+                            }
+                        }
                     });
+                    if !updated {
+                        query.set_fetch_source(previous_source);
+                    }
                     None
                 }
                 None => {
                     if let Some(result) = updater(None) {
-                        let query = with_owner(owner, || Query::new(key));
-                        query.set_state(QueryState::Loaded(QueryData::now(result)));
+                        let query = with_owner(owner, || Query::new(key, error_history_size));
+                        query.set_state_from(
+                            QueryState::Loaded(QueryData::now(result)),
+                            FetchSource::Manual,
+                        );
                         Some(query)
                     } else {
                         None
@@ -412,6 +986,46 @@ impl QueryClient {
             });
     }
 
+    /// Update the state of every query with a common <K, V> type, e.g. after a bulk operation on the server.
+    /// Unlike invalidation, the data is changed in place rather than refetched.
+    ///
+    /// `update` returns whether it changed the state, and should leave it untouched otherwise.
+    /// Only the observers of changed queries are notified.
+    ///
+    /// Returns how many queries were updated.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+    /// struct TodoId(u32);
+    ///
+    /// #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+    /// struct Todo {
+    ///     title: String,
+    ///     synced: bool,
+    /// }
+    ///
+    /// fn mark_all_synced() {
+    ///     let client = use_query_client();
+    ///     client.update_queries::<TodoId, Todo>(|_, state| match state.data_mut() {
+    ///         Some(todo) if !todo.synced => {
+    ///             todo.synced = true;
+    ///             true
+    ///         }
+    ///         _ => false,
+    ///     });
+    /// }
+    /// ```
+    pub fn update_queries<K, V>(&self, update: impl Fn(&K, &mut QueryState<V>) -> bool) -> usize
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache.update_queries(update)
+    }
+
     /// Update the query's data.
     /// If the query does not exist, it will be created.
     pub fn set_query_data<K, V>(&self, key: K, data: V)
@@ -436,6 +1050,9 @@ impl QueryClient {
         self.cache.use_cache::<K, V, bool>(move |cache| {
             let mut updated = false;
             if let Some(query) = cache.get(key.borrow()) {
+                if query.with_state(|state| state.data().is_some()) {
+                    query.set_fetch_source(Some(FetchSource::Manual));
+                }
                 query.update_state(|state| {
                     if let Some(data) = state.data_mut() {
                         updater(data);
@@ -447,6 +1064,200 @@ impl QueryClient {
         })
     }
 
+    /// Pins the data of a query, and stops it from being fetched or invalidated until [`clear_mock()`](Self::clear_mock) is called.
+    /// If the query does not exist, it will be created. An in-flight request is cancelled.
+    ///
+    /// Useful for component previews and tests, where components should render fixed data without hitting the network.
+    /// Unlike [`set_query_data()`](Self::set_query_data), a refetch cannot overwrite the mocked data.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    /// fn preview() {
+    ///     let client = use_query_client();
+    ///     client.mock_query::<u32, String>(0, "Mocked".to_string());
+    /// }
+    /// ```
+    pub fn mock_query<K, V>(&self, key: K, value: V)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        let query = self.cache.get_or_create_query::<K, V>(key);
+        query.cancel();
+        query.set_mocked(true);
+        query.set_state_from(
+            QueryState::Loaded(QueryData::now(value)),
+            FetchSource::Manual,
+        );
+    }
+
+    /// Restores normal behavior for a query pinned by [`mock_query()`](Self::mock_query).
+    /// The mocked data is invalidated, so active queries are immediately refetched in the background.
+    ///
+    /// Returns whether the query was mocked.
+    pub fn clear_mock<K, V>(&self, key: impl Borrow<K>) -> bool
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache.use_cache::<K, V, bool>(move |cache| {
+            if let Some(query) = cache.get(key.borrow()) {
+                let mocked = query.is_mocked();
+                if mocked {
+                    query.set_mocked(false);
+                    query.mark_invalid();
+                }
+                mocked
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Applies a delta to the loaded data of a query, e.g. an event received over a websocket.
+    /// Observers are notified, and the data is considered fresh.
+    ///
+    /// Returns false if the query is not loaded, in which case the delta is dropped.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// enum ScoreEvent {
+    ///     Goal,
+    ///     Reset,
+    /// }
+    ///
+    /// fn on_event(event: ScoreEvent) {
+    ///     let client = use_query_client();
+    ///     client.apply_delta::<u32, u32, _>(&0, event, |score, event| match event {
+    ///         ScoreEvent::Goal => *score += 1,
+    ///         ScoreEvent::Reset => *score = 0,
+    ///     });
+    /// }
+    /// ```
+    pub fn apply_delta<K, V, D>(&self, key: &K, delta: D, reducer: impl Fn(&mut V, D)) -> bool
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        let Some(query) = self.cache.get_query::<K, V, _>(key) else {
+            return false;
+        };
+        if !query.with_state(|state| matches!(state, QueryState::Loaded(_))) {
+            return false;
+        }
+        query.set_fetch_source(Some(FetchSource::Manual));
+        query.update_state(|state| {
+            if let QueryState::Loaded(data) = state {
+                reducer(&mut data.data, delta);
+                data.updated_at = crate::Instant::now();
+            }
+        });
+        true
+    }
+
+    /// Optimistically appends an item to the cached list of a query, e.g. right before a mutation that creates it.
+    /// Does nothing if the query has no data.
+    ///
+    /// Returns a [`RollbackHandle`] to restore the previous list if the mutation fails.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// async fn add_todo(todo: String) -> Result<(), ()> {
+    ///     todo!()
+    /// }
+    ///
+    /// async fn create_todo(todo: String) {
+    ///     let client = use_query_client();
+    ///     let rollback = client.optimistic_list_insert::<(), Vec<String>>((), todo.clone());
+    ///     if add_todo(todo).await.is_err() {
+    ///         rollback.rollback();
+    ///     }
+    /// }
+    /// ```
+    pub fn optimistic_list_insert<K, V>(&self, key: K, item: V::Item) -> RollbackHandle<K, V>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + AsList + 'static,
+    {
+        let snapshot = self
+            .peek_query_state::<K, V>(&key)
+            .and_then(|state| state.data().cloned());
+        self.update_query_data_mut::<K, V>(&key, move |list| list.as_list_mut().push(item));
+        RollbackHandle::new(self.clone(), key, snapshot)
+    }
+
+    /// Optimistically removes the items matching `predicate` from the cached list of a query, e.g. right before a mutation that deletes them.
+    /// Does nothing if the query has no data.
+    ///
+    /// Returns a [`RollbackHandle`] to restore the previous list if the mutation fails.
+    pub fn optimistic_list_remove<K, V>(
+        &self,
+        key: K,
+        predicate: impl Fn(&V::Item) -> bool,
+    ) -> RollbackHandle<K, V>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + AsList + 'static,
+    {
+        let snapshot = self
+            .peek_query_state::<K, V>(&key)
+            .and_then(|state| state.data().cloned());
+        self.update_query_data_mut::<K, V>(&key, move |list| {
+            list.as_list_mut().retain(|item| !predicate(item))
+        });
+        RollbackHandle::new(self.clone(), key, snapshot)
+    }
+
+    /// Takes a snapshot of the state of a query, e.g. right before an optimistic update with [`set_query_data()`](Self::set_query_data).
+    /// Returns [`None`](Option::None) if the query does not exist.
+    ///
+    /// Restore it with [`restore_query()`](Self::restore_query) if the mutation fails.
+    /// [`MutationResult::optimistic_update()`](crate::MutationResult::optimistic_update) does both for a mutation.
+    pub fn snapshot_query<K, V>(&self, key: &K) -> Option<QueryState<V>>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache.snapshot_query(key)
+    }
+
+    /// Sets a query back to a [`snapshot_query()`](Self::snapshot_query), notifying its observers and the persister.
+    /// The query is not refetched, and is created if it was evicted since the snapshot.
+    ///
+    /// The snapshot is restored even if the query was updated since, e.g. by a refetch.
+    /// So restore before invalidating, otherwise a refetch that completes first is overwritten by the rollback.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// async fn rename_todo(title: String) -> Result<(), ()> {
+    ///     todo!()
+    /// }
+    ///
+    /// async fn rename(title: String) {
+    ///     let client = use_query_client();
+    ///     let snapshot = client.snapshot_query::<u32, String>(&0);
+    ///     client.set_query_data::<u32, String>(0, title.clone());
+    ///     if rename_todo(title).await.is_err() {
+    ///         client.restore_query(0, snapshot.unwrap_or_default());
+    ///     }
+    ///     client.invalidate_query::<u32, String>(0);
+    /// }
+    /// ```
+    pub fn restore_query<K, V>(&self, key: K, snapshot: QueryState<V>)
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache.restore_query(key, snapshot)
+    }
+
     /// Cancel any currently executing query.
     /// Returns whether the query was cancelled or not.
     pub fn cancel_query<K, V>(&self, key: K) -> bool
@@ -463,6 +1274,135 @@ impl QueryClient {
         })
     }
 
+    /// Cancel the in-flight requests of all queries with a common <K, V> type whose key matches `predicate`.
+    /// Each cancelled query reverts to the state it had before the request started.
+    ///
+    /// Useful before an optimistic update, so a pending refetch doesn't overwrite the optimistic data.
+    ///
+    /// Returns how many requests were cancelled.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+    /// struct TodoFilter {
+    ///     list_id: u32,
+    ///     done: bool,
+    /// }
+    ///
+    /// fn close_list(list_id: u32) {
+    ///     let client = use_query_client();
+    ///     client.cancel_queries_where::<TodoFilter, Vec<String>>(|filter| filter.list_id == list_id);
+    /// }
+    /// ```
+    pub fn cancel_queries_where<K, V>(&self, predicate: impl Fn(&K) -> bool) -> usize
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache.cancel_queries_where::<K, V>(predicate)
+    }
+
+    /// Cancels the in-flight requests of all queries, regardless of their type.
+    /// Useful on route change or logout, to stop all pending work at once.
+    ///
+    /// Returns how many requests were cancelled.
+    pub fn cancel_all_fetches(&self) -> usize {
+        self.cache.cancel_all_fetches()
+    }
+
+    /// Retrieves the [`CancelToken`] for the in-flight request of a query.
+    /// Returns [`None`](Option::None) if no request is in flight.
+    ///
+    /// Call this synchronously when the fetcher is invoked, to receive the token of the request being started.
+    pub fn cancel_token<K, V>(&self, key: &K) -> Option<CancelToken>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache
+            .get_query::<K, V, _>(key)
+            .and_then(|query| query.get_cancel_token())
+    }
+
+    /// Returns a signal with the number of queries that are [`QueryState::Loading`] or [`QueryState::Fetching`],
+    /// regardless of their type.
+    pub fn fetching_count(&self) -> Signal<usize> {
+        self.fetching_count.signal()
+    }
+
+    /// Returns a signal that is true while any query is [`QueryState::Loading`] or [`QueryState::Fetching`],
+    /// regardless of its type. Useful for a global loading bar.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos::*;
+    /// use leptos_query::*;
+    ///
+    /// #[component]
+    /// fn LoadingBar() -> impl IntoView {
+    ///     let is_fetching = use_query_client().is_fetching_any();
+    ///
+    ///     view! {
+    ///         <Show when=move || is_fetching.get()>
+    ///             <div class="loading-bar"/>
+    ///         </Show>
+    ///     }
+    /// }
+    /// ```
+    pub fn is_fetching_any(&self) -> Signal<bool> {
+        let count = self.fetching_count();
+        Signal::derive(move || count.get() > 0)
+    }
+
+    /// Returns the keys of all queries with a common <K, V> type that have a request in flight.
+    ///
+    /// Unlike checking for [`QueryState::Loading`] or [`QueryState::Fetching`], this reflects the actual in-flight request,
+    /// so a cancelled request is excluded immediately.
+    pub fn fetching_keys<K, V>(&self) -> Vec<K>
+    where
+        K: QueryKey + 'static,
+        V: QueryValue + 'static,
+    {
+        self.cache
+            .use_cache_option(|cache: &QueryMap<K, V>| {
+                let keys = cache
+                    .iter()
+                    .filter(|(_, query)| query.is_fetching())
+                    .map(|(key, _)| key.clone())
+                    .collect::<Vec<_>>();
+                Some(keys)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Runs `func` with the cache frozen.
+    /// State changes made while frozen, e.g. by fetches that resolve, are applied,
+    /// but observers and [`CacheObserver`]s are only notified once `func` returns.
+    /// Invalidated queries are refetched once `func` returns as well.
+    /// The cache is unfrozen even if `func` panics.
+    ///
+    /// Useful during SSR streaming, to avoid resources being refetched between serializing and sending them,
+    /// which can cause hydration mismatches on the client.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    /// fn serialize() {
+    ///     let client = use_query_client();
+    ///     let html = client.with_frozen_cache(|| {
+    ///         // Serialize resources here.
+    ///         String::new()
+    ///     });
+    /// }
+    /// ```
+    pub fn with_frozen_cache<T>(&self, func: impl FnOnce() -> T) -> T {
+        self.cache.freeze();
+        let _unfreeze = Unfreeze(&self.cache);
+        func()
+    }
+
     /// Registers the cache observer.
     pub fn register_cache_observer(&self, observer: impl CacheObserver + 'static) {
         let key = self.cache.register_observer(observer);
@@ -473,6 +1413,77 @@ impl QueryClient {
         })
     }
 
+    /// Evicts the queries whose gc time has elapsed, but whose timeout hasn't fired yet.
+    /// Returns the number of evicted queries.
+    ///
+    /// Timeouts fire late if the device was asleep or the tab was throttled.
+    /// See [`QueryClientConfig::gc_on_focus`] to run this automatically.
+    pub fn collect_overdue_garbage(&self) -> usize {
+        self.cache.collect_overdue_garbage()
+    }
+
+    /// Calls `callback` with every [`CacheEvent`], the same events [`CacheObserver`]s such as the devtools receive.
+    /// Useful for analytics or logging, e.g. whenever a query loads or fails.
+    ///
+    /// Returns a key to remove the callback with [`unsubscribe_events()`](Self::unsubscribe_events).
+    /// Unlike [`register_cache_observer()`](Self::register_cache_observer), the callback is not removed when the current scope is cleaned up.
+    ///
+    /// The callback runs synchronously while the cache notifies its observers, right after the change.
+    /// So it must not subscribe or unsubscribe, and should defer anything expensive.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos::*;
+    /// use leptos_query::{cache_observer::CacheEvent, *};
+    ///
+    /// fn log_errors(client: &QueryClient) {
+    ///     client.subscribe_events(|event| {
+    ///         if let CacheEvent::Updated(query) = event {
+    ///             if let Some(error) = query.state.error() {
+    ///                 logging::error!("Query {} failed: {:?}", query.key.0, error);
+    ///             }
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn subscribe_events(&self, callback: impl Fn(CacheEvent) + 'static) -> CacheObserverKey {
+        self.cache
+            .register_observer(crate::cache_observer::FnObserver(callback))
+    }
+
+    /// Removes a callback added with [`subscribe_events()`](Self::subscribe_events).
+    /// Returns whether the callback was still subscribed.
+    pub fn unsubscribe_events(&self, key: CacheObserverKey) -> bool {
+        self.cache.unregister_observer(key).is_some()
+    }
+
+    /// Calls `callback` with the serialized key of each query right before it is garbage collected,
+    /// i.e. evicted because it had no observers for its gc time.
+    ///
+    /// Useful to measure cache churn and tune `gc_time`. The callback is removed when the current scope is cleaned up.
+    pub fn on_gc_evict(&self, callback: impl Fn(String) + 'static) {
+        let key = self.cache.add_gc_evict_listener(callback);
+        let cache = self.cache.clone();
+
+        on_cleanup(move || {
+            cache.remove_gc_evict_listener(key);
+        })
+    }
+
+    /// Calls `callback` with the serialized key and the error, when data restored from the persister fails to deserialize,
+    /// e.g. after the value type changed. The persisted data is ignored in that case.
+    ///
+    /// Useful to log these failures, or to remove the offending entry from the persister.
+    /// The callback is removed when the current scope is cleaned up.
+    pub fn on_persister_error(&self, callback: impl Fn(String, SerializationError) + 'static) {
+        let key = self.cache.add_persister_error_listener(callback);
+        let cache = self.cache.clone();
+
+        on_cleanup(move || {
+            cache.remove_persister_error_listener(key);
+        })
+    }
+
     /// Adds a persister to the cache.
     pub fn add_persister(&self, persister: impl QueryPersister + Clone + 'static) {
         self.register_cache_observer(persister.clone());
@@ -484,15 +1495,118 @@ impl QueryClient {
         self.cache.remove_persister().is_some()
     }
 
-    /// Clears the cache. All queries will be removed.
-    pub fn clear(&self) {
-        self.cache.clear_all_queries()
-    }
-}
-
-#[cfg(all(test, not(any(feature = "csr", feature = "hydrate"))))]
-mod tests {
-    use super::*;
+    /// Captures the data of every query in the cache, to roll back to later with [`restore()`](Self::restore).
+    ///
+    /// Useful to undo an action that affected many queries. To roll back a single query, see [`optimistic_list_insert()`](Self::optimistic_list_insert).
+    ///
+    /// The data is captured serialized, so taking a checkpoint costs about as much as serializing every query in the cache.
+    /// Queries whose data fails to serialize, or fails to deserialize on restore, are skipped: they are left as they are on restore.
+    /// In-flight requests are not captured: a loading query is captured without data, and a fetching query with its current data.
+    ///
+    /// Example:
+    /// ```
+    /// use leptos_query::*;
+    ///
+    /// fn archive_all(client: &QueryClient) -> Checkpoint {
+    ///     let checkpoint = client.checkpoint();
+    ///     client.set_query_data::<String, Vec<String>>("archived".to_string(), vec![]);
+    ///     // Keep the checkpoint to undo.
+    ///     checkpoint
+    /// }
+    ///
+    /// fn undo(client: &QueryClient, checkpoint: Checkpoint) {
+    ///     client.restore(checkpoint);
+    /// }
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.cache.checkpoint()
+    }
+
+    /// Rolls the cache back to a [`checkpoint()`](Self::checkpoint).
+    ///
+    /// Queries are set to their state and update time at the checkpoint, without refetching.
+    /// Queries created since the checkpoint are evicted, unless they are observed, and queries evicted since are recreated.
+    /// Requests that are in flight still complete, and update their query.
+    pub fn restore(&self, checkpoint: Checkpoint) {
+        self.cache.restore(checkpoint)
+    }
+
+    /// Clears the cache. All queries will be removed.
+    pub fn clear(&self) {
+        self.cache.clear_all_queries()
+    }
+}
+
+// Awaits the request in flight, if any. Returns whether there was one.
+async fn settle_in_flight<K, V>(query: &Query<K, V>) -> bool
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    if !query.is_fetching() {
+        return false;
+    }
+    let (sender, receiver) = futures_channel::oneshot::channel::<()>();
+    let listener = SettleListener::new(query.clone(), sender);
+    let _ = receiver.await;
+    drop(listener);
+    true
+}
+
+// One-shot listener that fires once a request started after its creation has settled, or the query is evicted.
+// Unsubscribes when dropped. It isn't an observer, so it doesn't keep the query alive or affect its options.
+struct SettleListener<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    query: Query<K, V>,
+    listener: ListenerKey,
+}
+
+impl<K, V> SettleListener<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn new(query: Query<K, V>, sender: futures_channel::oneshot::Sender<()>) -> Self {
+        let started = Cell::new(query.is_fetching());
+        let sender = Cell::new(Some(sender));
+        let listener = query.add_state_listener(move |state| match state {
+            QueryState::Loading | QueryState::Fetching(_) => started.set(true),
+            _ if started.get() => {
+                if let Some(sender) = sender.take() {
+                    let _ = sender.send(());
+                }
+            }
+            _ => {}
+        });
+        Self { query, listener }
+    }
+}
+
+impl<K, V> Drop for SettleListener<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn drop(&mut self) {
+        self.query.remove_state_listener(self.listener);
+    }
+}
+
+// Unfreezes the cache when dropped, so a panic doesn't leave it frozen.
+struct Unfreeze<'a>(&'a QueryCache);
+
+impl Drop for Unfreeze<'_> {
+    fn drop(&mut self) {
+        self.0.unfreeze();
+    }
+}
+
+#[cfg(all(test, not(any(feature = "csr", feature = "hydrate"))))]
+mod tests {
+    use super::*;
 
     #[test]
     fn update_query_data() {
@@ -504,7 +1618,7 @@ mod tests {
         let state = || {
             use_query_client()
                 .cache
-                .get_query::<u32, String>(&0)
+                .get_query::<u32, String, _>(&0)
                 .map(|q| q.get_state())
         };
 
@@ -546,7 +1660,7 @@ mod tests {
         let state = || {
             use_query_client()
                 .cache
-                .get_query::<u32, String>(&0)
+                .get_query::<u32, String, _>(&0)
                 .map(|q| q.get_state())
                 .and_then(|s| s.data().cloned())
         };
@@ -572,7 +1686,7 @@ mod tests {
         let state = |key: u32| {
             use_query_client()
                 .cache
-                .get_query::<u32, String>(&key)
+                .get_query::<u32, String, _>(&key)
                 .map(|q| q.get_state())
                 .and_then(|s| s.data().cloned())
         };
@@ -733,6 +1847,441 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn invalidate_by_prefix() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        for key in [(0, 0), (0, 1), (1, 0)] {
+            client.set_query_data::<(u32, u32), u32>(key, 0);
+        }
+
+        let mut invalidated = client.invalidate_by_prefix::<(u32, u32), u32, _>(&(0,));
+        invalidated.sort();
+        assert_eq!(vec![(0, 0), (0, 1)], invalidated);
+
+        let is_invalid = |key: (u32, u32)| {
+            client
+                .peek_query_state::<(u32, u32), u32>(&key)
+                .map(|state| matches!(state, QueryState::Invalid(_)))
+        };
+        assert_eq!(Some(false), is_invalid((1, 0)));
+
+        // Already invalid queries are not reported again.
+        assert!(client
+            .invalidate_by_prefix::<(u32, u32), u32, _>(&(0, 1))
+            .is_empty());
+    }
+
+    #[test]
+    fn invalidate_queries_where() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        for key in [(0, false), (0, true), (1, false)] {
+            client.set_query_data::<(u32, bool), u32>(key, 0);
+        }
+
+        assert_eq!(
+            2,
+            client.invalidate_queries_where::<(u32, bool), u32>(|(list_id, _)| *list_id == 0)
+        );
+        assert!(matches!(
+            client.peek_query_state::<(u32, bool), u32>(&(1, false)),
+            Some(QueryState::Loaded(_))
+        ));
+
+        // Already invalid queries are not counted again.
+        assert_eq!(
+            1,
+            client.invalidate_queries_where::<(u32, bool), u32>(|_| true)
+        );
+        // Other types are not affected.
+        assert_eq!(
+            0,
+            client.invalidate_queries_where::<(u32, bool), String>(|_| true)
+        );
+    }
+
+    #[test]
+    fn fetching_keys() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(client.fetching_keys::<u32, u32>().is_empty());
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        client.cache.get_or_create_query::<u32, u32>(1);
+
+        let execution = query.new_execution();
+        assert!(execution.is_some());
+        assert!(query.is_fetching());
+        assert_eq!(vec![0], client.fetching_keys::<u32, u32>());
+
+        query.finalize_execution();
+        assert!(!query.is_fetching());
+        assert!(client.fetching_keys::<u32, u32>().is_empty());
+    }
+
+    #[test]
+    fn initial_data_only_computed_on_create() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let calls = Cell::new(0);
+        let initial_data = || {
+            calls.set(calls.get() + 1);
+            Some(QueryData::now(1))
+        };
+
+        let query = client
+            .cache
+            .get_or_create_query_with::<u32, u32>(0, initial_data);
+        assert_eq!(1, calls.get());
+        assert_eq!(Some(1), query.with_state(|state| state.data().cloned()));
+        assert_eq!(Some(FetchSource::Initial), query.get_fetch_source());
+
+        client
+            .cache
+            .get_or_create_query_with::<u32, u32>(0, initial_data);
+        assert_eq!(1, calls.get());
+    }
+
+    #[test]
+    fn lookup_clones_key_at_most_once() {
+        thread_local! {
+            static CLONES: Cell<usize> = const { Cell::new(0) };
+        }
+
+        #[derive(Debug, PartialEq, Eq, Hash)]
+        struct CountedKey(String);
+
+        impl Clone for CountedKey {
+            fn clone(&self) -> Self {
+                CLONES.with(|c| c.set(c.get() + 1));
+                CountedKey(self.0.clone())
+            }
+        }
+
+        impl Borrow<str> for CountedKey {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client
+            .cache
+            .get_or_create_query::<CountedKey, u32>(CountedKey("key".to_string()));
+
+        let clones = || CLONES.with(|c| c.get());
+
+        let before = clones();
+        client
+            .cache
+            .get_or_create_query::<CountedKey, u32>(CountedKey("key".to_string()));
+        assert!(clones() - before <= 1);
+
+        let before = clones();
+        let query = client.cache.get_query::<CountedKey, u32, str>("key");
+        assert!(query.is_some());
+        assert!(clones() - before <= 1);
+    }
+
+    #[test]
+    fn observers_notified_in_subscription_order() {
+        use crate::query_observer::QueryObserver;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let notified = Rc::new(RefCell::new(Vec::new()));
+
+        let observers = (0..5)
+            .map(|i| {
+                let observer =
+                    QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+                let notified = notified.clone();
+                observer.add_listener(move |_| notified.borrow_mut().push(i));
+                observer
+            })
+            .collect::<Vec<_>>();
+
+        query.set_state(QueryState::Loading);
+        assert_eq!(vec![0, 1, 2, 3, 4], notified.take());
+
+        // Re-subscribing moves the observer to the end.
+        observers[1].update_query(None);
+        observers[1].update_query(Some(query.clone()));
+
+        query.set_state(QueryState::Loading);
+        assert_eq!(vec![0, 2, 3, 4, 1], notified.take());
+    }
+
+    #[test]
+    fn frozen_cache_defers_notifications() {
+        use crate::query_observer::QueryObserver;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let notified = Rc::new(Cell::new(0));
+
+        let observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+        observer.add_listener({
+            let notified = notified.clone();
+            move |_| notified.set(notified.get() + 1)
+        });
+
+        client.with_frozen_cache(|| {
+            client.set_query_data::<u32, u32>(0, 1);
+            // State is applied, but nobody is notified yet.
+            assert_eq!(Some(1), query.with_state(|state| state.data().cloned()));
+            assert_eq!(0, notified.get());
+        });
+
+        assert_eq!(1, notified.get());
+    }
+
+    #[test]
+    fn frozen_cache_defers_refetch_of_invalidated_queries() {
+        use crate::query_observer::QueryObserver;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::with_fetcher(
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 2 }
+                }
+            },
+            QueryOptions::default(),
+            query.clone(),
+        );
+        client.set_query_data::<u32, u32>(0, 1);
+
+        client.with_frozen_cache(|| {
+            assert!(client.invalidate_query::<u32, u32>(0));
+            assert_eq!(0, fetches.get());
+            assert!(query.with_state(|state| matches!(state, QueryState::Invalid(_))));
+        });
+
+        assert_eq!(1, fetches.get());
+        assert_eq!(Some(2), client.get_query_data::<u32, u32>(&0));
+    }
+
+    #[test]
+    fn frozen_cache_is_unfrozen_on_panic() {
+        use crate::query_observer::QueryObserver;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let notified = Rc::new(Cell::new(0));
+        let observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(query));
+        observer.add_listener({
+            let notified = notified.clone();
+            move |_| notified.set(notified.get() + 1)
+        });
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            client.with_frozen_cache(|| {
+                client.set_query_data::<u32, u32>(0, 1);
+                panic!("Serialization failed");
+            })
+        }));
+
+        assert!(result.is_err());
+        assert!(!client.cache.is_frozen());
+        assert_eq!(1, notified.get());
+    }
+
+    #[test]
+    fn mock_query() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let state = || {
+            client
+                .peek_query_state::<u32, String>(&0)
+                .and_then(|state| state.data().cloned())
+        };
+
+        client.mock_query::<u32, String>(0, "mock".to_string());
+        assert_eq!(Some("mock".to_string()), state());
+
+        let query = client.cache.get_query::<u32, String, _>(&0).unwrap();
+        assert!(query.is_mocked());
+        assert!(query.new_execution().is_none());
+        assert!(!client.invalidate_query::<u32, String>(0));
+
+        assert!(client.clear_mock::<u32, String>(0));
+        assert!(!query.is_mocked());
+        assert!(query.with_state(|state| matches!(state, QueryState::Invalid(_))));
+        assert!(!client.clear_mock::<u32, String>(0));
+    }
+
+    #[test]
+    fn cancel_resolves_token() {
+        use futures::FutureExt;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        assert!(client.cancel_token::<u32, u32>(&0).is_none());
+
+        let _execution = query.new_execution();
+        let token = client
+            .cancel_token::<u32, u32>(&0)
+            .expect("Token should exist while fetching");
+
+        assert!(!token.is_cancelled());
+        assert!(token.cancelled().now_or_never().is_none());
+
+        assert!(client.cancel_query::<u32, u32>(0));
+
+        assert!(token.is_cancelled());
+        assert!(token.cancelled().now_or_never().is_some());
+        assert!(!query.is_fetching(), "Query should no longer be fetching");
+        assert!(client.cancel_token::<u32, u32>(&0).is_none());
+
+        // Nothing left to cancel.
+        assert!(!client.cancel_query::<u32, u32>(0));
+    }
+
+    #[test]
+    fn cancelled_request_does_not_overwrite_newer_request() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+
+        let fetcher = |receiver: oneshot::Receiver<u32>| {
+            let receiver = RefCell::new(Some(receiver));
+            move |_: u32| {
+                let receiver = receiver.borrow_mut().take().expect("Fetcher called once");
+                async move { receiver.await.expect("Sender dropped") }
+            }
+        };
+
+        let (first_sender, first_receiver) = oneshot::channel();
+        let mut first = Box::pin(query::execute_query(query.clone(), fetcher(first_receiver)));
+        assert!((&mut first).now_or_never().is_none());
+
+        // Inputs change mid-flight, the newer request replaces the first.
+        assert!(query.cancel());
+        let (second_sender, second_receiver) = oneshot::channel();
+        let mut second = Box::pin(query::execute_query(
+            query.clone(),
+            fetcher(second_receiver),
+        ));
+        assert!((&mut second).now_or_never().is_none());
+
+        // The first request resolves late, its result is discarded.
+        first_sender.send(1).unwrap();
+        assert!((&mut first).now_or_never().is_some());
+        assert!(query.with_state(|state| matches!(state, QueryState::Loading)));
+        assert!(query.is_fetching());
+
+        second_sender.send(2).unwrap();
+        assert!((&mut second).now_or_never().is_some());
+        assert_eq!(Some(2), query.with_state(|state| state.data().cloned()));
+        assert!(!query.is_fetching());
+    }
+
+    #[test]
+    fn apply_delta() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let add = |value: &mut u32, delta: u32| *value += delta;
+
+        assert!(!client.apply_delta::<u32, u32, _>(&0, 1, add));
+
+        client.set_query_data::<u32, u32>(0, 1);
+        assert!(client.apply_delta::<u32, u32, _>(&0, 2, add));
+        assert_eq!(
+            Some(3),
+            client
+                .peek_query_state::<u32, u32>(&0)
+                .and_then(|state| state.data().cloned())
+        );
+
+        client.invalidate_query::<u32, u32>(0);
+        assert!(!client.apply_delta::<u32, u32, _>(&0, 2, add));
+    }
+
+    #[test]
+    fn optimistic_list_rollback() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let list = || {
+            client
+                .peek_query_state::<u32, Vec<u32>>(&0)
+                .and_then(|state| state.data().cloned())
+        };
+
+        // Nothing to update without data.
+        let _ = client.optimistic_list_insert::<u32, Vec<u32>>(0, 1);
+        assert_eq!(None, list());
+
+        client.set_query_data::<u32, Vec<u32>>(0, vec![1, 2]);
+
+        let rollback = client.optimistic_list_insert::<u32, Vec<u32>>(0, 3);
+        assert_eq!(Some(vec![1, 2, 3]), list());
+        rollback.rollback();
+        assert_eq!(Some(vec![1, 2]), list());
+
+        let rollback = client.optimistic_list_remove::<u32, Vec<u32>>(0, |item| *item == 1);
+        assert_eq!(Some(vec![2]), list());
+        rollback.rollback();
+        assert_eq!(Some(vec![1, 2]), list());
+    }
+
     #[test]
     fn update_query_data_mut() {
         let _ = create_runtime();
@@ -743,7 +2292,7 @@ mod tests {
         let state = |key: u32| {
             use_query_client()
                 .cache
-                .get_query::<u32, u32>(&key)
+                .get_query::<u32, u32, _>(&key)
                 .map(|q| q.get_state())
                 .and_then(|s| s.data().cloned())
         };
@@ -776,4 +2325,2349 @@ mod tests {
 
         assert_eq!(state(1), None, "Data was updated for a non-existent query")
     }
+
+    #[test]
+    fn owner_cleanup_cancels_in_flight_requests() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+
+        let ((client, owner), disposer) = as_child_of_current_owner(|_: ()| {
+            provide_query_client();
+            (
+                use_query_client(),
+                Owner::current().expect("Owner to be present"),
+            )
+        })(());
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+
+        let (sender, receiver) = oneshot::channel();
+        let receiver = RefCell::new(Some(receiver));
+        let mut fetch = Box::pin(query::execute_query(query.clone(), move |_| {
+            let receiver = receiver.borrow_mut().take().expect("Fetcher called once");
+            async move { receiver.await.expect("Sender dropped") }
+        }));
+        assert!(with_owner(owner, || (&mut fetch).now_or_never()).is_none());
+        let token = query.get_cancel_token().expect("Request in flight");
+
+        // App teardown, while the request is in flight.
+        drop(disposer);
+        assert!(token.is_cancelled());
+        assert!(!query.is_fetching());
+
+        // The request completes anyway, e.g. on the server, without touching the disposed cache.
+        sender.send(1).unwrap();
+        assert!((&mut fetch).now_or_never().is_some());
+        assert!(query.with_state(|state| matches!(state, QueryState::Loading)));
+    }
+
+    #[test]
+    fn invalidate_and_wait() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        // Keep the queries active, so they aren't garbage collected.
+        let observe = |key: u32| {
+            let query = client.cache.get_or_create_query::<u32, u32>(key);
+            QueryObserver::no_fetcher(QueryOptions::default(), Some(query))
+        };
+
+        // Nothing refetches the query.
+        client.set_query_data::<u32, u32>(0, 1);
+        let _first = observe(0);
+        assert!(client
+            .invalidate_and_wait::<u32, u32>(&0)
+            .now_or_never()
+            .is_some());
+
+        // Waits for the in-flight request.
+        let _second = observe(1);
+        let query = client.cache.get_or_create_query::<u32, u32>(1);
+        let (sender, receiver) = oneshot::channel();
+        let receiver = RefCell::new(Some(receiver));
+        let mut fetch = Box::pin(query::execute_query(query.clone(), move |_| {
+            let receiver = receiver.borrow_mut().take().expect("Fetcher called once");
+            async move { receiver.await.expect("Sender dropped") }
+        }));
+        assert!((&mut fetch).now_or_never().is_none());
+
+        let mut wait = Box::pin(client.invalidate_and_wait::<u32, u32>(&1));
+        assert!((&mut wait).now_or_never().is_none());
+        // Waiting doesn't count as observing the query.
+        assert_eq!(1, query.observer_count());
+
+        sender.send(2).unwrap();
+        assert!((&mut fetch).now_or_never().is_some());
+        assert!((&mut wait).now_or_never().is_some());
+        assert_eq!(Some(2), query.with_state(|state| state.data().cloned()));
+        assert_eq!(1, query.observer_count());
+    }
+
+    #[test]
+    fn invalidate_and_wait_while_offline() {
+        use futures::FutureExt;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, 1);
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer =
+            QueryObserver::with_fetcher(|_| async { 2 }, QueryOptions::default(), query.clone());
+
+        // The refetch is skipped while offline, so there is nothing to wait for.
+        client.online.set(false);
+        assert!(client
+            .invalidate_and_wait::<u32, u32>(&0)
+            .now_or_never()
+            .is_some());
+        assert!(query.with_state(|state| matches!(state, QueryState::Invalid(_))));
+    }
+
+    #[test]
+    fn cache_health() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        assert_eq!(0, client.stale_queries_count());
+        assert_eq!(None, client.oldest_updated_at());
+
+        client.set_query_data::<u32, u32>(0, 0);
+        client.set_query_data::<String, String>("1".to_string(), "1".to_string());
+        client.cache.get_or_create_query::<u32, u32>(2);
+
+        let oldest = client
+            .peek_query_state::<u32, u32>(&0)
+            .and_then(|state| state.updated_at());
+        assert!(oldest.is_some());
+        assert_eq!(oldest, client.oldest_updated_at());
+
+        // Without observers, only invalid queries are stale.
+        assert_eq!(0, client.stale_queries_count());
+        client.invalidate_query::<String, String>("1".to_string());
+        assert_eq!(1, client.stale_queries_count());
+    }
+
+    #[test]
+    fn late_subscriber_sees_in_flight_request() {
+        use futures::FutureExt;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let mut fetch = Box::pin(query::execute_query(query.clone(), |_| {
+            futures::future::pending::<u32>()
+        }));
+        assert!((&mut fetch).now_or_never().is_none());
+
+        let notified = Rc::new(RefCell::new(Vec::new()));
+        let observer = QueryObserver::no_fetcher(QueryOptions::default(), None);
+        observer.add_listener({
+            let notified = notified.clone();
+            move |state: &QueryState<u32>| notified.borrow_mut().push(state.clone())
+        });
+
+        observer.update_query(Some(query.clone()));
+        assert_eq!(vec![QueryState::Loading], notified.take());
+    }
+
+    #[test]
+    fn observers_mounting_together_fetch_once() {
+        use crate::test_timer::TestTimer;
+
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+        let timer = TestTimer::install();
+
+        // Stale, so every observer executes it on mount.
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        query.set_state(QueryState::Loaded(QueryData {
+            data: 0,
+            updated_at: crate::Instant(std::time::Duration::ZERO),
+        }));
+        let fetched = Rc::new(RefCell::new(Vec::new()));
+        let _observers = (0..5)
+            .map(|id| {
+                let fetched = fetched.clone();
+                QueryObserver::with_fetcher(
+                    move |_| {
+                        fetched.borrow_mut().push(id);
+                        async move { id }
+                    },
+                    QueryOptions::default(),
+                    query.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Nothing is fetched until the tick ends, then the most recent fetcher is used once.
+        assert!(RefCell::borrow(&fetched).is_empty());
+        assert_eq!(1, timer.run_microtasks());
+        assert_eq!(vec![4], *RefCell::borrow(&fetched));
+        assert_eq!(Some(4), query.with_state(|state| state.data().cloned()));
+    }
+
+    #[test]
+    fn checkpoint_restore() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, 0);
+        client.set_query_data::<u32, u32>(1, 1);
+        let updated_at = |key: u32| {
+            client
+                .peek_query_state::<u32, u32>(&key)
+                .and_then(|state| state.updated_at())
+        };
+        let before = updated_at(0);
+
+        let checkpoint = client.checkpoint();
+
+        client.update_query_data::<u32, u32>(0, |_| Some(10));
+        client.cache.evict_query::<u32, u32>(&1);
+        client.set_query_data::<u32, u32>(2, 2);
+        client.set_query_data::<String, String>("3".to_string(), "3".to_string());
+
+        client.restore(checkpoint);
+
+        assert_eq!(
+            Some(0),
+            client
+                .peek_query_state::<u32, u32>(&0)
+                .and_then(|s| s.data().cloned())
+        );
+        assert_eq!(before, updated_at(0));
+        assert_eq!(
+            Some(1),
+            client
+                .peek_query_state::<u32, u32>(&1)
+                .and_then(|s| s.data().cloned())
+        );
+        assert_eq!(None, client.peek_query_state::<u32, u32>(&2));
+        assert_eq!(
+            None,
+            client.peek_query_state::<String, String>(&"3".to_string())
+        );
+        assert_eq!(2, client.size().get_untracked());
+    }
+
+    #[test]
+    fn checkpoint_restore_keeps_observed_and_unserializable() {
+        use crate::query_observer::QueryObserver;
+        use std::cell::RefCell;
+
+        thread_local! {
+            static OPAQUE: Cell<bool> = const { Cell::new(false) };
+        }
+
+        // Fails to serialize while opaque.
+        #[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+        struct Opaque(u32);
+
+        impl serde::Serialize for Opaque {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if OPAQUE.with(Cell::get) {
+                    return Err(serde::ser::Error::custom("opaque"));
+                }
+                serializer.serialize_u32(self.0)
+            }
+        }
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, 0);
+        client.set_query_data::<String, Opaque>("0".into(), Opaque(0));
+        OPAQUE.with(|opaque| opaque.set(true));
+        let checkpoint = client.checkpoint();
+        OPAQUE.with(|opaque| opaque.set(false));
+
+        client.set_query_data::<u32, u32>(0, 10);
+        client.set_query_data::<u32, u32>(1, 1);
+        client.set_query_data::<String, Opaque>("0".into(), Opaque(10));
+
+        // Created since the checkpoint, but still observed.
+        client.set_query_data::<u32, u32>(2, 2);
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions::default(),
+            client.cache.get_query::<u32, u32, _>(&2),
+        );
+
+        // Observers may read the cache while they are notified.
+        let observer = QueryObserver::no_fetcher(
+            QueryOptions::default(),
+            client.cache.get_query::<u32, u32, _>(&0),
+        );
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        observer.add_listener({
+            let client = client.clone();
+            let seen = seen.clone();
+            move |_: &QueryState<u32>| {
+                seen.borrow_mut()
+                    .push(client.get_query_data::<u32, u32>(&1))
+            }
+        });
+
+        client.restore(checkpoint);
+
+        assert_eq!(Some(0), client.get_query_data::<u32, u32>(&0));
+        assert_eq!(None, client.peek_query_state::<u32, u32>(&1));
+        assert_eq!(Some(2), client.get_query_data::<u32, u32>(&2));
+        assert_eq!(
+            Some(Opaque(10)),
+            client.get_query_data::<String, Opaque>(&"0".into())
+        );
+        assert_eq!(Some(&None), RefCell::borrow(&seen).last());
+    }
+
+    #[test]
+    fn cache_only_query_is_never_fetched() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let fetches = Rc::new(Cell::new(0));
+        let _observer = QueryObserver::with_fetcher(
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 1 }
+                }
+            },
+            QueryOptions::default().set_cache_only(true),
+            query.clone(),
+        );
+
+        query.execute();
+        assert_eq!(0, fetches.get());
+        assert!(query.with_state(|state| matches!(state, QueryState::Created)));
+
+        client.set_query_data::<u32, u32>(0, 2);
+        client.invalidate_query::<u32, u32>(0);
+        assert_eq!(0, fetches.get());
+        assert_eq!(Some(2), query.with_state(|state| state.data().cloned()));
+    }
+
+    #[test]
+    fn invalidate_by_tag() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let tags = vec!["sidebar".to_string()];
+        client.set_query_data::<u32, u32>(0, 0);
+        client.set_query_data::<u32, u32>(1, 1);
+        client.set_query_data::<String, String>("2".to_string(), "2".to_string());
+
+        let first = client.cache.get_or_create_query::<u32, u32>(0);
+        let second = client.cache.get_or_create_query::<u32, u32>(1);
+        let third = client
+            .cache
+            .get_or_create_query::<String, String>("2".to_string());
+
+        let tagged = QueryObserver::no_fetcher(
+            QueryOptions::default().set_tags(tags.clone()),
+            Some(first.clone()),
+        );
+        // Keeps the first query active once the tagged observer is gone.
+        let _untagged = QueryObserver::no_fetcher(QueryOptions::default(), Some(first.clone()));
+        let _second = QueryObserver::no_fetcher(QueryOptions::default(), Some(second.clone()));
+        let _third =
+            QueryObserver::no_fetcher(QueryOptions::default().set_tags(tags), Some(third.clone()));
+
+        assert_eq!(2, client.invalidate_by_tag("sidebar"));
+        assert!(first.with_state(|s| matches!(s, QueryState::Invalid(_))));
+        assert!(second.with_state(|s| matches!(s, QueryState::Loaded(_))));
+        assert!(third.with_state(|s| matches!(s, QueryState::Invalid(_))));
+        assert_eq!(0, client.invalidate_by_tag("other"));
+
+        // The query loses the tag once the tagged observer is gone.
+        client.set_query_data::<u32, u32>(0, 0);
+        client.set_query_data::<String, String>("2".to_string(), "2".to_string());
+        tagged.cleanup();
+        assert_eq!(1, client.invalidate_by_tag("sidebar"));
+        assert!(first.with_state(|s| matches!(s, QueryState::Loaded(_))));
+    }
+
+    #[test]
+    fn use_query_mut() {
+        let _ = create_runtime();
+
+        provide_query_client();
+        let client = use_query_client();
+
+        let (data, set_data) = crate::use_query_mut::<u32, String>(|| 0);
+        assert_eq!(None, data.get_untracked());
+
+        set_data("local".to_string());
+        assert_eq!(Some("local".to_string()), data.get_untracked());
+        assert_eq!(
+            Some("local".to_string()),
+            client
+                .peek_query_state::<u32, String>(&0)
+                .and_then(|state| state.data().cloned())
+        );
+
+        // Writes from elsewhere are reflected.
+        client.set_query_data::<u32, String>(0, "server".to_string());
+        assert_eq!(Some("server".to_string()), data.get_untracked());
+    }
+
+    #[test]
+    fn dedup_ttl() {
+        use futures::FutureExt;
+        use std::time::Duration;
+
+        let _ = create_runtime();
+
+        provide_query_client_with_config(
+            DefaultQueryOptions::default(),
+            QueryClientConfig {
+                dedup_ttl: Some(Duration::from_secs(60)),
+                ..QueryClientConfig::default()
+            },
+        );
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let fetches = Rc::new(Cell::new(0));
+        let fetch = || {
+            let fetches = fetches.clone();
+            query::execute_query(query.clone(), move |_| {
+                fetches.set(fetches.get() + 1);
+                async { 1 }
+            })
+        };
+
+        assert!(fetch().now_or_never().is_some());
+        assert!(fetch().now_or_never().is_some());
+        assert_eq!(1, fetches.get());
+
+        // Invalidated queries are always fetched.
+        query.mark_invalid();
+        assert!(fetch().now_or_never().is_some());
+        assert_eq!(2, fetches.get());
+    }
+
+    #[test]
+    fn query_signal_does_not_churn() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let lookups = Rc::new(Cell::new(0));
+        let renders = Rc::new(Cell::new(0));
+        let key = create_rw_signal(0_u32);
+
+        let query = client.cache.get_query_signal::<u32, u32>(
+            {
+                let lookups = lookups.clone();
+                move || {
+                    lookups.set(lookups.get() + 1);
+                    key.get()
+                }
+            },
+            || None,
+        );
+        let rendered_key = create_memo({
+            let renders = renders.clone();
+            move |_| {
+                renders.set(renders.get() + 1);
+                *query.get().get_key()
+            }
+        });
+
+        for _ in 0..3 {
+            assert_eq!(0, rendered_key.get());
+        }
+        assert_eq!(1, lookups.get());
+        assert_eq!(1, renders.get());
+
+        // Re-setting an equal key repeats the lookup, but doesn't notify dependents.
+        key.set(0);
+        assert_eq!(0, rendered_key.get());
+        assert_eq!(2, lookups.get());
+        assert_eq!(1, renders.get());
+        assert_eq!(1, client.size().get_untracked());
+
+        key.set(1);
+        assert_eq!(1, rendered_key.get());
+        assert_eq!(3, lookups.get());
+        assert_eq!(2, renders.get());
+        assert_eq!(2, client.size().get_untracked());
+    }
+
+    #[test]
+    fn custom_cache_hasher() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasher;
+
+        #[derive(Clone, Default)]
+        struct CountingHasher(Rc<Cell<usize>>);
+
+        impl BuildHasher for CountingHasher {
+            type Hasher = DefaultHasher;
+
+            fn build_hasher(&self) -> DefaultHasher {
+                self.0.set(self.0.get() + 1);
+                DefaultHasher::new()
+            }
+        }
+
+        let _ = create_runtime();
+        let hasher = CountingHasher::default();
+        provide_query_client_with_options_and_hasher(
+            DefaultQueryOptions::default(),
+            hasher.clone(),
+        );
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, 1);
+        assert!(hasher.0.get() > 0);
+
+        let hashes = hasher.0.get();
+        assert_eq!(
+            Some(1),
+            client
+                .get_query_state::<u32, u32>(|| 0)
+                .get_untracked()
+                .and_then(|s| s.data().cloned())
+        );
+        assert!(hasher.0.get() > hashes);
+    }
+
+    #[test]
+    fn default_cache_hasher() {
+        use std::hash::{BuildHasher, Hash, Hasher};
+
+        let hasher = CacheHasher::default();
+        assert_eq!(hasher.hash_one(1_u32), hasher.clone().hash_one(1_u32));
+        assert_ne!(hasher.hash_one(1_u32), hasher.hash_one(2_u32));
+
+        let custom = CacheHasher::new(std::collections::hash_map::RandomState::new());
+        assert_eq!(custom.hash_one("key"), custom.clone().hash_one("key"));
+
+        // Hashing a key at once, as the maps do, matches hashing it with a built hasher.
+        #[allow(clippy::manual_hash_one)]
+        for hasher in [hasher, custom] {
+            let mut built = hasher.build_hasher();
+            (1_u64, "key").hash(&mut built);
+            assert_eq!(hasher.hash_one((1_u64, "key")), built.finish());
+        }
+    }
+
+    #[test]
+    fn on_gc_evict() {
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let evicted = Rc::new(RefCell::new(Vec::new()));
+        client.on_gc_evict({
+            let evicted = evicted.clone();
+            move |key| evicted.borrow_mut().push(key)
+        });
+
+        client.set_query_data::<String, u32>("first".to_string(), 1);
+        client.set_query_data::<String, u32>("second".to_string(), 2);
+
+        assert!(client
+            .cache
+            .gc_evict_query::<String, u32>(&"first".to_string()));
+        assert_eq!(vec!["\"first\"".to_string()], *RefCell::borrow(&evicted));
+        assert_eq!(1, client.size().get_untracked());
+
+        // Explicit evictions aren't reported.
+        client
+            .cache
+            .evict_query::<String, u32>(&"second".to_string());
+        assert_eq!(1, RefCell::borrow(&evicted).len());
+    }
+
+    #[test]
+    fn stale_time_jitter() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let stale_time = Some(Duration::from_secs(10));
+        let jitter = Some(Duration::from_secs(60));
+
+        let queries = (0..10)
+            .map(|key| client.cache.get_or_create_query::<u32, u32>(key))
+            .collect::<Vec<_>>();
+        let deadlines = queries
+            .iter()
+            .map(|query| query.jittered_stale_time(stale_time, jitter).unwrap())
+            .collect::<Vec<_>>();
+
+        // Deadlines are spread within the window, and stable across reads.
+        for (query, deadline) in queries.iter().zip(&deadlines) {
+            assert!(*deadline >= Duration::from_secs(10) && *deadline <= Duration::from_secs(70));
+            assert_eq!(
+                Some(*deadline),
+                query.jittered_stale_time(stale_time, jitter)
+            );
+        }
+        assert!(deadlines.iter().any(|deadline| *deadline != deadlines[0]));
+        assert_eq!(stale_time, queries[0].jittered_stale_time(stale_time, None));
+
+        // Data that would be stale without jitter is still fresh.
+        let (query, deadline) = queries
+            .iter()
+            .zip(&deadlines)
+            .max_by_key(|(_, deadline)| **deadline)
+            .unwrap();
+        let loaded_ago = |ago: Duration| {
+            query.set_state(QueryState::Loaded(QueryData {
+                data: 0,
+                updated_at: crate::Instant(crate::Instant::now().0 - ago),
+            }))
+        };
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions {
+                stale_time,
+                stale_time_jitter: jitter,
+                ..QueryOptions::default()
+            },
+            Some(query.clone()),
+        );
+
+        loaded_ago(Duration::from_secs(11));
+        assert!(*deadline > Duration::from_secs(11));
+        assert!(!query.is_stale());
+
+        loaded_ago(Duration::from_secs(71));
+        assert!(query.is_stale());
+    }
+
+    #[test]
+    fn collect_overdue_garbage() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let gc_time = Some(Duration::from_secs(60));
+        let loaded_ago = |key: u32, ago: Duration| {
+            let query = client.cache.get_or_create_query::<u32, u32>(key);
+            query.get_gc().unwrap().update_gc_time(gc_time);
+            query.set_state(QueryState::Loaded(QueryData {
+                data: 0,
+                updated_at: crate::Instant(crate::Instant::now().0 - ago),
+            }));
+            query
+        };
+
+        // The gc timeout would have fired during a sleep.
+        loaded_ago(0, Duration::from_secs(61));
+        loaded_ago(1, Duration::from_secs(10));
+        let observed = loaded_ago(2, Duration::from_secs(61));
+        let _observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(observed));
+
+        let evicted = Rc::new(Cell::new(0));
+        client.on_gc_evict({
+            let evicted = evicted.clone();
+            move |_| evicted.set(evicted.get() + 1)
+        });
+
+        assert_eq!(1, client.collect_overdue_garbage());
+        assert_eq!(1, evicted.get());
+        assert!(client.peek_query_state::<u32, u32>(&0).is_none());
+        assert!(client.peek_query_state::<u32, u32>(&1).is_some());
+        assert!(client.peek_query_state::<u32, u32>(&2).is_some());
+        assert_eq!(0, client.collect_overdue_garbage());
+    }
+
+    #[test]
+    fn status() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::cell::RefCell;
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        assert_eq!(None, client.status::<u32, u32>(&0));
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions {
+                stale_time: Some(Duration::from_secs(60)),
+                ..QueryOptions::default()
+            },
+            Some(query.clone()),
+        );
+
+        let (sender, receiver) = oneshot::channel();
+        let receiver = RefCell::new(Some(receiver));
+        let mut fetch = Box::pin(query::execute_query(query.clone(), move |_| {
+            let receiver = receiver.borrow_mut().take().expect("Fetcher called once");
+            async move { receiver.await.expect("Sender dropped") }
+        }));
+        assert!((&mut fetch).now_or_never().is_none());
+
+        let status = client.status::<u32, u32>(&0).unwrap();
+        assert!(status.is_fetching);
+        assert_eq!(None, status.updated_at);
+        assert_eq!(1, status.observer_count);
+
+        sender.send(1).unwrap();
+        assert!((&mut fetch).now_or_never().is_some());
+
+        let status = client.status::<u32, u32>(&0).unwrap();
+        assert!(!status.is_fetching);
+        assert!(!status.is_stale);
+        assert_eq!(query.get_updated_at(), status.updated_at);
+
+        assert_eq!(None, status.error);
+
+        query.mark_invalid();
+        assert!(client.status::<u32, u32>(&0).unwrap().is_stale);
+
+        let error = QueryError::new("Unavailable");
+        let fetch = query::execute_fallible_query(query.clone(), {
+            let error = error.clone();
+            move |_| {
+                let error = error.clone();
+                async move { Err(error) }
+            }
+        });
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(Some(error), client.status::<u32, u32>(&0).unwrap().error);
+    }
+
+    #[test]
+    fn invalidate_during_fetch() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let (sender, receiver) = oneshot::channel();
+        let receiver = RefCell::new(Some(receiver));
+        let mut fetch = Box::pin(query::execute_query(query.clone(), move |_| {
+            let receiver = receiver.borrow_mut().take().expect("Fetcher called once");
+            async move { receiver.await.expect("Sender dropped") }
+        }));
+        assert!((&mut fetch).now_or_never().is_none());
+
+        let refetches = Rc::new(Cell::new(0));
+        let _observer = QueryObserver::with_fetcher(
+            {
+                let refetches = refetches.clone();
+                move |_| {
+                    refetches.set(refetches.get() + 1);
+                    async { 2 }
+                }
+            },
+            QueryOptions::default(),
+            query.clone(),
+        );
+        assert_eq!(0, refetches.get());
+
+        // The in-flight request may return outdated data.
+        assert!(client.invalidate_query::<u32, u32>(0));
+
+        sender.send(1).unwrap();
+        assert!((&mut fetch).now_or_never().is_some());
+        assert_eq!(1, refetches.get());
+        assert_eq!(Some(2), query.with_state(|state| state.data().cloned()));
+    }
+
+    #[test]
+    fn unobserved_query_stops_polling() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, 1);
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let fetches = Rc::new(Cell::new(0));
+        let polling = QueryObserver::with_fetcher(
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 1 }
+                }
+            },
+            QueryOptions {
+                refetch_interval: Some(Duration::from_secs(1)),
+                // Not garbage collected.
+                gc_time: None,
+                ..QueryOptions::default()
+            },
+            query.clone(),
+        );
+
+        // What a refetch interval tick does.
+        query.execute();
+        assert_eq!(1, fetches.get());
+
+        polling.cleanup();
+        assert_eq!(0, query.observer_count());
+
+        query.execute();
+        assert_eq!(1, fetches.get());
+        assert!(client.peek_query_state::<u32, u32>(&0).is_some());
+    }
+
+    #[test]
+    fn observer_options() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(client.observer_options::<u32, u32>(&0).is_empty());
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let observe = |stale_time: u64| {
+            QueryObserver::no_fetcher(
+                QueryOptions {
+                    stale_time: Some(Duration::from_secs(stale_time)),
+                    ..QueryOptions::default()
+                },
+                Some(query.clone()),
+            )
+        };
+        let _first = observe(60);
+        let _second = observe(5);
+
+        let stale_times = client
+            .observer_options::<u32, u32>(&0)
+            .into_iter()
+            .map(|options| options.stale_time)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![Some(Duration::from_secs(60)), Some(Duration::from_secs(5))],
+            stale_times
+        );
+    }
+
+    #[test]
+    fn invalidating_unobserved_queries_defers_fetch() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        for key in 0..1000 {
+            client.set_query_data::<u32, u32>(key, key);
+        }
+
+        let fetches = Rc::new(Cell::new(0));
+        let fetcher = {
+            let fetches = fetches.clone();
+            move |key: u32| {
+                fetches.set(fetches.get() + 1);
+                async move { key + 1 }
+            }
+        };
+
+        client.invalidate_all_queries();
+        assert_eq!(0, fetches.get());
+        assert_eq!(1000, client.stale_queries_count());
+        client.set_query_data::<u32, u32>(1, 1);
+
+        // The data is fresh, yet the invalidated query is fetched once observed.
+        let options = QueryOptions::default().set_stale_time(Some(Duration::from_secs(60)));
+        let invalidated = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer =
+            QueryObserver::with_fetcher(fetcher.clone(), options.clone(), invalidated.clone());
+        assert_eq!(1, fetches.get());
+        assert_eq!(
+            Some(1),
+            invalidated.with_state(|state| state.data().cloned())
+        );
+        assert!(invalidated.with_state(|state| matches!(state, QueryState::Loaded(_))));
+
+        // Unlike a query whose data was replaced since.
+        let valid = client.cache.get_or_create_query::<u32, u32>(1);
+        let _observer = QueryObserver::with_fetcher(fetcher, options, valid.clone());
+        assert_eq!(1, fetches.get());
+        assert_eq!(998, client.stale_queries_count());
+    }
+
+    #[test]
+    fn request_context() {
+        use futures::FutureExt;
+
+        #[derive(Clone)]
+        struct Auth(&'static str);
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, String>(0);
+        let fetcher = Rc::new(crate::use_query::with_request_context(
+            |key: u32, auth: Option<Auth>| async move {
+                format!("{key} {}", auth.map_or("anonymous", |auth| auth.0))
+            },
+        ));
+        let fetch = || {
+            let fetcher = fetcher.clone();
+            query::execute_query(query.clone(), move |key| fetcher(key))
+        };
+
+        // Fetches without a context.
+        assert!(client.request_context::<Auth>().is_none());
+        assert!(fetch().now_or_never().is_some());
+        assert_eq!(
+            Some("0 anonymous".to_string()),
+            query.with_state(|s| s.data().cloned())
+        );
+
+        client.set_request_context(Auth("first"));
+        assert!(client.request_context::<String>().is_none());
+        assert!(fetch().now_or_never().is_some());
+        assert_eq!(
+            Some("0 first".to_string()),
+            query.with_state(|s| s.data().cloned())
+        );
+
+        // The new context is used for the next fetch.
+        client.set_request_context(Auth("second"));
+        assert!(fetch().now_or_never().is_some());
+        assert_eq!(
+            Some("0 second".to_string()),
+            query.with_state(|s| s.data().cloned())
+        );
+    }
+
+    #[test]
+    fn on_persister_error() {
+        use crate::query_persister::PersistQueryData;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        client.on_persister_error({
+            let errors = errors.clone();
+            move |key, _| errors.borrow_mut().push(key)
+        });
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let persisted = |value: &str| PersistQueryData {
+            value: value.to_string(),
+            updated_at: 0,
+        };
+
+        // Persisted with an older value type.
+        client
+            .cache
+            .restore_persisted(&query, persisted("\"zero\""));
+        assert_eq!(vec!["0".to_string()], *RefCell::borrow(&errors));
+        assert_eq!(QueryState::Created, query.get_state());
+
+        client.cache.restore_persisted(&query, persisted("1"));
+        assert_eq!(1, RefCell::borrow(&errors).len());
+        assert_eq!(Some(1), query.with_state(|state| state.data().cloned()));
+    }
+
+    #[test]
+    fn use_query_states() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        // Observed elsewhere, so the queries aren't garbage collected once unobserved here.
+        let _observers = (0..3)
+            .map(|key| {
+                client.set_query_data::<u32, u32>(key, key * 10);
+                let query = client.cache.get_or_create_query::<u32, u32>(key);
+                QueryObserver::no_fetcher(QueryOptions::default(), Some(query))
+            })
+            .collect::<Vec<_>>();
+
+        let keys = create_rw_signal(vec![0_u32, 1, 5]);
+        let (states, disposer) = as_child_of_current_owner(move |_| {
+            crate::use_query_states::<u32, u32>(move || keys.get())
+        })(());
+        let data = move || {
+            states
+                .get_untracked()
+                .into_iter()
+                .map(|(key, state)| (key, state.data().cloned()))
+                .collect::<Vec<_>>()
+        };
+        let observer_count = |key: u32| {
+            client
+                .cache
+                .get_query::<u32, u32, _>(&key)
+                .unwrap()
+                .observer_count()
+        };
+
+        assert_eq!(vec![(0, Some(0)), (1, Some(10))], data());
+        assert_eq!(2, observer_count(0));
+
+        client.set_query_data::<u32, u32>(1, 11);
+        assert_eq!(vec![(0, Some(0)), (1, Some(11))], data());
+
+        // Created queries are picked up.
+        client.set_query_data::<u32, u32>(5, 50);
+        let _fifth = QueryObserver::no_fetcher(
+            QueryOptions::default(),
+            client.cache.get_query::<u32, u32, _>(&5),
+        );
+        assert_eq!(vec![(0, Some(0)), (1, Some(11)), (5, Some(50))], data());
+
+        // Removed keys are unobserved.
+        keys.set(vec![1, 2]);
+        assert_eq!(vec![(1, Some(11)), (2, Some(20))], data());
+        assert_eq!(1, observer_count(0));
+        assert_eq!(1, observer_count(5));
+        assert_eq!(2, observer_count(2));
+
+        // Duplicate keys share an observer, and every occurrence is updated.
+        keys.set(vec![1, 2, 1]);
+        assert_eq!(2, observer_count(1));
+        client.set_query_data::<u32, u32>(1, 12);
+        assert_eq!(vec![(1, Some(12)), (2, Some(20)), (1, Some(12))], data());
+
+        keys.set(vec![2]);
+        assert_eq!(1, observer_count(1));
+
+        drop(disposer);
+        assert_eq!(1, observer_count(1));
+        assert_eq!(1, observer_count(2));
+    }
+
+    #[test]
+    fn use_query_states_skips_unfetched_queries() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        // Created, but nothing fetches it.
+        let unfetched = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(unfetched));
+        client.set_query_data::<u32, u32>(1, 10);
+
+        // Sleeps finish immediately in tests, so the grace period is over right away.
+        let states = crate::use_query_states::<u32, u32>(|| vec![0, 1]);
+        let data = move || {
+            states
+                .get_untracked()
+                .into_iter()
+                .map(|(key, state)| (key, state.data().cloned()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![(1, Some(10))], data());
+
+        // Listed again once loaded.
+        client.set_query_data::<u32, u32>(0, 0);
+        assert_eq!(vec![(0, Some(0)), (1, Some(10))], data());
+    }
+
+    #[test]
+    fn fetch_completing_after_eviction() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let (sender, receiver) = oneshot::channel();
+        let receiver = RefCell::new(Some(receiver));
+        let mut fetch = Box::pin(query::execute_query(query.clone(), move |_| {
+            let receiver = receiver.borrow_mut().take().expect("Fetcher called once");
+            async move { receiver.await.expect("Sender dropped") }
+        }));
+        assert!((&mut fetch).now_or_never().is_none());
+
+        let notified = Rc::new(Cell::new(0));
+        let observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+        observer.add_listener({
+            let notified = notified.clone();
+            move |_| notified.set(notified.get() + 1)
+        });
+
+        client.cache.evict_query::<u32, u32>(&0);
+        assert_eq!(0, client.size().get_untracked());
+
+        sender.send(1).unwrap();
+        assert!((&mut fetch).now_or_never().is_some());
+        assert_eq!(0, notified.get());
+        assert_eq!(QueryState::Loading, query.get_state());
+        assert!(client.peek_query_state::<u32, u32>(&0).is_none());
+    }
+
+    #[test]
+    fn set_query_options() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        assert!(!client.set_query_options::<u32, u32>(&0, QueryOptions::default()));
+
+        client.set_query_data::<u32, u32>(0, 0);
+        let query = client.cache.get_query::<u32, u32, _>(&0).unwrap();
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions {
+                stale_time: Some(Duration::from_secs(60)),
+                ..QueryOptions::default()
+            },
+            Some(query),
+        );
+        assert_eq!(
+            Some(false),
+            client.status::<u32, u32>(&0).map(|s| s.is_stale)
+        );
+
+        assert!(client.set_query_options::<u32, u32>(
+            &0,
+            QueryOptions {
+                stale_time: Some(Duration::ZERO),
+                ..QueryOptions::default()
+            },
+        ));
+        assert_eq!(
+            Some(true),
+            client.status::<u32, u32>(&0).map(|s| s.is_stale)
+        );
+        assert_eq!(
+            vec![Some(Duration::ZERO)],
+            client
+                .observer_options::<u32, u32>(&0)
+                .into_iter()
+                .map(|options| options.stale_time)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn cancel_all_fetches() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        assert_eq!(0, client.cancel_all_fetches());
+
+        fn pending<K, V: 'static>() -> (oneshot::Sender<V>, impl Fn(K) -> oneshot::Receiver<V>) {
+            let (sender, receiver) = oneshot::channel();
+            let receiver = RefCell::new(Some(receiver));
+            (sender, move |_: K| {
+                receiver.borrow_mut().take().expect("Fetcher called once")
+            })
+        }
+
+        let numbers = [0, 1].map(|key| client.cache.get_or_create_query::<u32, u32>(key));
+        let text = client
+            .cache
+            .get_or_create_query::<String, String>("a".into());
+        // Settled queries are not counted.
+        client.set_query_data::<u32, u32>(2, 2);
+
+        let mut senders = Vec::new();
+        let mut fetches = Vec::new();
+        for query in numbers.iter() {
+            let (sender, fetcher) = pending::<u32, u32>();
+            senders.push(sender);
+            let fetcher = move |key| fetcher(key).map(|value| value.unwrap_or_default());
+            fetches.push(query::execute_query(query.clone(), fetcher).boxed_local());
+        }
+        let (text_sender, text_fetcher) = pending::<String, String>();
+        let text_fetcher = move |key| text_fetcher(key).map(|value| value.unwrap_or_default());
+        fetches.push(query::execute_query(text.clone(), text_fetcher).boxed_local());
+
+        for fetch in fetches.iter_mut() {
+            assert!(fetch.now_or_never().is_none());
+        }
+
+        assert_eq!(3, client.cancel_all_fetches());
+
+        // Requests that can't be interrupted still resolve, but their results are discarded.
+        for (value, sender) in senders.into_iter().enumerate() {
+            sender.send(value as u32).unwrap();
+        }
+        text_sender.send("a".into()).unwrap();
+        for fetch in fetches.iter_mut() {
+            assert!(fetch.now_or_never().is_some());
+        }
+        for query in numbers.iter() {
+            assert!(!query.is_fetching());
+            assert!(query.with_state(|state| matches!(state, QueryState::Created)));
+        }
+        assert!(!text.is_fetching());
+        assert!(text.with_state(|state| matches!(state, QueryState::Created)));
+        assert_eq!(0, client.cancel_all_fetches());
+    }
+
+    #[test]
+    fn failed_fetch_keeps_last_good_data() {
+        use futures::FutureExt;
+        use std::cell::RefCell;
+
+        #[derive(Debug, PartialEq)]
+        struct Unavailable;
+
+        impl std::fmt::Display for Unavailable {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("Unavailable")
+            }
+        }
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let results = RefCell::new(vec![Err(Unavailable), Ok(1), Err(Unavailable)]);
+        let fetch = || {
+            let fetcher = |_: u32| {
+                let result = results.borrow_mut().remove(0);
+                async move { result.map_err(QueryError::new) }
+            };
+            assert!(query::execute_fallible_query(query.clone(), fetcher)
+                .now_or_never()
+                .is_some());
+        };
+
+        // First load fails, there is no data to keep.
+        fetch();
+        let error = query.with_state(|state| {
+            assert!(state.data().is_none());
+            state.error().cloned().expect("Query should have failed")
+        });
+        assert_eq!(
+            Some(&Unavailable),
+            error.downcast::<Unavailable>().as_deref()
+        );
+        assert!(error.downcast::<String>().is_none());
+        assert_eq!("Unavailable", error.to_string());
+        assert!(query.needs_execute());
+
+        fetch();
+        assert!(
+            query.with_state(|state| matches!(state, QueryState::Loaded(data) if data.data == 1))
+        );
+
+        fetch();
+        assert!(query.with_state(|state| state.error().is_some() && state.data() == Some(&1)));
+        assert!(!query.is_fetching());
+    }
+
+    #[test]
+    fn error_history_is_bounded() {
+        use futures::FutureExt;
+
+        let _ = create_runtime();
+        provide_query_client_with_config(
+            DefaultQueryOptions::default(),
+            QueryClientConfig {
+                error_history_size: 2,
+                ..QueryClientConfig::default()
+            },
+        );
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let fetch = |result: Result<u32, &'static str>| {
+            let fetcher = move |_: u32| async move { result.map_err(QueryError::new) };
+            assert!(query::execute_fallible_query(query.clone(), fetcher)
+                .now_or_never()
+                .is_some());
+        };
+        let errors = || {
+            query
+                .get_error_history()
+                .into_iter()
+                .map(|(_, error)| error.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        fetch(Err("first"));
+        fetch(Ok(1));
+        assert_eq!(vec!["first"], errors());
+
+        // The oldest error is dropped.
+        fetch(Err("second"));
+        fetch(Err("third"));
+        assert_eq!(vec!["second", "third"], errors());
+    }
+
+    #[test]
+    fn failed_refetch_invalidates_data() {
+        use crate::query_observer::QueryObserver;
+        use futures::FutureExt;
+        use std::cell::RefCell;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions::default().set_invalidate_on_error(true),
+            Some(query.clone()),
+        );
+        let results = RefCell::new(vec![Err("unavailable"), Ok(1), Err("unavailable")]);
+        let fetch = || {
+            let fetcher = |_: u32| {
+                let result = results.borrow_mut().remove(0);
+                async move { result.map_err(QueryError::new) }
+            };
+            assert!(query::execute_fallible_query(query.clone(), fetcher)
+                .now_or_never()
+                .is_some());
+        };
+
+        // Without data, the error is kept.
+        fetch();
+        assert!(query.with_state(|state| state.error().is_some()));
+
+        fetch();
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&0));
+
+        // The data is retried on the next read, instead of showing the error.
+        fetch();
+        assert!(
+            query.with_state(|state| matches!(state, QueryState::Invalid(data) if data.data == 1))
+        );
+        assert!(query.needs_execute());
+        assert!(!query.is_fetching());
+    }
+
+    #[test]
+    fn retry_failed_fetches() {
+        use crate::test_timer::TestTimer;
+        use futures::FutureExt;
+        use std::{cell::RefCell, time::Duration};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        let timer = TestTimer::install();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions {
+                retry: Some(2),
+                retry_delay: Some(Rc::new(|attempt| {
+                    Duration::from_millis(100 * attempt as u64)
+                })),
+                ..QueryOptions::default()
+            },
+            Some(query.clone()),
+        );
+
+        let results = Rc::new(RefCell::new(Vec::<Result<u32, &'static str>>::new()));
+        let fetcher = {
+            let results = results.clone();
+            move |_: u32| {
+                let result = results.borrow_mut().remove(0);
+                async move { result.map_err(QueryError::new) }
+            }
+        };
+
+        let remaining = || RefCell::borrow(&results).len();
+
+        // Succeeds on the last retry, waiting for the delay before each retry.
+        *results.borrow_mut() = vec![Err("first"), Err("second"), Ok(1)];
+        let mut fetch = query::execute_fallible_query(query.clone(), fetcher.clone()).boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(2, remaining());
+        assert_eq!(vec![Duration::from_millis(100)], timer.requested());
+
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(2, remaining());
+
+        assert_eq!(1, timer.fire());
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(1, remaining());
+        assert_eq!(
+            vec![Duration::from_millis(100), Duration::from_millis(200)],
+            timer.requested()
+        );
+
+        assert_eq!(1, timer.fire());
+        assert!(fetch.now_or_never().is_some());
+        assert!(remaining() == 0);
+        assert!(
+            query.with_state(|state| matches!(state, QueryState::Loaded(data) if data.data == 1))
+        );
+
+        // Gives up once the retries are exhausted.
+        *results.borrow_mut() = vec![Err("first"), Err("second"), Err("third"), Ok(2)];
+        let mut fetch = query::execute_fallible_query(query.clone(), fetcher).boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(1, timer.fire());
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(1, timer.fire());
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(1, remaining());
+        assert_eq!(4, timer.requested().len());
+        let error = query.with_state(|state| state.error().cloned()).unwrap();
+        assert_eq!(Some(&"third"), error.downcast::<&str>().as_deref());
+    }
+
+    #[test]
+    fn default_retry_delay_backs_off() {
+        use std::time::Duration;
+
+        let delays = [1, 2, 3, 5, 6, 40].map(query::default_retry_delay);
+        assert_eq!([1, 2, 4, 16, 30, 30].map(Duration::from_secs), delays);
+    }
+
+    #[test]
+    fn get_query_data() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        assert_eq!(None, client.get_query_data::<u32, String>(&0));
+
+        client.cache.get_or_create_query::<u32, String>(0);
+        assert_eq!(None, client.get_query_data::<u32, String>(&0));
+        assert_eq!(
+            Some(QueryState::Created),
+            client.peek_query_state::<u32, String>(&0)
+        );
+
+        client.set_query_data::<u32, String>(0, "zero".into());
+        client.invalidate_query::<u32, String>(0);
+        assert_eq!(
+            Some("zero".to_string()),
+            client.get_query_data::<u32, String>(&0)
+        );
+        let query = client.cache.get_query::<u32, String, _>(&0).unwrap();
+        assert_eq!(0, query.observer_count());
+    }
+
+    #[test]
+    fn stale_initial_data_is_refetched() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let seed = |key: u32, ago: Duration| {
+            let options = QueryOptions::default()
+                .set_initial_data(0)
+                .set_initial_data_updated_at(Some(crate::Instant(crate::Instant::now().0 - ago)))
+                .set_stale_time(Some(Duration::from_secs(10)));
+            let initial_data = options.initial_data_fn.clone().unwrap();
+            let updated_at = options.initial_data_updated_at.unwrap();
+            let query = client.cache.get_or_create_query_with::<u32, u32>(key, || {
+                initial_data().map(|data| QueryData { data, updated_at })
+            });
+            assert_eq!(Some(0), query.with_state(|state| state.data().cloned()));
+            QueryObserver::with_fetcher(|_| async { 1 }, options, query.clone())
+        };
+
+        let _fresh = seed(0, Duration::ZERO);
+        let _stale = seed(1, Duration::from_secs(60));
+
+        assert_eq!(Some(0), client.get_query_data::<u32, u32>(&0));
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&1));
+    }
+
+    #[test]
+    fn prefetch_skips_fresh_queries() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        let default_stale_time = Some(Duration::from_secs(10));
+
+        let loaded_ago = |key: u32, ago: Duration| {
+            let query = client.cache.get_or_create_query::<u32, u32>(key);
+            query.set_state(QueryState::Loaded(QueryData {
+                data: 0,
+                updated_at: crate::Instant(crate::Instant::now().0 - ago),
+            }));
+            query
+        };
+
+        assert!(client
+            .cache
+            .get_or_create_query::<u32, u32>(0)
+            .needs_prefetch(default_stale_time));
+        assert!(!loaded_ago(1, Duration::from_secs(5)).needs_prefetch(default_stale_time));
+        assert!(loaded_ago(2, Duration::from_secs(60)).needs_prefetch(default_stale_time));
+
+        // The stale time of observers takes precedence.
+        let query = loaded_ago(3, Duration::from_secs(60));
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions::default().set_stale_time(Some(Duration::from_secs(120))),
+            Some(query.clone()),
+        );
+        assert!(!query.needs_prefetch(default_stale_time));
+    }
+
+    #[test]
+    fn offline_queries_are_fetched_on_reconnect() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        assert!(client.is_online().get_untracked());
+
+        client.online.set(false);
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer =
+            QueryObserver::with_fetcher(|_| async { 1 }, QueryOptions::default(), query.clone());
+        assert_eq!(QueryState::Created, query.get_state());
+
+        let stale = client.cache.get_or_create_query::<u32, u32>(1);
+        stale.set_state(QueryState::Loaded(QueryData {
+            data: 0,
+            updated_at: crate::Instant(crate::Instant::now().0 - Duration::from_secs(60)),
+        }));
+        let _stale_observer = QueryObserver::with_fetcher(
+            |_| async { 1 },
+            QueryOptions::default()
+                .set_stale_time(Some(Duration::from_secs(10)))
+                .set_refetch_on_reconnect(true),
+            stale.clone(),
+        );
+        assert_eq!(Some(0), client.get_query_data::<u32, u32>(&1));
+
+        client.online.set(true);
+        client.cache.refetch_on_reconnect();
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&0));
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&1));
+    }
+
+    #[test]
+    fn max_queries_evicts_least_recently_used() {
+        let _ = create_runtime();
+        provide_query_client_with_config(
+            DefaultQueryOptions::default(),
+            QueryClientConfig {
+                max_queries: Some(3),
+                ..QueryClientConfig::default()
+            },
+        );
+        let client = use_query_client();
+        let exists = |key: u32| client.cache.get_query::<u32, u32, _>(&key).is_some();
+
+        let observed = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(observed));
+        client.set_query_data::<u32, u32>(1, 1);
+        client.set_query_data::<u32, String>(2, "2".into());
+        assert_eq!(3, client.size().get_untracked());
+
+        // Accessed after the String query was created.
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&1));
+
+        // The least recently accessed query is evicted, regardless of its type.
+        client.set_query_data::<u32, u32>(3, 3);
+        assert_eq!(3, client.size().get_untracked());
+        assert!(client.cache.get_query::<u32, String, _>(&2).is_none());
+
+        // The observed query is never evicted, and the created query is kept.
+        client.set_query_data::<u32, u32>(4, 4);
+        assert_eq!(3, client.size().get_untracked());
+        assert!(exists(0) && !exists(1) && exists(3) && exists(4));
+    }
+
+    #[test]
+    fn subscribe_events() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let key = client.subscribe_events({
+            let events = events.clone();
+            move |event| {
+                let event = match event {
+                    CacheEvent::Created(query) => format!("created {}", query.key.0),
+                    CacheEvent::Updated(query) => format!("updated {}", query.key.0),
+                    CacheEvent::Removed(key) => format!("removed {}", key.0),
+                    _ => return,
+                };
+                events.borrow_mut().push(event);
+            }
+        });
+
+        client.cache.get_or_create_query::<u32, u32>(0);
+        client.set_query_data::<u32, u32>(0, 1);
+        client.cache.evict_query::<u32, u32>(&0);
+        assert_eq!(
+            vec!["created 0", "updated 0", "removed 0"],
+            *RefCell::borrow(&events)
+        );
+
+        assert!(client.unsubscribe_events(key));
+        assert!(!client.unsubscribe_events(key));
+        client.set_query_data::<u32, u32>(1, 1);
+        assert_eq!(3, RefCell::borrow(&events).len());
+    }
+
+    #[test]
+    fn cancel_queries_where() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        assert_eq!(0, client.cancel_queries_where::<u32, u32>(|_| true));
+
+        client.set_query_data::<u32, u32>(0, 10);
+        let queries = [0, 1, 2].map(|key| client.cache.get_or_create_query::<u32, u32>(key));
+
+        let mut senders = Vec::new();
+        let mut fetches = Vec::new();
+        for query in queries.iter() {
+            let (sender, receiver) = oneshot::channel::<u32>();
+            let receiver = RefCell::new(Some(receiver));
+            senders.push(sender);
+            let fetcher = move |_: u32| {
+                receiver
+                    .borrow_mut()
+                    .take()
+                    .expect("Fetcher called once")
+                    .map(|value| value.unwrap_or_default())
+            };
+            fetches.push(query::execute_query(query.clone(), fetcher).boxed_local());
+        }
+        for fetch in fetches.iter_mut() {
+            assert!(fetch.now_or_never().is_none());
+        }
+
+        assert_eq!(2, client.cancel_queries_where::<u32, u32>(|key| *key < 2));
+        assert_eq!(0, client.cancel_queries_where::<u32, u32>(|key| *key < 2));
+
+        for sender in senders {
+            sender.send(20).unwrap();
+        }
+        for fetch in fetches.iter_mut() {
+            assert!(fetch.now_or_never().is_some());
+        }
+
+        // Cancelled queries revert to their previous state.
+        assert_eq!(Some(10), client.get_query_data::<u32, u32>(&0));
+        assert!(queries[1].with_state(|state| matches!(state, QueryState::Created)));
+        assert_eq!(Some(20), client.get_query_data::<u32, u32>(&2));
+    }
+
+    #[test]
+    fn structural_sharing_keeps_equal_data() {
+        use crate::query_observer::QueryObserver;
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        // Only the id is compared, the revision stands in for a new allocation.
+        let response = Rc::new(Cell::new((1_u32, 1_u32)));
+        let query = client.cache.get_or_create_query::<u32, (u32, u32)>(0);
+        let _observer = QueryObserver::with_fetcher(
+            {
+                let response = response.clone();
+                move |_| {
+                    let response = response.get();
+                    async move { response }
+                }
+            },
+            QueryOptions::default().set_structural_sharing_fn(Some(Rc::new(
+                |a: &(u32, u32), b: &(u32, u32)| a.0 == b.0,
+            ))),
+            query.clone(),
+        );
+        query.execute();
+        assert_eq!(Some((1, 1)), client.get_query_data::<u32, (u32, u32)>(&0));
+        let updated_at = query.get_updated_at().unwrap();
+
+        std::thread::sleep(Duration::from_millis(2));
+        response.set((1, 2));
+        query.execute();
+        assert_eq!(Some((1, 1)), client.get_query_data::<u32, (u32, u32)>(&0));
+        assert!(query.get_updated_at().unwrap() > updated_at);
+
+        response.set((2, 3));
+        query.execute();
+        assert_eq!(Some((2, 3)), client.get_query_data::<u32, (u32, u32)>(&0));
+
+        let options = QueryOptions::<u32>::default().set_structural_sharing(true);
+        assert!(options
+            .structural_sharing
+            .is_some_and(|equal| equal(&1, &1) && !equal(&1, &2)));
+    }
+
+    #[test]
+    fn fetch_times_out() {
+        use crate::query_observer::QueryObserver;
+        use crate::test_timer::TestTimer;
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+        use std::{cell::RefCell, rc::Rc, time::Duration};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        let timer = TestTimer::install();
+        let timeout = Duration::from_millis(10);
+        let delay = Duration::from_millis(100);
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions::default()
+                .set_timeout(Some(timeout))
+                .set_retry(Some(1))
+                .set_retry_delay(Some(Rc::new(move |_| delay))),
+            Some(query.clone()),
+        );
+
+        // Each attempt times out on its own, then the query fails.
+        let mut fetch = query::execute_fallible_query(query.clone(), |_| {
+            futures::future::pending::<Result<u32, QueryError>>()
+        })
+        .boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(vec![timeout], timer.requested());
+        assert_eq!(1, timer.fire());
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(vec![timeout, delay], timer.requested());
+        assert_eq!(1, timer.fire());
+        assert!((&mut fetch).now_or_never().is_none());
+        assert!(query.is_fetching());
+        assert_eq!(vec![timeout, delay, timeout], timer.requested());
+        assert_eq!(1, timer.fire());
+        assert!(fetch.now_or_never().is_some());
+        assert!(!query.is_fetching());
+        let error = query.with_state(|state| state.error().cloned()).unwrap();
+        assert_eq!(
+            Some(QueryTimeout(timeout)),
+            error.downcast::<QueryTimeout>().map(|timeout| *timeout)
+        );
+
+        // An infallible first load goes back to created.
+        let query = client.cache.get_or_create_query::<u32, u32>(1);
+        let _observer = QueryObserver::no_fetcher(
+            QueryOptions::default().set_timeout(Some(timeout)),
+            Some(query.clone()),
+        );
+        let mut fetch =
+            query::execute_query(query.clone(), |_| futures::future::pending()).boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        assert!(query.with_state(|state| matches!(state, QueryState::Loading)));
+        assert_eq!(1, timer.fire());
+        assert!(fetch.now_or_never().is_some());
+        assert!(query.with_state(|state| matches!(state, QueryState::Created)));
+
+        // A fetch that completes in time is kept.
+        let (sender, receiver) = oneshot::channel::<u32>();
+        let receiver = RefCell::new(Some(receiver));
+        let mut fetch = query::execute_query(query.clone(), move |_| {
+            receiver
+                .borrow_mut()
+                .take()
+                .expect("Fetcher called once")
+                .map(|value| value.unwrap_or_default())
+        })
+        .boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        sender.send(1).unwrap();
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&1));
+        // The pending timeout no longer matters.
+        assert_eq!(1, timer.fire());
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&1));
+
+        // An infallible refetch keeps the previous data.
+        let mut fetch =
+            query::execute_query(query.clone(), |_| futures::future::pending()).boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        assert!(query.with_state(|state| matches!(state, QueryState::Fetching(_))));
+        assert_eq!(1, timer.fire());
+        assert!(fetch.now_or_never().is_some());
+        assert!(query.with_state(|state| matches!(state, QueryState::Loaded(_))));
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&1));
+    }
+
+    #[test]
+    fn focus_throttle() {
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client_with_config(
+            DefaultQueryOptions::default(),
+            QueryClientConfig {
+                focus_throttle: Some(Duration::from_secs(5)),
+                ..QueryClientConfig::default()
+            },
+        );
+        let throttle = use_query_client().focus_throttle;
+
+        assert!(throttle.passes(1_000.0));
+        // Every observer handles the same event.
+        assert!(throttle.passes(1_000.0));
+        // Later events within the window are ignored.
+        assert!(!throttle.passes(2_000.0));
+        assert!(!throttle.passes(5_999.0));
+        assert!(throttle.passes(6_000.0));
+        assert!(!throttle.passes(7_000.0));
+
+        // Unthrottled by default.
+        let throttle = FocusThrottle::new(None);
+        assert!(throttle.passes(1_000.0));
+        assert!(throttle.passes(1_001.0));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn key_type_with_multiple_value_types() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, 1);
+        assert_eq!(None, client.cache.check_value_type::<u32, u32>());
+        assert_eq!(Some("u32"), client.cache.check_value_type::<u32, String>());
+        assert_eq!(None, client.cache.check_value_type::<u64, String>());
+
+        // The caches stay separate.
+        client.set_query_data::<u32, String>(0, "1".to_string());
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&0));
+        assert_eq!(
+            Some("1".to_string()),
+            client.get_query_data::<u32, String>(&0)
+        );
+    }
+
+    #[test]
+    fn isolated_stale_time() {
+        use crate::query_observer::QueryObserver;
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        query.set_state(QueryState::Loaded(QueryData {
+            data: 1,
+            updated_at: Instant(Instant::now().0 - Duration::from_secs(30)),
+        }));
+
+        let patient = QueryObserver::no_fetcher(
+            QueryOptions::default().set_stale_time(Some(Duration::from_secs(60))),
+            Some(query.clone()),
+        );
+        let eager = QueryObserver::no_fetcher(
+            QueryOptions::default()
+                .set_stale_time(Some(Duration::from_secs(10)))
+                .set_isolated_stale_time(true),
+            Some(query.clone()),
+        );
+
+        // The short stale time only applies to the observer that set it.
+        assert!(eager.is_stale(&query));
+        assert!(!patient.is_stale(&query));
+        assert!(!query.is_stale());
+
+        // Shared stale times are minimized across observers.
+        let _shared = QueryObserver::no_fetcher(
+            QueryOptions::default().set_stale_time(Some(Duration::from_secs(10))),
+            Some(query.clone()),
+        );
+        assert!(query.is_stale());
+    }
+
+    #[test]
+    fn refetch_on_focus_respects_stale_time() {
+        use crate::query_observer::QueryObserver;
+        use std::{cell::Cell, rc::Rc, time::Duration};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        query.set_state(QueryState::Loaded(QueryData {
+            data: 1,
+            updated_at: Instant::now(),
+        }));
+
+        let fetches = Rc::new(Cell::new(0));
+        let observer = QueryObserver::with_fetcher(
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 2 }
+                }
+            },
+            QueryOptions::default()
+                .set_stale_time(Some(Duration::from_secs(60)))
+                .set_refetch_on_focus(true),
+            query.clone(),
+        );
+
+        // Focusing while the data is fresh doesn't refetch.
+        observer.ensure_execute(&query);
+        assert_eq!(0, fetches.get());
+
+        query.set_state(QueryState::Loaded(QueryData {
+            data: 1,
+            updated_at: Instant(Instant::now().0 - Duration::from_secs(120)),
+        }));
+        observer.ensure_execute(&query);
+        assert_eq!(1, fetches.get());
+        assert_eq!(
+            Some(2),
+            client
+                .peek_query_state::<u32, u32>(&0)
+                .and_then(|s| s.data().copied())
+        );
+    }
+
+    #[test]
+    fn replaced_fetcher_is_used_on_next_fetch() {
+        use crate::query_observer::QueryObserver;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, String>(0);
+        let _observer = QueryObserver::with_fetcher(
+            |_| async { "stale token".to_string() },
+            QueryOptions::default(),
+            query.clone(),
+        );
+        query.execute();
+        assert_eq!(
+            Some("stale token".to_string()),
+            client.get_query_data::<u32, String>(&0)
+        );
+
+        client.set_query_fetcher(&0_u32, |_| async { "fresh token".to_string() });
+        query.execute();
+        assert_eq!(
+            Some("fresh token".to_string()),
+            client.get_query_data::<u32, String>(&0)
+        );
+
+        // A newer observer's fetcher takes precedence.
+        let _newer = QueryObserver::with_fetcher(
+            |_| async { "newer token".to_string() },
+            QueryOptions::default(),
+            query.clone(),
+        );
+        query.execute();
+        assert_eq!(
+            Some("newer token".to_string()),
+            client.get_query_data::<u32, String>(&0)
+        );
+    }
+
+    #[test]
+    fn retry_skips_retry_delay() {
+        use crate::query_observer::QueryObserver;
+        use crate::test_timer::TestTimer;
+        use futures::FutureExt;
+        use std::{rc::Rc, time::Duration};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        let timer = TestTimer::install();
+        let delay = Duration::from_millis(100);
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let observer = QueryObserver::no_fetcher(
+            QueryOptions::default()
+                .set_retry(Some(1))
+                .set_retry_delay(Some(Rc::new(move |_| delay))),
+            Some(query.clone()),
+        );
+
+        let mut fetch = query::execute_fallible_query(query.clone(), |_| async {
+            Err::<u32, _>(QueryError::new("Unavailable"))
+        })
+        .boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        assert_eq!(vec![delay], timer.requested());
+        observer.set_fetcher(|_| async { Ok(1_u32) });
+
+        // A refetch waits for the request in flight.
+        query.execute();
+        assert!(query.is_fetching());
+        assert_eq!(None, client.get_query_data::<u32, u32>(&0));
+
+        // A retry replaces it and fetches right away.
+        query.retry();
+        assert!(!query.is_fetching());
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&0));
+
+        // The replaced request doesn't retry anymore.
+        assert_eq!(1, timer.fire());
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(vec![delay], timer.requested());
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&0));
+    }
+
+    #[test]
+    fn fetching_count() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let count = client.fetching_count();
+        let is_fetching_any = client.is_fetching_any();
+        assert_eq!(0, count.get_untracked());
+        assert!(!is_fetching_any.get_untracked());
+
+        // Queries of different types are counted together.
+        let (number_sender, number_receiver) = oneshot::channel::<u32>();
+        let number_receiver = RefCell::new(Some(number_receiver));
+        let number = client.cache.get_or_create_query::<u32, u32>(0);
+        let mut number_fetch = query::execute_query(number, move |_| {
+            number_receiver
+                .borrow_mut()
+                .take()
+                .expect("Fetcher called once")
+                .map(|value| value.unwrap_or_default())
+        })
+        .boxed_local();
+        let (text_sender, text_receiver) = oneshot::channel::<String>();
+        let text_receiver = RefCell::new(Some(text_receiver));
+        client.set_query_data::<String, String>("a".into(), "a".into());
+        let text = client
+            .cache
+            .get_or_create_query::<String, String>("a".into());
+        let mut text_fetch = query::execute_query(text, move |_| {
+            text_receiver
+                .borrow_mut()
+                .take()
+                .expect("Fetcher called once")
+                .map(|value| value.unwrap_or_default())
+        })
+        .boxed_local();
+        assert!((&mut number_fetch).now_or_never().is_none());
+        assert!((&mut text_fetch).now_or_never().is_none());
+        assert_eq!(2, count.get_untracked());
+        assert!(is_fetching_any.get_untracked());
+
+        number_sender.send(1).unwrap();
+        assert!(number_fetch.now_or_never().is_some());
+        assert_eq!(1, count.get_untracked());
+
+        text_sender.send("b".into()).unwrap();
+        assert!(text_fetch.now_or_never().is_some());
+        assert_eq!(0, count.get_untracked());
+        assert!(!is_fetching_any.get_untracked());
+    }
+
+    #[test]
+    fn update_queries() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        for key in 0..3 {
+            client.set_query_data::<u32, u32>(key, key);
+        }
+        client.cache.get_or_create_query::<u32, u32>(3);
+
+        let notified = Rc::new(Cell::new(0));
+        let _observers = [0, 1].map(|key| {
+            let observer = QueryObserver::no_fetcher(
+                QueryOptions::default(),
+                client.cache.get_query::<u32, u32, _>(&key),
+            );
+            let notified = notified.clone();
+            let listener = observer.add_listener(move |_| notified.set(notified.get() + 1));
+            (observer, listener)
+        });
+
+        // Only even numbers change.
+        let updated = client.update_queries::<u32, u32>(|_, state| match state.data_mut() {
+            Some(data) if *data % 2 == 0 => {
+                *data += 10;
+                true
+            }
+            _ => false,
+        });
+        assert_eq!(2, updated);
+        assert_eq!(1, notified.get());
+        assert_eq!(Some(10), client.get_query_data::<u32, u32>(&0));
+        assert_eq!(Some(1), client.get_query_data::<u32, u32>(&1));
+        assert_eq!(Some(12), client.get_query_data::<u32, u32>(&2));
+        assert_eq!(
+            Some(FetchSource::Manual),
+            client
+                .cache
+                .get_query::<u32, u32, _>(&0)
+                .unwrap()
+                .get_fetch_source()
+        );
+
+        assert_eq!(0, client.update_queries::<u32, String>(|_, _| true));
+    }
+
+    #[test]
+    fn ensure_query_data() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let fetcher = {
+            let fetches = fetches.clone();
+            move |key: u32| {
+                fetches.set(fetches.get() + 1);
+                async move { key + 10 }
+            }
+        };
+
+        // Fresh data is returned from the cache.
+        client.set_query_data::<u32, u32>(0, 1);
+        let data = client.ensure_query_data(0, fetcher.clone()).now_or_never();
+        assert_eq!(Some(1), data);
+        assert_eq!(0, fetches.get());
+
+        // Missing data is fetched and cached.
+        let data = client.ensure_query_data(1, fetcher.clone()).now_or_never();
+        assert_eq!(Some(11), data);
+        assert_eq!(1, fetches.get());
+        assert_eq!(Some(11), client.get_query_data::<u32, u32>(&1));
+
+        // An in-flight request is awaited.
+        let (sender, receiver) = oneshot::channel::<u32>();
+        let receiver = RefCell::new(Some(receiver));
+        let query = client.cache.get_or_create_query::<u32, u32>(2);
+        // Keeps the query observed, so dropping the listener doesn't schedule garbage collection.
+        let _observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+        let mut fetch = query::execute_query(query, move |_| {
+            receiver
+                .borrow_mut()
+                .take()
+                .expect("Fetcher called once")
+                .map(|value| value.unwrap_or_default())
+        })
+        .boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+
+        let mut ensure = client.ensure_query_data(2, fetcher).boxed_local();
+        assert!((&mut ensure).now_or_never().is_none());
+        sender.send(2).unwrap();
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(Some(2), ensure.now_or_never());
+        assert_eq!(1, fetches.get());
+    }
+
+    #[test]
+    fn prefetch_queries_awaits_in_flight_requests() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetched = Rc::new(RefCell::new(Vec::new()));
+        let fetcher = {
+            let fetched = fetched.clone();
+            move |key: u32| {
+                fetched.borrow_mut().push(key);
+                async move { key + 10 }
+            }
+        };
+
+        let (sender, receiver) = oneshot::channel::<u32>();
+        let receiver = RefCell::new(Some(receiver));
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let _observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+        let mut fetch = query::execute_query(query, move |_| {
+            receiver
+                .borrow_mut()
+                .take()
+                .expect("Fetcher called once")
+                .map(|value| value.unwrap_or_default())
+        })
+        .boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        client.set_query_data::<u32, u32>(2, 2);
+
+        // Only the missing query is fetched, and the request in flight is awaited.
+        let mut prefetch = client
+            .prefetch_queries(vec![0, 1, 2], fetcher)
+            .boxed_local();
+        assert!((&mut prefetch).now_or_never().is_none());
+        assert_eq!(vec![1], *RefCell::borrow(&fetched));
+        assert_eq!(Some(11), client.get_query_data::<u32, u32>(&1));
+
+        sender.send(0).unwrap();
+        assert!(fetch.now_or_never().is_some());
+        assert!(prefetch.now_or_never().is_some());
+        assert_eq!(Some(0), client.get_query_data::<u32, u32>(&0));
+        assert_eq!(vec![1], *RefCell::borrow(&fetched));
+    }
+
+    #[test]
+    fn prefetch_queries_result() {
+        use futures::FutureExt;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(2, 2);
+
+        let results = client
+            .prefetch_queries_result(vec![0, 1, 2], |key: u32| async move {
+                if key == 1 {
+                    Err("not found")
+                } else {
+                    Ok(key + 10)
+                }
+            })
+            .now_or_never()
+            .expect("Prefetched");
+
+        let results = results
+            .into_iter()
+            .map(|(key, result)| (key, result.map_err(|error| error.to_string())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![(0, Ok(())), (1, Err("not found".to_string())), (2, Ok(()))],
+            results
+        );
+        assert_eq!(Some(10), client.get_query_data::<u32, u32>(&0));
+        assert_eq!(Some(2), client.get_query_data::<u32, u32>(&2));
+    }
+
+    #[test]
+    fn fetch_query_data_awaits_in_flight_request() {
+        use futures::FutureExt;
+        use futures_channel::oneshot;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let fetcher = {
+            let fetches = fetches.clone();
+            move |key: u32| {
+                fetches.set(fetches.get() + 1);
+                async move { key + 10 }
+            }
+        };
+
+        assert_eq!(
+            Some(Some(10)),
+            client.fetch_query_data(0, fetcher.clone()).now_or_never()
+        );
+        assert_eq!(1, fetches.get());
+
+        // The first load is awaited instead of resolving without data.
+        let (sender, receiver) = oneshot::channel::<u32>();
+        let receiver = RefCell::new(Some(receiver));
+        let query = client.cache.get_or_create_query::<u32, u32>(1);
+        let _observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+        let mut fetch = query::execute_query(query, move |_| {
+            receiver
+                .borrow_mut()
+                .take()
+                .expect("Fetcher called once")
+                .map(|value| value.unwrap_or_default())
+        })
+        .boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+
+        let mut data = client.fetch_query_data(1, fetcher).boxed_local();
+        assert!((&mut data).now_or_never().is_none());
+        sender.send(1).unwrap();
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(Some(Some(1)), data.now_or_never());
+        assert_eq!(1, fetches.get());
+    }
+
+    #[test]
+    fn created_query_evict() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let created = Rc::new(RefCell::new(None));
+        client.subscribe_events({
+            let created = created.clone();
+            move |event| {
+                if let CacheEvent::Created(query) = event {
+                    *created.borrow_mut() = Some(query);
+                }
+            }
+        });
+
+        client.set_query_data::<u32, u32>(0, 1);
+        let query = created.borrow_mut().take().expect("Created event");
+        assert!((query.evict)());
+        assert!(client.get_query_data::<u32, u32>(&0).is_none());
+        assert!(!(query.evict)());
+    }
 }
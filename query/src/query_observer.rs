@@ -1,25 +1,30 @@
-use std::cell::{Cell, RefCell};
+use std::cell::{Cell, Ref, RefCell};
 use std::future::Future;
+use std::time::Duration;
 use std::{pin::Pin, rc::Rc};
 
-use leptos::leptos_dom::helpers::IntervalHandle;
+use leptos::leptos_dom::helpers::{IntervalHandle, WindowListenerHandle};
 use slotmap::{new_key_type, SlotMap};
 
 use crate::query::Query;
-use crate::{QueryKey, QueryOptions, QueryState, QueryValue};
+use crate::{QueryError, QueryKey, QueryOptions, QueryState, QueryValue};
 
 #[derive(Clone)]
 pub struct QueryObserver<K, V> {
     id: ObserverKey,
     query: Rc<RefCell<Option<Query<K, V>>>>,
-    fetcher: Option<Fetcher<K, V>>,
+    fetcher: Rc<RefCell<Option<Fetcher<K, V>>>>,
     refetch: Rc<Cell<Option<IntervalHandle>>>,
-    options: QueryOptions<V>,
+    focus_listeners: Rc<RefCell<Vec<WindowListenerHandle>>>,
+    // Shared with the clones held by the query, so runtime changes apply to all of them.
+    options: Rc<RefCell<QueryOptions<V>>>,
+    // The fetcher can't fail, so a timed out fetch reverts the state instead of failing the query.
+    infallible: bool,
     #[allow(clippy::type_complexity)]
     listeners: Rc<RefCell<SlotMap<ListenerKey, Box<dyn Fn(&QueryState<V>)>>>>,
 }
 
-type Fetcher<K, V> = Rc<dyn Fn(K) -> Pin<Box<dyn Future<Output = V>>>>;
+type Fetcher<K, V> = Rc<dyn Fn(K) -> Pin<Box<dyn Future<Output = Result<V, QueryError>>>>>;
 
 new_key_type! {
     pub struct ListenerKey;
@@ -34,9 +39,11 @@ where
         f.debug_struct("QueryObserver")
             .field("id", &self.id)
             .field("query", &self.query)
-            .field("fetcher", &self.fetcher.is_some())
+            .field("fetcher", &self.fetcher.borrow().is_some())
             .field("refetch", &self.refetch.get().is_some())
-            .field("options", &self.options)
+            .field("focus_listeners", &self.focus_listeners.borrow().len())
+            .field("options", &*self.options.borrow())
+            .field("infallible", &self.infallible)
             .field("listeners", &self.listeners.borrow().len())
             .finish()
     }
@@ -47,65 +54,54 @@ where
     K: QueryKey + 'static,
     V: QueryValue + 'static,
 {
+    #[cfg(test)]
     pub fn with_fetcher<F, Fu>(fetcher: F, options: QueryOptions<V>, query: Query<K, V>) -> Self
     where
         F: Fn(K) -> Fu + 'static,
         Fu: Future<Output = V> + 'static,
     {
-        let fetcher =
-            Some(
-                Rc::new(move |s| Box::pin(fetcher(s)) as Pin<Box<dyn Future<Output = V>>>)
-                    as Fetcher<K, V>,
-            );
-        let query = Rc::new(RefCell::new(Some(query)));
-        let id = next_id();
+        use futures::FutureExt;
 
-        #[cfg(any(feature = "csr", feature = "hydrate"))]
-        let refetch = {
-            use leptos::logging;
+        Self::with_fetcher_of_kind(move |key| fetcher(key).map(Ok), options, query, true)
+    }
 
-            let interval = {
-                if let Some(refetch_interval) = options.refetch_interval {
-                    let query = query.clone();
-                    let timeout = leptos::set_interval_with_handle(
-                        move || {
-                            if let Ok(query) = query.try_borrow() {
-                                if let Some(query) = query.as_ref() {
-                                    query.execute()
-                                }
-                            } else {
-                                logging::debug_warn!("QueryObserver: Query is already borrowed");
-                            }
-                        },
-                        refetch_interval,
-                    )
-                    .ok();
-                    if timeout.is_none() {
-                        logging::debug_warn!("QueryObserver: Failed to set refetch interval");
-                    }
-                    timeout
-                } else {
-                    None
-                }
-            };
-            Rc::new(Cell::new(interval))
-        };
-        #[cfg(not(any(feature = "csr", feature = "hydrate")))]
-        let refetch = Rc::new(Cell::new(None));
+    /// Creates an observer, an `infallible` fetcher never returns an error.
+    pub(crate) fn with_fetcher_of_kind<F, Fu>(
+        fetcher: F,
+        options: QueryOptions<V>,
+        query: Query<K, V>,
+        infallible: bool,
+    ) -> Self
+    where
+        F: Fn(K) -> Fu + 'static,
+        Fu: Future<Output = Result<V, QueryError>> + 'static,
+    {
+        let fetcher = Rc::new(RefCell::new(Some(make_fetcher(fetcher))));
+        let query = Rc::new(RefCell::new(Some(query)));
+        let id = next_id();
 
         let observer = Self {
             id,
             query: query.clone(),
             fetcher,
-            refetch,
-            options,
+            refetch: Rc::new(Cell::new(None)),
+            focus_listeners: Rc::new(RefCell::new(Vec::new())),
+            options: Rc::new(RefCell::new(options)),
+            infallible,
             listeners: Rc::new(RefCell::new(SlotMap::with_key())),
         };
+        observer.start_refetch_interval();
+        observer.start_focus_listeners();
 
         if let Some(query) = query.borrow().as_ref() {
             query.subscribe(&observer);
-            if query.is_stale() {
-                query.execute()
+            // Observers that mount together share a single execution.
+            // Invalidated unobserved queries are refetched here, and failed fetches are retried.
+            if observer.is_stale(query)
+                || query
+                    .with_state(|s| matches!(s, QueryState::Invalid(_) | QueryState::Error { .. }))
+            {
+                query.execute_coalesced()
             }
         }
 
@@ -119,9 +115,11 @@ where
         let observer = Self {
             id,
             query: query.clone(),
-            fetcher: None,
+            fetcher: Rc::new(RefCell::new(None)),
             refetch: Rc::new(Cell::new(None)),
-            options,
+            focus_listeners: Rc::new(RefCell::new(Vec::new())),
+            options: Rc::new(RefCell::new(options)),
+            infallible: false,
             listeners: Rc::new(RefCell::new(SlotMap::with_key())),
         };
 
@@ -133,15 +131,118 @@ where
     }
 
     pub fn get_fetcher(&self) -> Option<Fetcher<K, V>> {
-        self.fetcher.clone()
+        self.fetcher.borrow().clone()
+    }
+
+    pub(crate) fn is_infallible(&self) -> bool {
+        self.infallible
+    }
+
+    /// Replaces the fetcher, the new fetcher is used on the next execution.
+    /// Useful when the previous fetcher captured outdated values, e.g. an expired auth token.
+    pub fn set_fetcher<F, Fu>(&self, fetcher: F)
+    where
+        F: Fn(K) -> Fu + 'static,
+        Fu: Future<Output = Result<V, QueryError>> + 'static,
+    {
+        *self.fetcher.borrow_mut() = Some(make_fetcher(fetcher));
     }
 
     pub fn get_id(&self) -> ObserverKey {
         self.id
     }
 
-    pub fn get_options(&self) -> &QueryOptions<V> {
-        &self.options
+    pub fn get_options(&self) -> Ref<'_, QueryOptions<V>> {
+        self.options.borrow()
+    }
+
+    /// Updates the stale time, gc time and refetch interval, re-arming the refetch interval.
+    pub fn set_timing(
+        &self,
+        stale_time: Option<Duration>,
+        gc_time: Option<Duration>,
+        refetch_interval: Option<Duration>,
+    ) {
+        {
+            let mut options = self.options.borrow_mut();
+            options.stale_time = stale_time;
+            options.gc_time = gc_time;
+            options.refetch_interval = refetch_interval;
+        }
+        self.clear_refetch_interval();
+        self.start_refetch_interval();
+    }
+
+    // Only polls on the client.
+    fn start_refetch_interval(&self) {
+        #[cfg(any(feature = "csr", feature = "hydrate"))]
+        {
+            use leptos::logging;
+
+            let refetch_interval = {
+                let options = self.options.borrow();
+                options.refetch_interval.filter(|_| !options.cache_only)
+            };
+            if let Some(refetch_interval) = refetch_interval {
+                let query = self.query.clone();
+                let options = self.options.clone();
+                let timeout = leptos::set_interval_with_handle(
+                    move || {
+                        // Paused while disabled, as executing would fetch through the other observers' fetchers.
+                        if !options.borrow().fetches() {
+                            return;
+                        }
+                        // A cleaned up observer no longer holds the query, and a query without observers has no fetcher,
+                        // so polling stops once the query is unobserved, even before it's garbage collected.
+                        if let Ok(query) = query.try_borrow() {
+                            if let Some(query) = query.as_ref() {
+                                query.execute()
+                            }
+                        } else {
+                            logging::debug_warn!("QueryObserver: Query is already borrowed");
+                        }
+                    },
+                    refetch_interval,
+                )
+                .ok();
+                if timeout.is_none() {
+                    logging::debug_warn!("QueryObserver: Failed to set refetch interval");
+                }
+                self.refetch.set(timeout);
+            }
+        }
+    }
+
+    // Only listens on the client, as it touches the window.
+    fn start_focus_listeners(&self) {
+        #[cfg(any(feature = "csr", feature = "hydrate"))]
+        {
+            let refetch_on_focus = {
+                let options = self.options.borrow();
+                options.refetch_on_focus && !options.cache_only
+            };
+            if refetch_on_focus {
+                let focus_throttle = crate::use_query_client().focus_throttle;
+                let handles = ["focus", "visibilitychange"].map(|event| {
+                    let query = self.query.clone();
+                    let options = self.options.clone();
+                    let focus_throttle = focus_throttle.clone();
+                    leptos::window_event_listener_untyped(event, move |event| {
+                        if leptos::document().hidden() || !focus_throttle.passes(event.time_stamp())
+                        {
+                            return;
+                        }
+                        // A cleaned up observer no longer holds the query.
+                        if let Some(query) = query.borrow().as_ref() {
+                            if needs_execute(&options.borrow(), query) {
+                                query.execute();
+                            }
+                        }
+                    })
+                });
+                self.focus_listeners.borrow_mut().extend(handles);
+            }
+        }
     }
 
     pub fn notify(&self, state: QueryState<V>) {
@@ -171,10 +272,10 @@ where
 
     pub fn update_query(&self, new_query: Option<Query<K, V>>) {
         // Determine if the new query is the same as the current one.
-        let is_same_query = self.query.borrow().as_ref().map_or(false, |current_query| {
-            new_query.as_ref().map_or(false, |new_query| {
-                new_query.get_key() == current_query.get_key()
-            })
+        let is_same_query = self.query.borrow().as_ref().is_some_and(|current_query| {
+            new_query
+                .as_ref()
+                .is_some_and(|new_query| new_query.get_key() == current_query.get_key())
         });
 
         // If the new query is the same as the current, do nothing.
@@ -193,7 +294,23 @@ where
         if let Some(ref query) = new_query {
             // Subscribe to the new query and ensure it's executed.
             query.subscribe(self);
-            query.ensure_execute();
+            self.ensure_execute(query);
+        }
+    }
+
+    pub(crate) fn is_stale(&self, query: &Query<K, V>) -> bool {
+        is_stale(&self.options.borrow(), query)
+    }
+
+    pub(crate) fn ensure_execute(&self, query: &Query<K, V>) {
+        if needs_execute(&self.options.borrow(), query) {
+            query.execute();
+        }
+    }
+
+    pub fn clear_refetch_interval(&self) {
+        if let Some(interval) = self.refetch.take() {
+            interval.clear();
         }
     }
 
@@ -202,8 +319,9 @@ where
             query.unsubscribe(self);
         }
 
-        if let Some(interval) = self.refetch.take() {
-            interval.clear();
+        self.clear_refetch_interval();
+        for handle in self.focus_listeners.take() {
+            handle.remove();
         }
 
         if !self
@@ -219,11 +337,40 @@ where
     }
 }
 
+fn is_stale<K, V>(options: &QueryOptions<V>, query: &Query<K, V>) -> bool
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    if options.isolated_stale_time {
+        query.is_stale_for(query.jittered_stale_time(options.stale_time, options.stale_time_jitter))
+    } else {
+        query.is_stale()
+    }
+}
+
+fn needs_execute<K, V>(options: &QueryOptions<V>, query: &Query<K, V>) -> bool
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    query.needs_initial_execute() || is_stale(options, query)
+}
+
+fn make_fetcher<K, V, F, Fu>(fetcher: F) -> Fetcher<K, V>
+where
+    F: Fn(K) -> Fu + 'static,
+    Fu: Future<Output = Result<V, QueryError>> + 'static,
+{
+    Rc::new(move |s| Box::pin(fetcher(s)) as Pin<Box<dyn Future<Output = Result<V, QueryError>>>>)
+}
+
 thread_local! {
     static NEXT_ID: Cell<u32> = const { Cell::new(1) } ;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// Ids are increasing, so a greater id belongs to a more recently created observer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ObserverKey(u32);
 
 fn next_id() -> ObserverKey {
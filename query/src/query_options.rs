@@ -1,4 +1,8 @@
-use std::time::Duration;
+use std::{rc::Rc, time::Duration};
+
+use leptos::{Signal, SignalGetUntracked};
+
+use crate::{query_options_provider::ScopedQueryOptions, Instant};
 
 /// Default options for all queries under this client.
 /// Only differs from [`QueryOptions`] in that it doesn't have a default value.
@@ -25,13 +29,62 @@ impl Default for DefaultQueryOptions {
     }
 }
 
+/// Settings for the whole client, passed to [`provide_query_client_with_config()`](crate::provide_query_client_with_config).
+/// Unlike [`DefaultQueryOptions`], these can't be overridden per query or per scope.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryClientConfig {
+    /// Time after a fetch completes during which another fetch of the same query is skipped, returning the cached data.
+    /// Protects against burst fetching, e.g. back to back refetches, independent of stale time.
+    /// Invalidated queries are always fetched.
+    /// Default is None.
+    pub dedup_ttl: Option<Duration>,
+    /// Evict queries past their gc time whenever the window regains focus or becomes visible.
+    /// Timeouts are throttled in background tabs and don't run while the device sleeps, so unused queries may otherwise linger.
+    /// Default is false.
+    pub gc_on_focus: bool,
+    /// Minimum time between two focus events that refetch, see [`QueryOptions::refetch_on_focus`].
+    /// Prevents a burst of refetches when the user switches back and forth between windows.
+    /// Applies to all queries of the client together, a focus event within the throttle window of the last one is ignored.
+    /// Default is None.
+    pub focus_throttle: Option<Duration>,
+    /// Maximum number of queries in the cache.
+    /// Once exceeded, the least recently accessed query without observers is evicted.
+    /// Queries with observers are never evicted this way, so the cache may still exceed the limit.
+    /// Default is None.
+    pub max_queries: Option<usize>,
+    /// Time a query without a fetcher may stay [`Created`](crate::QueryState::Created) while it is read by [`use_query_states()`](crate::use_query_states()).
+    /// Afterwards it is skipped like a missing query, until it is loaded, and a warning is logged in debug builds,
+    /// as no enabled [`use_query()`](crate::use_query()) for the key is mounted to fetch it.
+    /// Default is 5 seconds.
+    pub fetcher_grace_period: Option<Duration>,
+    /// Number of errors kept per query, see [`Query::get_error_history()`](crate::Query::get_error_history).
+    /// Once exceeded, the oldest error is dropped, so a query that fails on every refetch doesn't grow without bound.
+    /// Default is 10.
+    pub error_history_size: usize,
+}
+
+impl Default for QueryClientConfig {
+    fn default() -> Self {
+        Self {
+            dedup_ttl: None,
+            gc_on_focus: false,
+            focus_throttle: None,
+            max_queries: None,
+            fetcher_grace_period: Some(DEFAULT_FETCHER_GRACE_PERIOD),
+            error_history_size: DEFAULT_ERROR_HISTORY_SIZE,
+        }
+    }
+}
+
 const DEFAULT_STALE_TIME: Duration = Duration::from_secs(10);
 const DEFAULT_GC_TIME: Duration = Duration::from_secs(60 * 5);
+const DEFAULT_FETCHER_GRACE_PERIOD: Duration = Duration::from_secs(5);
+const DEFAULT_ERROR_HISTORY_SIZE: usize = 10;
 
 /**
  * Options for a query [`use_query()`](crate::use_query())
  */
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct QueryOptions<V> {
     /// Placeholder value to use while the query is loading for the first time.
     pub default_value: Option<V>,
@@ -53,6 +106,128 @@ pub struct QueryOptions<V> {
     pub refetch_interval: Option<Duration>,
     /// Determines which type of resource to use.
     pub resource_option: Option<ResourceOption>,
+    /// If a refetch fails, mark the retained data as invalid instead of failing the query with an error.
+    /// The next read of the query will then retry the fetch.
+    /// Default is false.
+    /// NOTE: If different values are used for the same key, true takes precedence.
+    pub invalidate_on_error: bool,
+    /// Decide staleness using only this observer's stale_time, instead of the minimum across all observers of the key.
+    /// An isolated observer refetches on mount only when the data is stale according to its own stale_time,
+    /// and its stale_time no longer forces refetches for other observers of the same key.
+    /// Default is false.
+    pub isolated_stale_time: bool,
+    /// Tags to group queries by, e.g. all queries of a page section.
+    /// See [`use_is_group_fetching()`](crate::use_is_group_fetching()).
+    pub tags: Vec<String>,
+    /// Lazily computes the initial data of the query.
+    /// Only invoked when the query does not exist in the cache yet, so it's skipped when the cache already has the entry.
+    /// If it returns a value, the query starts out loaded with it, shared by all observers and persisted like fetched data.
+    /// Stale initial data, according to [`initial_data_updated_at`](Self::initial_data_updated_at), is refetched in the background.
+    pub initial_data_fn: Option<Rc<dyn Fn() -> Option<V>>>,
+    /// The time the initial data was last updated, used to decide whether it's stale.
+    /// If none, the initial data is considered updated when the query is created.
+    pub initial_data_updated_at: Option<Instant>,
+    /// Cancel the in-flight request when the query is refetched, and fetch again with the latest fetcher.
+    /// By default a refetch is ignored while a request is in flight. Useful when only the newest request matters, e.g. autocomplete.
+    /// Default is false.
+    /// NOTE: If different values are used for the same key, true takes precedence.
+    pub cancel_previous_on_refetch: bool,
+    /// If true, the query is never fetched by this observer, neither on read nor in the background.
+    /// It only reflects data placed in the cache, e.g. with [`QueryClient::set_query_data()`](crate::QueryClient::set_query_data), a persister or `initial_data`.
+    /// Reads return the cached data, or [`None`](Option::None) instead of suspending.
+    /// Useful for offline first screens, and for tests where the network must not be touched.
+    /// Default is false.
+    /// NOTE: Other observers of the same key that are not cache only may still fetch it.
+    pub cache_only: bool,
+    /// Spreads refetches of queries that go stale together, e.g. after a navigation loaded them at once.
+    /// Each query's stale time is extended by a fraction of this window, derived from its key, so it is stable across reads.
+    /// Default is None.
+    pub stale_time_jitter: Option<Duration>,
+    /// Data to seed the query with on the server, so SSR renders it right away instead of fetching.
+    /// Unlike [`initial_data_fn`](Self::initial_data_fn), which applies everywhere, the client still fetches the real value.
+    /// Default is None.
+    pub ssr_default: Option<V>,
+    /// How many times a failed fetch is retried before the query transitions to [`QueryState::Error`].
+    /// Only fallible fetchers, e.g. of [`use_query_result()`](crate::use_query_result()), can fail.
+    /// Default is None, i.e. no retries.
+    /// NOTE: The options of the most recent observer of the key are used.
+    pub retry: Option<u32>,
+    /// The delay before a retry, given the number of the retry starting at 1. Useful for exponential backoff with jitter.
+    /// Default is None, i.e. 1 second doubling with every retry, up to 30 seconds.
+    pub retry_delay: Option<Rc<dyn Fn(u32) -> Duration>>,
+    /// Data to render while the query is loading for the first time, instead of suspending.
+    /// Unlike [`initial_data_fn`](Self::initial_data_fn), the placeholder is never written into the cache, and [`is_loading`](crate::QueryResult::is_loading) stays true.
+    /// Only applies on the client, use [`ssr_default`](Self::ssr_default) for the server.
+    /// Default is None.
+    pub placeholder_data: Option<Rc<dyn Fn() -> V>>,
+    /// Refetch stale data whenever the window regains focus or becomes visible, e.g. when the user returns to a tab left open for minutes.
+    /// Fresh data, according to the stale time, is not refetched.
+    /// Only applies on the client.
+    /// Default is false.
+    pub refetch_on_focus: bool,
+    /// Refetch stale data when the network connection is restored, e.g. after a flaky connection dropped.
+    /// Fresh data, according to the stale time, is not refetched.
+    /// Only applies on the client.
+    /// Default is false.
+    pub refetch_on_reconnect: bool,
+    /// Whether this observer may fetch the query, e.g. a signal that is false until a dependency such as a user id is known.
+    /// While disabled, the query is neither fetched on read nor in the background, and stays [`QueryState::Created`](crate::QueryState::Created) if it has no data.
+    /// Reads return [`None`](Option::None) instead of suspending, and [`is_loading`](crate::QueryResult::is_loading) is false.
+    /// Once it becomes true, the query is fetched if it needs to be.
+    /// Default is None, i.e. always enabled.
+    /// NOTE: Other observers of the same key that are enabled may still fetch it.
+    pub enabled: Option<Signal<bool>>,
+    /// Decides whether refetched data equals the cached data, in which case the cached value is kept and only its update time changes.
+    /// Observers are still notified that the fetch finished, but the [`data`](crate::QueryResult::data) of a query is not updated, so views of it don't re-render.
+    /// Use [`set_structural_sharing()`](Self::set_structural_sharing) to compare with [`PartialEq`].
+    /// Default is None, i.e. refetched data always replaces the cached data.
+    /// NOTE: The option of the most recent observer of the key is used.
+    #[allow(clippy::type_complexity)]
+    pub structural_sharing: Option<Rc<dyn Fn(&V, &V) -> bool>>,
+    /// Keep returning the data of the previous key while the query of a new key loads for the first time, e.g. the previous page of a paginated list.
+    /// The previous data is replaced once the new key has data, and [`is_previous_data`](crate::QueryResult::is_previous_data) tells the two apart.
+    /// Default is false.
+    pub keep_previous_data: bool,
+    /// How long each attempt of a fetch may take before it is dropped. A timed out attempt is retried like a failed one, see [`retry`](Self::retry).
+    /// Once the retries are exhausted, a fallible query fails with a [`QueryTimeout`](crate::QueryTimeout) error,
+    /// while an infallible query returns to its previous state, e.g. [`QueryState::Created`](crate::QueryState::Created) on the first load.
+    /// Protects against fetchers that never complete, which would otherwise leave the query loading forever.
+    /// Default is None, i.e. no timeout.
+    /// NOTE: The option of the most recent observer of the key is used.
+    pub timeout: Option<Duration>,
+}
+
+impl<V: std::fmt::Debug> std::fmt::Debug for QueryOptions<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryOptions")
+            .field("default_value", &self.default_value)
+            .field("stale_time", &self.stale_time)
+            .field("gc_time", &self.gc_time)
+            .field("refetch_interval", &self.refetch_interval)
+            .field("resource_option", &self.resource_option)
+            .field("invalidate_on_error", &self.invalidate_on_error)
+            .field("isolated_stale_time", &self.isolated_stale_time)
+            .field("tags", &self.tags)
+            .field("initial_data_fn", &self.initial_data_fn.is_some())
+            .field("initial_data_updated_at", &self.initial_data_updated_at)
+            .field(
+                "cancel_previous_on_refetch",
+                &self.cancel_previous_on_refetch,
+            )
+            .field("cache_only", &self.cache_only)
+            .field("stale_time_jitter", &self.stale_time_jitter)
+            .field("ssr_default", &self.ssr_default)
+            .field("retry", &self.retry)
+            .field("retry_delay", &self.retry_delay.is_some())
+            .field("placeholder_data", &self.placeholder_data.is_some())
+            .field("refetch_on_focus", &self.refetch_on_focus)
+            .field("refetch_on_reconnect", &self.refetch_on_reconnect)
+            .field("enabled", &self.enabled)
+            .field("structural_sharing", &self.structural_sharing.is_some())
+            .field("keep_previous_data", &self.keep_previous_data)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
 }
 
 impl<V> QueryOptions<V> {
@@ -90,7 +265,170 @@ impl<V> QueryOptions<V> {
         }
     }
 
+    /// Set whether a failed refetch marks the data as invalid.
+    pub fn set_invalidate_on_error(self, invalidate_on_error: bool) -> Self {
+        QueryOptions {
+            invalidate_on_error,
+            ..self
+        }
+    }
+
+    /// Set whether staleness is decided per observer.
+    pub fn set_isolated_stale_time(self, isolated_stale_time: bool) -> Self {
+        QueryOptions {
+            isolated_stale_time,
+            ..self
+        }
+    }
+
+    /// Set the tags.
+    pub fn set_tags(self, tags: Vec<String>) -> Self {
+        QueryOptions { tags, ..self }
+    }
+
+    /// Set the function that lazily computes the initial data.
+    pub fn set_initial_data_fn(self, initial_data_fn: Option<Rc<dyn Fn() -> Option<V>>>) -> Self {
+        QueryOptions {
+            initial_data_fn,
+            ..self
+        }
+    }
+
+    /// Set the initial data, e.g. data already available from the server render context.
+    /// Shorthand for [`set_initial_data_fn()`](Self::set_initial_data_fn) with a value at hand.
+    pub fn set_initial_data(self, initial_data: V) -> Self
+    where
+        V: Clone + 'static,
+    {
+        self.set_initial_data_fn(Some(Rc::new(move || Some(initial_data.clone()))))
+    }
+
+    /// Set the time the initial data was last updated.
+    pub fn set_initial_data_updated_at(self, initial_data_updated_at: Option<Instant>) -> Self {
+        QueryOptions {
+            initial_data_updated_at,
+            ..self
+        }
+    }
+
+    /// Set whether a refetch cancels the in-flight request.
+    pub fn set_cancel_previous_on_refetch(self, cancel_previous_on_refetch: bool) -> Self {
+        QueryOptions {
+            cancel_previous_on_refetch,
+            ..self
+        }
+    }
+
+    /// Set the cache only option.
+    pub fn set_cache_only(self, cache_only: bool) -> Self {
+        QueryOptions { cache_only, ..self }
+    }
+
+    /// Set the stale time jitter
+    pub fn set_stale_time_jitter(self, stale_time_jitter: Option<Duration>) -> Self {
+        QueryOptions {
+            stale_time_jitter,
+            ..self
+        }
+    }
+
+    /// Set the data the query is seeded with on the server
+    pub fn set_ssr_default(self, ssr_default: Option<V>) -> Self {
+        QueryOptions {
+            ssr_default,
+            ..self
+        }
+    }
+
+    /// Set the number of retries.
+    pub fn set_retry(self, retry: Option<u32>) -> Self {
+        QueryOptions { retry, ..self }
+    }
+
+    /// Set the delay before a retry.
+    pub fn set_retry_delay(self, retry_delay: Option<Rc<dyn Fn(u32) -> Duration>>) -> Self {
+        QueryOptions {
+            retry_delay,
+            ..self
+        }
+    }
+
+    /// Set the placeholder data.
+    pub fn set_placeholder_data(self, placeholder_data: Option<Rc<dyn Fn() -> V>>) -> Self {
+        QueryOptions {
+            placeholder_data,
+            ..self
+        }
+    }
+
+    /// Set whether to refetch stale data when the window regains focus or becomes visible.
+    pub fn set_refetch_on_focus(self, refetch_on_focus: bool) -> Self {
+        QueryOptions {
+            refetch_on_focus,
+            ..self
+        }
+    }
+
+    /// Set whether to refetch stale data when the network connection is restored.
+    pub fn set_refetch_on_reconnect(self, refetch_on_reconnect: bool) -> Self {
+        QueryOptions {
+            refetch_on_reconnect,
+            ..self
+        }
+    }
+
+    /// Set the signal that enables fetching.
+    pub fn set_enabled(self, enabled: Option<Signal<bool>>) -> Self {
+        QueryOptions { enabled, ..self }
+    }
+
+    /// Whether this observer may fetch, i.e. it is neither cache only nor disabled.
+    pub(crate) fn fetches(&self) -> bool {
+        let enabled = match self.enabled {
+            // A disposed signal belongs to an unmounted observer.
+            Some(enabled) => enabled.try_get_untracked().unwrap_or(false),
+            None => true,
+        };
+        enabled && !self.cache_only
+    }
+
+    /// Set the function that decides whether refetched data equals the cached data.
+    #[allow(clippy::type_complexity)]
+    pub fn set_structural_sharing_fn(
+        self,
+        structural_sharing: Option<Rc<dyn Fn(&V, &V) -> bool>>,
+    ) -> Self {
+        QueryOptions {
+            structural_sharing,
+            ..self
+        }
+    }
+
+    /// Set whether to keep the cached data when refetched data is equal to it.
+    /// Shorthand for [`set_structural_sharing_fn()`](Self::set_structural_sharing_fn) comparing with [`PartialEq`].
+    pub fn set_structural_sharing(self, structural_sharing: bool) -> Self
+    where
+        V: PartialEq + 'static,
+    {
+        let structural_sharing = structural_sharing.then(|| Rc::new(V::eq) as Rc<_>);
+        self.set_structural_sharing_fn(structural_sharing)
+    }
+
+    /// Set whether to keep showing the data of the previous key while the new key loads.
+    pub fn set_keep_previous_data(self, keep_previous_data: bool) -> Self {
+        QueryOptions {
+            keep_previous_data,
+            ..self
+        }
+    }
+
+    /// Set the timeout of a fetch.
+    pub fn set_timeout(self, timeout: Option<Duration>) -> Self {
+        QueryOptions { timeout, ..self }
+    }
+
     /// Transform the default value.
+    /// The initial data, placeholder data and structural sharing functions are dropped, as they cannot be transformed.
     pub fn map_value<R>(self, func: impl FnOnce(V) -> R) -> QueryOptions<R> {
         QueryOptions {
             default_value: self.default_value.map(func),
@@ -98,6 +436,24 @@ impl<V> QueryOptions<V> {
             gc_time: self.gc_time,
             refetch_interval: self.refetch_interval,
             resource_option: self.resource_option,
+            invalidate_on_error: self.invalidate_on_error,
+            isolated_stale_time: self.isolated_stale_time,
+            tags: self.tags,
+            initial_data_fn: None,
+            initial_data_updated_at: self.initial_data_updated_at,
+            cancel_previous_on_refetch: self.cancel_previous_on_refetch,
+            cache_only: self.cache_only,
+            stale_time_jitter: self.stale_time_jitter,
+            ssr_default: None,
+            retry: self.retry,
+            retry_delay: self.retry_delay,
+            placeholder_data: None,
+            refetch_on_focus: self.refetch_on_focus,
+            refetch_on_reconnect: self.refetch_on_reconnect,
+            enabled: self.enabled,
+            structural_sharing: None,
+            keep_previous_data: self.keep_previous_data,
+            timeout: self.timeout,
         }
     }
 
@@ -114,15 +470,34 @@ impl<V> QueryOptions<V> {
             gc_time: self.gc_time,
             refetch_interval: self.refetch_interval,
             resource_option: self.resource_option,
+            invalidate_on_error: self.invalidate_on_error,
+            isolated_stale_time: self.isolated_stale_time,
+            tags: self.tags,
+            initial_data_fn: self.initial_data_fn,
+            initial_data_updated_at: self.initial_data_updated_at,
+            cancel_previous_on_refetch: self.cancel_previous_on_refetch,
+            cache_only: self.cache_only,
+            stale_time_jitter: self.stale_time_jitter,
+            ssr_default: self.ssr_default,
+            retry: self.retry,
+            retry_delay: self.retry_delay,
+            placeholder_data: self.placeholder_data,
+            refetch_on_focus: self.refetch_on_focus,
+            refetch_on_reconnect: self.refetch_on_reconnect,
+            enabled: self.enabled,
+            structural_sharing: self.structural_sharing,
+            keep_previous_data: self.keep_previous_data,
+            timeout: self.timeout,
         }
     }
 }
 
 impl<V> Default for QueryOptions<V> {
     fn default() -> Self {
-        // Use cache wide defaults if they exist.
-        let default_options = leptos::use_context::<crate::QueryClient>()
-            .map(|c| c.default_options)
+        // Use the nearest scoped defaults, then cache wide defaults if they exist.
+        let default_options = leptos::use_context::<ScopedQueryOptions>()
+            .map(|scoped| scoped.0)
+            .or_else(|| leptos::use_context::<crate::QueryClient>().map(|c| c.default_options))
             .unwrap_or_default();
         Self {
             default_value: None,
@@ -130,6 +505,24 @@ impl<V> Default for QueryOptions<V> {
             gc_time: default_options.gc_time,
             refetch_interval: default_options.refetch_interval,
             resource_option: Some(default_options.resource_option),
+            invalidate_on_error: false,
+            isolated_stale_time: false,
+            tags: Vec::new(),
+            initial_data_fn: None,
+            initial_data_updated_at: None,
+            cancel_previous_on_refetch: false,
+            cache_only: false,
+            stale_time_jitter: None,
+            ssr_default: None,
+            retry: None,
+            retry_delay: None,
+            placeholder_data: None,
+            refetch_on_focus: false,
+            refetch_on_reconnect: false,
+            enabled: None,
+            structural_sharing: None,
+            keep_previous_data: false,
+            timeout: None,
         }
         .validate()
     }
@@ -145,6 +538,25 @@ pub enum ResourceOption {
     Blocking,
     /// Query will use [`create_local_resource()`](leptos::create_local_resource)
     Local,
+    /// Query will use [`create_blocking_resource()`](leptos::create_blocking_resource) on the server,
+    /// and [`create_resource()`](leptos::create_resource) on the client.
+    ///
+    /// Useful for SEO critical pages: the server only sends the HTML once it contains the data,
+    /// while fetches on the client don't block.
+    BlockingSsr,
+}
+
+impl ResourceOption {
+    /// The resource option used on the current target, i.e. [`BlockingSsr`](Self::BlockingSsr) is mapped to the server or client option.
+    pub fn for_target(self) -> Self {
+        match self {
+            #[cfg(not(any(feature = "hydrate", feature = "csr")))]
+            ResourceOption::BlockingSsr => ResourceOption::Blocking,
+            #[cfg(any(feature = "hydrate", feature = "csr"))]
+            ResourceOption::BlockingSsr => ResourceOption::NonBlocking,
+            option => option,
+        }
+    }
 }
 
 fn ensure_valid_stale_time(
@@ -178,7 +590,7 @@ fn ensure_valid_stale_time(
 
 #[cfg(test)]
 mod tests {
-    use crate::provide_query_client_with_options;
+    use crate::{provide_query_client_with_options, provide_query_options};
 
     use super::*;
 
@@ -190,6 +602,24 @@ mod tests {
             gc_time: Some(Duration::from_secs(10)),
             refetch_interval: None,
             resource_option: None,
+            invalidate_on_error: false,
+            isolated_stale_time: false,
+            tags: Vec::new(),
+            initial_data_fn: None,
+            initial_data_updated_at: None,
+            cancel_previous_on_refetch: false,
+            cache_only: false,
+            stale_time_jitter: None,
+            ssr_default: None,
+            retry: None,
+            retry_delay: None,
+            placeholder_data: None,
+            refetch_on_focus: false,
+            refetch_on_reconnect: false,
+            enabled: None,
+            structural_sharing: None,
+            keep_previous_data: false,
+            timeout: None,
         }
         .validate();
 
@@ -213,6 +643,24 @@ mod tests {
             gc_time: Some(Duration::from_secs(10)),
             refetch_interval: None,
             resource_option: None,
+            invalidate_on_error: false,
+            isolated_stale_time: false,
+            tags: Vec::new(),
+            initial_data_fn: None,
+            initial_data_updated_at: None,
+            cancel_previous_on_refetch: false,
+            cache_only: false,
+            stale_time_jitter: None,
+            ssr_default: None,
+            retry: None,
+            retry_delay: None,
+            placeholder_data: None,
+            refetch_on_focus: false,
+            refetch_on_reconnect: false,
+            enabled: None,
+            structural_sharing: None,
+            keep_previous_data: false,
+            timeout: None,
         }
         .validate();
 
@@ -236,6 +684,24 @@ mod tests {
             gc_time: None,
             refetch_interval: None,
             resource_option: None,
+            invalidate_on_error: false,
+            isolated_stale_time: false,
+            tags: Vec::new(),
+            initial_data_fn: None,
+            initial_data_updated_at: None,
+            cancel_previous_on_refetch: false,
+            cache_only: false,
+            stale_time_jitter: None,
+            ssr_default: None,
+            retry: None,
+            retry_delay: None,
+            placeholder_data: None,
+            refetch_on_focus: false,
+            refetch_on_reconnect: false,
+            enabled: None,
+            structural_sharing: None,
+            keep_previous_data: false,
+            timeout: None,
         }
         .validate();
 
@@ -255,6 +721,24 @@ mod tests {
             gc_time: Some(Duration::from_secs(10)),
             refetch_interval: None,
             resource_option: None,
+            invalidate_on_error: false,
+            isolated_stale_time: false,
+            tags: Vec::new(),
+            initial_data_fn: None,
+            initial_data_updated_at: None,
+            cancel_previous_on_refetch: false,
+            cache_only: false,
+            stale_time_jitter: None,
+            ssr_default: None,
+            retry: None,
+            retry_delay: None,
+            placeholder_data: None,
+            refetch_on_focus: false,
+            refetch_on_reconnect: false,
+            enabled: None,
+            structural_sharing: None,
+            keep_previous_data: false,
+            timeout: None,
         }
         .validate();
         assert_eq!(
@@ -277,6 +761,24 @@ mod tests {
             gc_time: None,
             refetch_interval: None,
             resource_option: None,
+            invalidate_on_error: false,
+            isolated_stale_time: false,
+            tags: Vec::new(),
+            initial_data_fn: None,
+            initial_data_updated_at: None,
+            cancel_previous_on_refetch: false,
+            cache_only: false,
+            stale_time_jitter: None,
+            ssr_default: None,
+            retry: None,
+            retry_delay: None,
+            placeholder_data: None,
+            refetch_on_focus: false,
+            refetch_on_reconnect: false,
+            enabled: None,
+            structural_sharing: None,
+            keep_previous_data: false,
+            timeout: None,
         }
         .validate();
 
@@ -327,4 +829,57 @@ mod tests {
             "After validation, gc_time should not be less than stale_time"
         );
     }
+
+    #[test]
+    fn test_scoped_default() {
+        let _ = leptos::create_runtime();
+
+        provide_query_client_with_options(DefaultQueryOptions {
+            stale_time: Some(Duration::from_secs(1)),
+            gc_time: Some(Duration::from_secs(2)),
+            refetch_interval: None,
+            resource_option: ResourceOption::NonBlocking,
+        });
+
+        provide_query_options(DefaultQueryOptions {
+            stale_time: Some(Duration::from_secs(30)),
+            gc_time: Some(Duration::from_secs(60)),
+            refetch_interval: None,
+            resource_option: ResourceOption::Blocking,
+        });
+
+        let scoped_options: QueryOptions<()> = Default::default();
+
+        assert_eq!(
+            scoped_options.stale_time,
+            Some(Duration::from_secs(30)),
+            "Scoped stale_time should override the QueryClient's default"
+        );
+        assert_eq!(
+            scoped_options.gc_time,
+            Some(Duration::from_secs(60)),
+            "Scoped gc_time should override the QueryClient's default"
+        );
+        assert_eq!(
+            scoped_options.resource_option,
+            Some(ResourceOption::Blocking),
+            "Scoped resource_option should override the QueryClient's default"
+        );
+    }
+
+    #[test]
+    fn blocking_ssr_for_target() {
+        // Tests run as the server.
+        assert_eq!(
+            ResourceOption::Blocking,
+            ResourceOption::BlockingSsr.for_target()
+        );
+        for option in [
+            ResourceOption::NonBlocking,
+            ResourceOption::Blocking,
+            ResourceOption::Local,
+        ] {
+            assert_eq!(option, option.for_target());
+        }
+    }
 }
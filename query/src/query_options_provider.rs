@@ -0,0 +1,62 @@
+use leptos::*;
+
+use crate::DefaultQueryOptions;
+
+/// Overrides the default query options for every query created within the current scope.
+///
+/// Options resolve in the following order:
+/// 1. Options passed to [`use_query()`](crate::use_query()).
+/// 2. Nearest provided scoped options (this function, or [`QueryOptionsProvider`]).
+/// 3. The client wide [`DefaultQueryOptions`].
+///
+/// Only [`QueryOptions::default()`](crate::QueryOptions) picks up the scoped options, so per-call options should be built with `..QueryOptions::default()`.
+pub fn provide_query_options(options: DefaultQueryOptions) {
+    provide_context(ScopedQueryOptions(options));
+}
+
+/// Component that overrides the default query options for all queries in its children.
+///
+/// Useful for a subtree where every query should share a configuration, e.g. a settings page where everything has a long `stale_time`.
+///
+/// Example:
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+/// use std::time::Duration;
+///
+/// #[component]
+/// fn SettingsPage() -> impl IntoView {
+///     let options = DefaultQueryOptions {
+///         stale_time: Some(Duration::from_secs(60 * 5)),
+///         gc_time: Some(Duration::from_secs(60 * 10)),
+///         ..DefaultQueryOptions::default()
+///     };
+///
+///     view! {
+///         <QueryOptionsProvider options=options>
+///             // Queries in here are fresh for 5 minutes.
+///             <SettingsForm/>
+///         </QueryOptionsProvider>
+///     }
+/// }
+///
+/// #[component]
+/// fn SettingsForm() -> impl IntoView {
+///     view! {
+///         <form></form>
+///     }
+/// }
+/// ```
+#[component]
+pub fn QueryOptionsProvider(
+    /// The options to use for all queries within this component's children.
+    options: DefaultQueryOptions,
+    /// The subtree.
+    children: Children,
+) -> impl IntoView {
+    provide_query_options(options);
+    children()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScopedQueryOptions(pub(crate) DefaultQueryOptions);
@@ -4,16 +4,36 @@ use super::{PersistQueryData, QueryPersister};
 
 #[cfg(any(feature = "hydrate", feature = "csr"))]
 use async_cell::unsync::AsyncCell;
+use std::time::Duration;
 #[cfg(any(feature = "hydrate", feature = "csr"))]
-use std::rc::Rc;
+use std::{cell::Cell, rc::Rc};
 
 /// A persister that uses indexed db to persist queries.
+/// Suited for larger payloads than local storage, and doesn't block the main thread.
+///
+/// The database is opened on first use. If it fails to open, e.g. in a private browsing window,
+/// the failure is logged and the persister behaves as if it's empty.
+///
+/// Example
+/// ```
+/// use leptos_query::{query_persister::IndexedDbPersister, *};
+/// use std::time::Duration;
+///
+/// fn add_persister(client: &QueryClient) {
+///     let persister = IndexedDbPersister::new("my_app".to_string(), "query_cache".to_string())
+///         .with_max_age(Duration::from_secs(60 * 60 * 24));
+///     client.add_persister(persister);
+/// }
+/// ```
 #[derive(Clone, Debug)]
 pub struct IndexedDbPersister {
     database_name: String,
     object_store: String,
+    max_age: Option<Duration>,
     #[cfg(any(feature = "hydrate", feature = "csr"))]
-    database: Rc<AsyncCell<Rc<indexed_db_futures::IdbDatabase>>>,
+    opened: Rc<Cell<bool>>,
+    #[cfg(any(feature = "hydrate", feature = "csr"))]
+    database: Rc<AsyncCell<Result<Rc<indexed_db_futures::IdbDatabase>, String>>>,
 }
 
 impl Default for IndexedDbPersister {
@@ -23,32 +43,34 @@ impl Default for IndexedDbPersister {
 }
 
 impl IndexedDbPersister {
-    /// Create a new indexed db persister
+    /// Create a new indexed db persister, using a separate database and object store per app.
     pub fn new(database_name: String, object_store: String) -> Self {
-        let persister = Self {
+        Self {
             database_name,
             object_store,
+            max_age: None,
+            #[cfg(any(feature = "hydrate", feature = "csr"))]
+            opened: Rc::new(Cell::new(false)),
             #[cfg(any(feature = "hydrate", feature = "csr"))]
             database: Rc::new(AsyncCell::new()),
-        };
-
-        #[cfg(any(feature = "hydrate", feature = "csr"))]
-        persister.setup();
+        }
+    }
 
-        persister
+    /// Drop persisted queries that were last updated more than `max_age` ago.
+    /// Expired queries are swept when the database is opened, and are never restored.
+    pub fn with_max_age(self, max_age: Duration) -> Self {
+        Self {
+            max_age: Some(max_age),
+            ..self
+        }
     }
 
-    /// Initialize the persister eagerly, so that it is ready to use when needed.
-    #[cfg(any(feature = "hydrate", feature = "csr"))]
-    fn setup(&self) {
-        let db = {
-            let persister = self.clone();
-            async move {
-                persister.set_up_db().await;
-            }
-        };
-        leptos::spawn_local(async move {
-            let _ = db.await;
+    #[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+    fn is_expired(&self, query: &PersistQueryData, now: crate::Instant) -> bool {
+        self.max_age.is_some_and(|max_age| {
+            now.0
+                .saturating_sub(Duration::from_millis(query.updated_at))
+                > max_age
         })
     }
 }
@@ -60,7 +82,9 @@ impl QueryPersister for IndexedDbPersister {
         use js_sys::wasm_bindgen::JsValue;
 
         let object_store = self.object_store.as_str();
-        let db = self.get_database().await;
+        let Some(db) = self.get_database().await else {
+            return;
+        };
 
         let transaction = db
             .transaction_on_one_with_mode(object_store, web_sys::IdbTransactionMode::Readwrite)
@@ -83,7 +107,9 @@ impl QueryPersister for IndexedDbPersister {
         use js_sys::wasm_bindgen::JsValue;
 
         let object_store = self.object_store.as_str();
-        let db = self.get_database().await;
+        let Some(db) = self.get_database().await else {
+            return;
+        };
 
         let transaction = db
             .transaction_on_one_with_mode(object_store, web_sys::IdbTransactionMode::Readwrite)
@@ -105,7 +131,7 @@ impl QueryPersister for IndexedDbPersister {
         use indexed_db_futures::IdbQuerySource;
 
         let object_store = self.object_store.as_str();
-        let db = self.get_database().await;
+        let db = self.get_database().await?;
 
         let transaction = db
             .transaction_on_one(object_store)
@@ -120,17 +146,21 @@ impl QueryPersister for IndexedDbPersister {
             .expect("Failed to execute get operation")
             .await;
 
-        match request {
-            Ok(Some(result)) => IndexedDbPersister::from_json_string(&result),
-            Ok(None) => None,
-            Err(_) => None,
-        }
+        let query: PersistQueryData = match request {
+            Ok(Some(result)) => IndexedDbPersister::from_json_string(&result)?,
+            Ok(None) => return None,
+            Err(_) => return None,
+        };
+        // Not swept yet, e.g. because it expired since the database was opened.
+        (!self.is_expired(&query, crate::Instant::now())).then_some(query)
     }
 
     async fn clear(&self) {
         let object_store = self.object_store.as_str();
 
-        let db = self.get_database().await;
+        let Some(db) = self.get_database().await else {
+            return;
+        };
 
         let transaction = db
             .transaction_on_one_with_mode(object_store, web_sys::IdbTransactionMode::Readwrite)
@@ -164,22 +194,104 @@ impl QueryPersister for IndexedDbPersister {
     async fn clear(&self) {}
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_queries_older_than_max_age() {
+        let now = crate::Instant(Duration::from_secs(1000));
+        let updated_ago = |ago: u64| PersistQueryData {
+            value: "1".to_string(),
+            updated_at: (now.0 - Duration::from_secs(ago)).as_millis() as u64,
+        };
+
+        let persister = IndexedDbPersister::default().with_max_age(Duration::from_secs(60));
+        assert!(!persister.is_expired(&updated_ago(30), now));
+        assert!(persister.is_expired(&updated_ago(90), now));
+
+        assert!(!IndexedDbPersister::default().is_expired(&updated_ago(900), now));
+    }
+}
+
 #[cfg(any(feature = "hydrate", feature = "csr"))]
 impl IndexedDbPersister {
-    async fn get_database(&self) -> Rc<indexed_db_futures::IdbDatabase> {
+    // Opens the database on first use. Later calls wait for it to be opened.
+    async fn get_database(&self) -> Option<Rc<indexed_db_futures::IdbDatabase>> {
+        if !self.opened.replace(true) {
+            self.set_up_db().await;
+        }
         let db = self.database.clone();
-        let result = db.get().await;
-        result
+        match db.get().await {
+            Ok(db) => Some(db),
+            Err(error) => {
+                leptos::logging::debug_warn!("IndexedDbPersister: {}", error);
+                None
+            }
+        }
     }
 
     async fn set_up_db(&self) {
-        let db = self.create_database().await;
-        let db = Rc::new(db);
+        let db = self.create_database().await.map(Rc::new).map_err(|error| {
+            format!(
+                "Failed to open database {}: {:?}",
+                self.database_name, error
+            )
+        });
+
+        if let Ok(db) = db.as_ref() {
+            if let Err(error) = self.sweep_expired(db).await {
+                leptos::logging::debug_warn!(
+                    "IndexedDbPersister: Failed to sweep expired queries: {:?}",
+                    error
+                );
+            }
+        }
 
         self.database.set(db);
     }
 
-    async fn create_database(&self) -> indexed_db_futures::IdbDatabase {
+    // Drops the queries older than the max age.
+    async fn sweep_expired(
+        &self,
+        db: &indexed_db_futures::IdbDatabase,
+    ) -> Result<(), web_sys::DomException> {
+        use indexed_db_futures::IdbQuerySource;
+
+        if self.max_age.is_none() {
+            return Ok(());
+        }
+        let object_store = self.object_store.as_str();
+        let now = crate::Instant::now();
+
+        let transaction =
+            db.transaction_on_one_with_mode(object_store, web_sys::IdbTransactionMode::Readwrite)?;
+        let store = transaction.object_store(object_store)?;
+
+        // Collect first, deleting while iterating would invalidate the cursor.
+        let mut expired = Vec::new();
+        if let Some(cursor) = store.open_cursor()?.await? {
+            loop {
+                let query =
+                    IndexedDbPersister::from_json_string::<PersistQueryData>(&cursor.value());
+                if query.is_some_and(|query| self.is_expired(&query, now)) {
+                    expired.extend(cursor.key());
+                }
+                if !cursor.continue_cursor()?.await? {
+                    break;
+                }
+            }
+        }
+        for key in expired {
+            let _ = store.delete(&key)?;
+        }
+
+        transaction.await.into_result()
+    }
+
+    async fn create_database(
+        &self,
+    ) -> Result<indexed_db_futures::IdbDatabase, web_sys::DomException> {
         let db_name = self.database_name.as_str();
         let object_store = self.object_store.as_str();
 
@@ -189,8 +301,7 @@ impl IndexedDbPersister {
         };
         use js_sys::wasm_bindgen::JsValue;
 
-        let mut db_req: OpenDbRequest =
-            IdbDatabase::open_u32(db_name, 1).expect("Database open request");
+        let mut db_req: OpenDbRequest = IdbDatabase::open_u32(db_name, 1)?;
 
         let object_store = object_store.to_string();
         db_req.set_on_upgrade_needed(Some(
@@ -208,7 +319,7 @@ impl IndexedDbPersister {
             },
         ));
 
-        db_req.await.expect("Database open request")
+        db_req.await
     }
 
     fn to_json_string<T: miniserde::Serialize>(value: &T) -> js_sys::wasm_bindgen::JsValue {
@@ -1,47 +1,110 @@
 use crate::query_persister::*;
 
+use super::storage_quota::{KeyValueStorage, StorageQuota};
+
 /// A persister that uses local storage to persist queries.
-#[derive(Clone, Copy)]
-pub struct LocalStoragePersister;
+///
+/// If the storage quota is exceeded, the oldest persisted queries are evicted to make room.
+/// If there is still no room, persisting is disabled for the rest of the session.
+///
+/// Example
+/// ```
+/// use leptos_query::{query_persister::LocalStoragePersister, *};
+///
+/// fn add_persister(client: &QueryClient) {
+///     // Keys are stored as "my_app:<key>", so clearing only removes the queries of this app.
+///     client.add_persister(LocalStoragePersister::with_prefix("my_app:"));
+/// }
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct LocalStoragePersister {
+    prefix: &'static str,
+}
+
+impl LocalStoragePersister {
+    /// A persister without a key prefix, same as [`LocalStoragePersister::default()`].
+    /// Replaces the former unit struct value `LocalStoragePersister`.
+    pub const DEFAULT: Self = Self::with_prefix("");
+
+    /// Creates a persister that prefixes the keys of persisted queries.
+    ///
+    /// Useful when multiple apps share an origin, as clearing the persister only removes keys with its prefix.
+    /// Without a prefix, clearing removes everything in local storage.
+    pub const fn with_prefix(prefix: &'static str) -> Self {
+        Self { prefix }
+    }
+
+    fn storage_key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+}
 
 #[cfg(any(feature = "hydrate", feature = "csr"))]
 thread_local! {
-    #[cfg(any(feature = "hydrate", feature = "csr"))]
-    pub(crate) static LOCAL_STORAGE: Option<web_sys::Storage> = leptos::window().local_storage().ok().flatten()
+    pub(crate) static LOCAL_STORAGE: Option<web_sys::Storage> = leptos::window().local_storage().ok().flatten();
+    static QUOTA: std::cell::RefCell<StorageQuota> = std::cell::RefCell::new(StorageQuota::default());
 }
 
 #[cfg(any(feature = "hydrate", feature = "csr"))]
 fn local_storage() -> Option<web_sys::Storage> {
     LOCAL_STORAGE.with(Clone::clone)
 }
+
+// Independent of the browser storage, so it can be tested natively.
+#[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+impl LocalStoragePersister {
+    fn persist_to(
+        &self,
+        storage: &impl KeyValueStorage,
+        quota: &mut StorageQuota,
+        key: &str,
+        query: PersistQueryData,
+    ) {
+        let value = miniserde::json::to_string(&query);
+        quota.set_item(storage, &self.storage_key(key), &value);
+    }
+
+    fn remove_from(&self, storage: &impl KeyValueStorage, quota: &mut StorageQuota, key: &str) {
+        quota.remove_item(storage, &self.storage_key(key));
+    }
+
+    fn retrieve_from(&self, storage: &impl KeyValueStorage, key: &str) -> Option<PersistQueryData> {
+        let value = storage.get_item(&self.storage_key(key))?;
+        miniserde::json::from_str(&value).ok()
+    }
+
+    fn clear_from(&self, storage: &impl KeyValueStorage, quota: &mut StorageQuota) {
+        for key in storage.keys() {
+            if key.starts_with(self.prefix) {
+                storage.remove_item(&key);
+            }
+        }
+        quota.clear();
+    }
+}
+
 #[cfg(any(feature = "hydrate", feature = "csr"))]
 #[async_trait(?Send)]
 impl QueryPersister for LocalStoragePersister {
     async fn persist(&self, key: &str, query: PersistQueryData) {
         if let Some(storage) = local_storage() {
-            let value = miniserde::json::to_string(&query);
-            let _ = storage.set(&key, &value);
+            QUOTA.with(|quota| self.persist_to(&storage, &mut quota.borrow_mut(), key, query));
         }
     }
 
     async fn remove(&self, key: &str) {
         if let Some(storage) = local_storage() {
-            let _ = storage.remove_item(key);
+            QUOTA.with(|quota| self.remove_from(&storage, &mut quota.borrow_mut(), key));
         }
     }
 
     async fn retrieve(&self, key: &str) -> Option<PersistQueryData> {
-        if let Some(storage) = local_storage() {
-            if let Some(value) = storage.get_item(key).ok().flatten() {
-                return miniserde::json::from_str(&value).ok();
-            }
-        }
-        None
+        local_storage().and_then(|storage| self.retrieve_from(&storage, key))
     }
 
     async fn clear(&self) {
         if let Some(storage) = local_storage() {
-            let _ = storage.clear();
+            QUOTA.with(|quota| self.clear_from(&storage, &mut quota.borrow_mut()));
         }
     }
 }
@@ -65,3 +128,63 @@ impl QueryPersister for LocalStoragePersister {
 
     async fn clear(&self) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_persister::storage_quota::tests::LimitedStorage;
+
+    #[test]
+    fn round_trip() {
+        let storage = LimitedStorage::new(1000);
+        let mut quota = StorageQuota::default();
+        let persister = LocalStoragePersister::with_prefix("app:");
+
+        let query = PersistQueryData {
+            value: "\"data\"".to_string(),
+            updated_at: 42,
+        };
+        persister.persist_to(&storage, &mut quota, "key", query);
+        assert!(storage.contains("app:key"));
+
+        let restored = persister
+            .retrieve_from(&storage, "key")
+            .expect("Persisted query");
+        assert_eq!("\"data\"", restored.value);
+        assert_eq!(42, restored.updated_at);
+
+        persister.remove_from(&storage, &mut quota, "key");
+        assert!(persister.retrieve_from(&storage, "key").is_none());
+    }
+
+    #[test]
+    fn clear_only_removes_prefixed_keys() {
+        let storage = LimitedStorage::new(1000);
+        let mut quota = StorageQuota::default();
+        let persister = LocalStoragePersister::with_prefix("app:");
+        storage.set_item("other", "value").unwrap();
+
+        let query = PersistQueryData {
+            value: "1".to_string(),
+            updated_at: 0,
+        };
+        persister.persist_to(&storage, &mut quota, "key", query);
+        persister.clear_from(&storage, &mut quota);
+
+        assert!(!storage.contains("app:key"));
+        assert!(storage.contains("other"));
+    }
+
+    #[test]
+    fn default_has_no_prefix() {
+        let storage = LimitedStorage::new(1000);
+        let mut quota = StorageQuota::default();
+
+        let query = PersistQueryData {
+            value: "1".to_string(),
+            updated_at: 0,
+        };
+        LocalStoragePersister::DEFAULT.persist_to(&storage, &mut quota, "key", query);
+        assert!(storage.contains("key"));
+    }
+}
@@ -57,13 +57,18 @@ where
 /// Serialized query data.
 #[derive(Clone)]
 #[cfg_attr(
-    any(feature = "local_storage", feature = "indexed_db"),
+    any(
+        feature = "local_storage",
+        feature = "session_storage",
+        feature = "indexed_db"
+    ),
     derive(miniserde::Serialize, miniserde::Deserialize)
 )]
 pub struct PersistQueryData {
     /// The serialized query data.
     pub value: String,
     /// The time the query was last updated in millis.
+    /// Restored with the data, so a restored query is as stale as when it was persisted.
     pub updated_at: u64,
 }
 
@@ -91,7 +96,8 @@ impl TryFrom<crate::QueryState<String>> for PersistQueryData {
             crate::QueryState::Loading
             | crate::QueryState::Created
             | crate::QueryState::Invalid(_)
-            | crate::QueryState::Fetching(_) => Err(()),
+            | crate::QueryState::Fetching(_)
+            | crate::QueryState::Error { .. } => Err(()),
         }
     }
 }
@@ -104,6 +110,41 @@ impl From<crate::QueryData<String>> for PersistQueryData {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{query::Query, Instant, QueryData, QueryState};
+
+    #[test]
+    fn restored_data_keeps_updated_at() {
+        let an_hour_ago = Instant(Instant::now().0 - Duration::from_secs(60 * 60));
+        let persisted: PersistQueryData = QueryData {
+            data: "1".to_string(),
+            updated_at: an_hour_ago,
+        }
+        .into();
+
+        let restored: QueryData<u32> = persisted.try_into().expect("Deserialize");
+        assert_eq!(1, restored.data);
+        assert_eq!(an_hour_ago.0.as_millis(), restored.updated_at.0.as_millis());
+
+        // Old data is stale immediately, rather than appearing freshly loaded.
+        let query = Query::<u32, u32>::new(0, 0);
+        query.seed_state(
+            QueryState::Loaded(restored),
+            Some(crate::FetchSource::Persister),
+        );
+        assert!(query.is_stale_for(Some(Duration::from_secs(60))));
+        assert!(!query.is_stale_for(Some(Duration::from_secs(2 * 60 * 60))));
+    }
+}
+
+#[cfg(any(feature = "local_storage", feature = "session_storage"))]
+#[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+mod storage_quota;
+
 #[cfg(feature = "indexed_db")]
 mod indexed_db;
 #[cfg(feature = "indexed_db")]
@@ -113,3 +154,8 @@ pub use indexed_db::IndexedDbPersister;
 mod local_storage;
 #[cfg(feature = "local_storage")]
 pub use local_storage::LocalStoragePersister;
+
+#[cfg(feature = "session_storage")]
+mod session_storage;
+#[cfg(feature = "session_storage")]
+pub use session_storage::SessionStoragePersister;
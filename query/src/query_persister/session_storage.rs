@@ -0,0 +1,157 @@
+use crate::query_persister::*;
+
+use super::storage_quota::{KeyValueStorage, StorageQuota};
+
+/// A persister that uses session storage to persist queries.
+///
+/// Persisted queries live as long as the browser tab, and are not shared between tabs.
+/// Keys are prefixed, so clearing this persister only removes queries it persisted itself.
+///
+/// If the storage quota is exceeded, the oldest persisted queries are evicted to make room.
+/// If there is still no room, persisting is disabled for the rest of the session.
+#[derive(Clone, Copy)]
+pub struct SessionStoragePersister;
+
+const KEY_PREFIX: &str = "leptos_query:";
+
+#[cfg(any(feature = "hydrate", feature = "csr"))]
+thread_local! {
+    pub(crate) static SESSION_STORAGE: Option<web_sys::Storage> = leptos::window().session_storage().ok().flatten();
+    static QUOTA: std::cell::RefCell<StorageQuota> = std::cell::RefCell::new(StorageQuota::default());
+}
+
+#[cfg(any(feature = "hydrate", feature = "csr"))]
+fn session_storage() -> Option<web_sys::Storage> {
+    SESSION_STORAGE.with(Clone::clone)
+}
+
+fn make_storage_key(key: &str) -> String {
+    format!("{KEY_PREFIX}{key}")
+}
+
+// Independent of the browser storage, so it can be tested natively.
+#[cfg_attr(not(any(feature = "hydrate", feature = "csr")), allow(dead_code))]
+impl SessionStoragePersister {
+    fn persist_to(
+        &self,
+        storage: &impl KeyValueStorage,
+        quota: &mut StorageQuota,
+        key: &str,
+        query: PersistQueryData,
+    ) {
+        let value = miniserde::json::to_string(&query);
+        quota.set_item(storage, &make_storage_key(key), &value);
+    }
+
+    fn remove_from(&self, storage: &impl KeyValueStorage, quota: &mut StorageQuota, key: &str) {
+        quota.remove_item(storage, &make_storage_key(key));
+    }
+
+    fn retrieve_from(&self, storage: &impl KeyValueStorage, key: &str) -> Option<PersistQueryData> {
+        let value = storage.get_item(&make_storage_key(key))?;
+        miniserde::json::from_str(&value).ok()
+    }
+
+    fn clear_from(&self, storage: &impl KeyValueStorage, quota: &mut StorageQuota) {
+        for key in storage.keys() {
+            if key.starts_with(KEY_PREFIX) {
+                storage.remove_item(&key);
+            }
+        }
+        quota.clear();
+    }
+}
+
+#[cfg(any(feature = "hydrate", feature = "csr"))]
+#[async_trait(?Send)]
+impl QueryPersister for SessionStoragePersister {
+    async fn persist(&self, key: &str, query: PersistQueryData) {
+        if let Some(storage) = session_storage() {
+            QUOTA.with(|quota| self.persist_to(&storage, &mut quota.borrow_mut(), key, query));
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        if let Some(storage) = session_storage() {
+            QUOTA.with(|quota| self.remove_from(&storage, &mut quota.borrow_mut(), key));
+        }
+    }
+
+    async fn retrieve(&self, key: &str) -> Option<PersistQueryData> {
+        session_storage().and_then(|storage| self.retrieve_from(&storage, key))
+    }
+
+    async fn clear(&self) {
+        if let Some(storage) = session_storage() {
+            QUOTA.with(|quota| self.clear_from(&storage, &mut quota.borrow_mut()));
+        }
+    }
+}
+
+#[cfg(not(any(feature = "hydrate", feature = "csr")))]
+#[async_trait(?Send)]
+impl QueryPersister for SessionStoragePersister {
+    async fn persist(&self, key: &str, query: PersistQueryData) {
+        let _ = key;
+        let _ = query;
+    }
+
+    async fn remove(&self, key: &str) {
+        let _ = key;
+    }
+
+    async fn retrieve(&self, key: &str) -> Option<PersistQueryData> {
+        let _ = key;
+        None
+    }
+
+    async fn clear(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_persister::storage_quota::tests::LimitedStorage;
+
+    #[test]
+    fn round_trip() {
+        let storage = LimitedStorage::new(1000);
+        let mut quota = StorageQuota::default();
+
+        let query = PersistQueryData {
+            value: "\"data\"".to_string(),
+            updated_at: 42,
+        };
+        SessionStoragePersister.persist_to(&storage, &mut quota, "key", query);
+        assert!(storage.contains("leptos_query:key"));
+
+        let restored = SessionStoragePersister
+            .retrieve_from(&storage, "key")
+            .expect("Persisted query");
+        assert_eq!("\"data\"", restored.value);
+        assert_eq!(42, restored.updated_at);
+
+        SessionStoragePersister.remove_from(&storage, &mut quota, "key");
+        assert!(SessionStoragePersister
+            .retrieve_from(&storage, "key")
+            .is_none());
+    }
+
+    #[test]
+    fn clear_only_removes_own_keys() {
+        let storage = LimitedStorage::new(1000);
+        let mut quota = StorageQuota::default();
+        // E.g. stored by the app, or by an unprefixed persister.
+        storage.set_item("key", "value").unwrap();
+
+        let query = PersistQueryData {
+            value: "1".to_string(),
+            updated_at: 0,
+        };
+        SessionStoragePersister.persist_to(&storage, &mut quota, "key", query);
+        SessionStoragePersister.clear_from(&storage, &mut quota);
+
+        assert!(!storage.contains("leptos_query:key"));
+        assert!(storage.contains("key"));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+
+/// The subset of the Web Storage API used by the storage persisters.
+pub(crate) trait KeyValueStorage {
+    /// Fails if the value could not be stored, e.g. with a QuotaExceededError.
+    fn set_item(&self, key: &str, value: &str) -> Result<(), ()>;
+    fn get_item(&self, key: &str) -> Option<String>;
+    fn remove_item(&self, key: &str);
+    fn keys(&self) -> Vec<String>;
+}
+
+#[cfg(any(feature = "hydrate", feature = "csr"))]
+impl KeyValueStorage for web_sys::Storage {
+    fn set_item(&self, key: &str, value: &str) -> Result<(), ()> {
+        self.set(key, value).map_err(|_| ())
+    }
+
+    fn get_item(&self, key: &str) -> Option<String> {
+        web_sys::Storage::get_item(self, key).ok().flatten()
+    }
+
+    fn remove_item(&self, key: &str) {
+        let _ = web_sys::Storage::remove_item(self, key);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let length = self.length().unwrap_or(0);
+        (0..length)
+            .filter_map(|index| self.key(index).ok().flatten())
+            .collect()
+    }
+}
+
+/// Writes to storage, making room when the storage quota is exceeded.
+///
+/// When a write fails, the oldest entries written by this persister are evicted until there is room for the new entry, and the write is retried once.
+/// If it fails again, persisting is disabled for the rest of the session, rather than failing on every write.
+/// Only entries written during this session are known, so entries from earlier sessions are never evicted.
+#[derive(Default)]
+pub(crate) struct StorageQuota {
+    // Keys and sizes of the written entries, oldest write first.
+    entries: VecDeque<(String, usize)>,
+    disabled: bool,
+}
+
+impl StorageQuota {
+    pub(crate) fn set_item(&mut self, storage: &impl KeyValueStorage, key: &str, value: &str) {
+        if self.disabled {
+            return;
+        }
+
+        self.forget(key);
+        let size = key.len() + value.len();
+
+        if storage.set_item(key, value).is_err() {
+            let mut freed = 0;
+            while freed < size {
+                match self.entries.pop_front() {
+                    Some((oldest, oldest_size)) => {
+                        storage.remove_item(&oldest);
+                        freed += oldest_size;
+                    }
+                    None => break,
+                }
+            }
+
+            if storage.set_item(key, value).is_err() {
+                self.disabled = true;
+                leptos::logging::debug_warn!(
+                    "Storage quota exceeded. Queries will not be persisted for the rest of the session."
+                );
+                return;
+            }
+        }
+
+        self.entries.push_back((key.to_string(), size));
+    }
+
+    pub(crate) fn remove_item(&mut self, storage: &impl KeyValueStorage, key: &str) {
+        self.forget(key);
+        storage.remove_item(key);
+    }
+
+    // Clearing frees up the storage, so persisting is attempted again.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.disabled = false;
+    }
+
+    fn forget(&mut self, key: &str) {
+        self.entries.retain(|(entry, _)| entry != key);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::HashMap};
+
+    // Rejects writes beyond a fixed number of bytes.
+    pub(crate) struct LimitedStorage {
+        limit: usize,
+        items: RefCell<HashMap<String, String>>,
+    }
+
+    impl LimitedStorage {
+        pub(crate) fn new(limit: usize) -> Self {
+            Self {
+                limit,
+                items: RefCell::new(HashMap::new()),
+            }
+        }
+
+        pub(crate) fn contains(&self, key: &str) -> bool {
+            self.items.borrow().contains_key(key)
+        }
+    }
+
+    impl KeyValueStorage for LimitedStorage {
+        fn set_item(&self, key: &str, value: &str) -> Result<(), ()> {
+            let mut items = self.items.borrow_mut();
+            let used: usize = items
+                .iter()
+                .filter(|(k, _)| k.as_str() != key)
+                .map(|(k, v)| k.len() + v.len())
+                .sum();
+            if used + key.len() + value.len() > self.limit {
+                return Err(());
+            }
+            items.insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn get_item(&self, key: &str) -> Option<String> {
+            self.items.borrow().get(key).cloned()
+        }
+
+        fn remove_item(&self, key: &str) {
+            self.items.borrow_mut().remove(key);
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.items.borrow().keys().cloned().collect()
+        }
+    }
+
+    #[test]
+    fn evicts_oldest_when_full() {
+        let storage = LimitedStorage::new(20);
+        let mut quota = StorageQuota::default();
+
+        quota.set_item(&storage, "a", "123456789");
+        quota.set_item(&storage, "b", "123456789");
+        assert!(storage.contains("a") && storage.contains("b"));
+
+        quota.set_item(&storage, "c", "123456789");
+        assert!(!storage.contains("a"), "Oldest entry should be evicted");
+        assert!(storage.contains("b") && storage.contains("c"));
+    }
+
+    #[test]
+    fn rewrite_refreshes_entry() {
+        let storage = LimitedStorage::new(20);
+        let mut quota = StorageQuota::default();
+
+        quota.set_item(&storage, "a", "123456789");
+        quota.set_item(&storage, "b", "123456789");
+        quota.set_item(&storage, "a", "987654321");
+
+        quota.set_item(&storage, "c", "123456789");
+        assert!(
+            !storage.contains("b"),
+            "Least recently written entry should be evicted"
+        );
+        assert!(storage.contains("a") && storage.contains("c"));
+    }
+
+    #[test]
+    fn disables_when_entry_never_fits() {
+        let storage = LimitedStorage::new(20);
+        let mut quota = StorageQuota::default();
+
+        quota.set_item(&storage, "a", "123456789");
+        quota.set_item(&storage, "b", "this value is too large to ever fit");
+        assert!(!storage.contains("b"));
+
+        // Disabled, even though this would fit.
+        quota.set_item(&storage, "c", "1");
+        assert!(!storage.contains("c"));
+
+        quota.clear();
+        quota.set_item(&storage, "c", "1");
+        assert!(storage.contains("c"));
+    }
+}
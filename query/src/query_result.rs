@@ -1,4 +1,6 @@
-use crate::QueryState;
+use std::{any::Any, rc::Rc, sync::Arc, time::Duration};
+
+use crate::{query::Query, FetchSource, QueryKey, QueryState, QueryValue};
 use leptos::*;
 
 /// Reactive query result.
@@ -19,9 +21,60 @@ where
     pub is_fetching: Signal<bool>,
     /// If the query data has been marked as invalid.
     pub is_invalid: Signal<bool>,
+    /// If the query may fetch, according to the [`enabled`](crate::QueryOptions::enabled) option.
+    /// Useful to render a "waiting for prerequisites" state while the query is disabled. Always true without the option.
+    pub is_enabled: Signal<bool>,
+    /// The error of the last fetch, if it failed. Only fallible fetchers, e.g. of [`use_query_result()`](crate::use_query_result()), can fail.
+    pub error: Signal<Option<crate::QueryError>>,
+    /// Where the current data came from. None if there is no data yet.
+    pub last_fetch_source: Signal<Option<FetchSource>>,
+    /// If [`data`](Self::data) holds the data of the previous key, while the current key loads.
+    /// Only true with [`keep_previous_data`](crate::QueryOptions::keep_previous_data).
+    pub is_previous_data: Signal<bool>,
+    /// How long ago the data was updated, e.g. for "updated 3 minutes ago" labels. None if there is no data yet.
+    /// Ticks every second during the first minute, then every minute, then every hour, until the component unmounts.
+    pub age: Signal<Option<Duration>>,
 
     /// Refetch the query.
     pub refetch: R,
+    /// Fetch again after an error, e.g. for a "Try again" button.
+    /// Unlike [`refetch`](Self::refetch), a request that is waiting to [retry](crate::QueryOptions::retry) is replaced,
+    /// so the new request isn't delayed by the remaining retry delay.
+    pub retry: Rc<dyn Fn()>,
+
+    // Type erased Query<K, V>, the key type is not part of QueryResult.
+    pub(crate) raw_query: Signal<Rc<dyn Any>>,
+}
+
+impl<V, R> QueryResult<V, R>
+where
+    V: QueryValue + 'static,
+    R: RefetchFn,
+{
+    /// The error of the last fetch, if it failed with an error of type `E`,
+    /// e.g. the error type of a [`use_query_result()`](crate::use_query_result()) fetcher.
+    /// Typed alternative to [`error`](Self::error).
+    pub fn error_as<E>(&self) -> Signal<Option<Arc<E>>>
+    where
+        E: Send + Sync + 'static,
+    {
+        let error = self.error;
+        Signal::derive(move || error.with(|error| error.as_ref()?.downcast::<E>()))
+    }
+
+    /// Returns the underlying [`Query`] for the current key. Does not track the key.
+    ///
+    /// This is an escape hatch for advanced use cases, such as setting the state directly or inspecting observers.
+    /// Prefer the signals on this struct and the [`QueryClient`](crate::QueryClient) methods, which maintain the invariants between the cache and its observers.
+    ///
+    /// Returns [`None`](Option::None) if `K` is not the key type used to create the query.
+    pub fn query<K>(&self) -> Option<Query<K, V>>
+    where
+        K: QueryKey + 'static,
+    {
+        self.raw_query
+            .with_untracked(|query| query.downcast_ref::<Query<K, V>>().cloned())
+    }
 }
 
 /// Convenience Trait alias for a Query Result's refetch function.
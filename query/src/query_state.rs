@@ -1,3 +1,10 @@
+use std::{
+    any::Any,
+    fmt::{Debug, Display},
+    sync::Arc,
+    time::Duration,
+};
+
 use crate::Instant;
 
 /// The lifecycle of a query.
@@ -36,6 +43,17 @@ pub enum QueryState<V> {
     ///
     /// The associated `QueryData<V>` object holds the invalidated data.
     Invalid(QueryData<V>),
+
+    /// The state indicating that the last fetch of a query failed.
+    ///
+    /// Only fallible fetchers, e.g. of [`use_query_result()`](crate::use_query_result()), can fail.
+    /// The last successfully fetched data is preserved, if any.
+    Error {
+        /// The last successfully fetched data, if any.
+        data: Option<QueryData<V>>,
+        /// The error of the failed fetch.
+        error: QueryError,
+    },
 }
 
 impl<V> QueryState<V> {
@@ -46,6 +64,15 @@ impl<V> QueryState<V> {
             QueryState::Fetching(data) | QueryState::Loaded(data) | QueryState::Invalid(data) => {
                 Some(data)
             }
+            QueryState::Error { data, .. } => data.as_ref(),
+        }
+    }
+
+    /// Returns the error of the last fetch, if it failed.
+    pub fn error(&self) -> Option<&QueryError> {
+        match self {
+            QueryState::Error { error, .. } => Some(error),
+            _ => None,
         }
     }
 
@@ -66,6 +93,7 @@ impl<V> QueryState<V> {
             QueryState::Fetching(data) | QueryState::Loaded(data) | QueryState::Invalid(data) => {
                 Some(&mut data.data)
             }
+            QueryState::Error { data, .. } => data.as_mut().map(|data| &mut data.data),
         }
     }
 
@@ -86,10 +114,104 @@ impl<V> QueryState<V> {
                 data: mapper(&data.data),
                 updated_at: data.updated_at,
             }),
+            QueryState::Error { data, error } => QueryState::Error {
+                data: data.as_ref().map(|data| QueryData {
+                    data: mapper(&data.data),
+                    updated_at: data.updated_at,
+                }),
+                error: error.clone(),
+            },
         }
     }
 }
 
+/// The error of a failed fetch, with its type erased so [`QueryState`] doesn't depend on the error type.
+///
+/// Equality is by identity, i.e. two clones of the same error are equal.
+/// Displays as the wrapped error, e.g. in the devtools.
+#[derive(Clone)]
+pub struct QueryError(Arc<dyn ErrorValue>);
+
+impl QueryError {
+    /// Wraps an error.
+    pub fn new<E>(error: E) -> Self
+    where
+        E: Debug + Display + Send + Sync + 'static,
+    {
+        Self(Arc::new(error))
+    }
+
+    /// Returns the error if it is of type `E`.
+    pub fn downcast<E>(&self) -> Option<Arc<E>>
+    where
+        E: Send + Sync + 'static,
+    {
+        self.0.clone().into_any().downcast::<E>().ok()
+    }
+}
+
+impl Debug for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt_display(f)
+    }
+}
+
+impl PartialEq for QueryError {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for QueryError {}
+
+trait ErrorValue: Debug + Send + Sync {
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+    fn fmt_display(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result;
+}
+
+impl<E> ErrorValue for E
+where
+    E: Debug + Display + Send + Sync + 'static,
+{
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn fmt_display(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// The error of a fetch that didn't complete within its [`timeout`](crate::QueryOptions::timeout).
+///
+/// Tell it apart from the errors of the fetcher with [`QueryError::downcast()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryTimeout(pub Duration);
+
+impl Display for QueryTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Timed out after {:?}", self.0)
+    }
+}
+
+/// The error of a [streaming query](crate::use_streaming_query) whose stream ended without yielding a value.
+///
+/// Tell it apart from other errors with [`QueryError::downcast()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmptyStream;
+
+impl Display for EmptyStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Stream ended without a value")
+    }
+}
+
 /// The latest data for a Query.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct QueryData<V> {
@@ -108,3 +230,31 @@ impl<V> QueryData<V> {
         }
     }
 }
+
+/// Where the current data of a query came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FetchSource {
+    /// Data was fetched with the query's fetcher.
+    Network,
+    /// Data was restored from a [`QueryPersister`](crate::query_persister::QueryPersister).
+    Persister,
+    /// Data was provided upfront, e.g. during hydration.
+    Initial,
+    /// Data was set manually, e.g. with [`set_query_data`](crate::QueryClient::set_query_data).
+    Manual,
+}
+
+/// A snapshot of a query, returned by [`QueryClient::status()`](crate::QueryClient::status).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryStatus {
+    /// Whether a request is in flight.
+    pub is_fetching: bool,
+    /// Whether the data is invalid or stale.
+    pub is_stale: bool,
+    /// The instant the data was last updated, if there is any.
+    pub updated_at: Option<Instant>,
+    /// The number of active observers, e.g. mounted `use_query` calls.
+    pub observer_count: usize,
+    /// The error of the last fetch, if it failed.
+    pub error: Option<QueryError>,
+}
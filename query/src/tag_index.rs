@@ -0,0 +1,122 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    cache_observer::{CacheEvent, CacheObserver, CreatedQuery, ObserverAdded, QueryCacheKey},
+    QueryOptions,
+};
+
+// Index of the queries carrying each tag, across all key and value types.
+// Maintained from cache events, so invalidating a tag only visits the matching queries.
+#[derive(Clone, Default)]
+pub(crate) struct TagIndex {
+    inner: Rc<RefCell<TagIndexInner>>,
+}
+
+#[derive(Default)]
+struct TagIndexInner {
+    queries: HashMap<QueryCacheKey, TaggedQuery>,
+    tags: HashMap<String, HashSet<QueryCacheKey>>,
+}
+
+struct TaggedQuery {
+    tags: HashSet<String>,
+    mark_invalid: Rc<dyn Fn() -> bool>,
+    observer_options: Rc<dyn Fn() -> Vec<QueryOptions<String>>>,
+}
+
+impl TagIndex {
+    // Returns the number of queries that were invalidated.
+    pub(crate) fn invalidate(&self, tag: &str) -> usize {
+        let mark_invalid = {
+            let inner = self.inner.borrow();
+            inner
+                .tags
+                .get(tag)
+                .into_iter()
+                .flatten()
+                .filter_map(|key| inner.queries.get(key))
+                .map(|query| query.mark_invalid.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Invalidation emits cache events, so the index must not be borrowed.
+        mark_invalid
+            .into_iter()
+            .filter(|mark_invalid| mark_invalid())
+            .count()
+    }
+
+    // A query carries the tags of its active observers.
+    fn sync_tags(&self, key: &QueryCacheKey) {
+        let mut inner = self.inner.borrow_mut();
+        let TagIndexInner { queries, tags } = &mut *inner;
+        let Some(query) = queries.get_mut(key) else {
+            return;
+        };
+
+        let current = (query.observer_options)()
+            .into_iter()
+            .flat_map(|options| options.tags)
+            .collect::<HashSet<_>>();
+
+        for tag in query.tags.difference(&current) {
+            if let Some(keys) = tags.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    tags.remove(tag);
+                }
+            }
+        }
+        for tag in current.difference(&query.tags) {
+            tags.entry(tag.clone()).or_default().insert(key.clone());
+        }
+
+        query.tags = current;
+    }
+
+    fn remove(&self, key: &QueryCacheKey) {
+        let mut inner = self.inner.borrow_mut();
+        let TagIndexInner { queries, tags } = &mut *inner;
+        if let Some(query) = queries.remove(key) {
+            for tag in query.tags {
+                if let Some(keys) = tags.get_mut(&tag) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        tags.remove(&tag);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl CacheObserver for TagIndex {
+    fn process_cache_event(&self, event: CacheEvent) {
+        match event {
+            CacheEvent::Created(CreatedQuery {
+                key,
+                mark_invalid,
+                observer_options,
+                ..
+            }) => {
+                self.inner.borrow_mut().queries.insert(
+                    key.clone(),
+                    TaggedQuery {
+                        tags: HashSet::new(),
+                        mark_invalid,
+                        observer_options,
+                    },
+                );
+                self.sync_tags(&key);
+            }
+            CacheEvent::ObserverAdded(ObserverAdded { key, .. }) => self.sync_tags(&key),
+            CacheEvent::ObserverRemoved(key) => self.sync_tags(&key),
+            CacheEvent::Removed(key) => self.remove(&key),
+            CacheEvent::Updated(_) => {}
+        }
+    }
+}
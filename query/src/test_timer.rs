@@ -0,0 +1,81 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use futures_channel::oneshot;
+
+thread_local! {
+    static TIMER: RefCell<Option<TestTimer>> = const { RefCell::new(None) };
+}
+
+type Sleeps = Vec<(Duration, Option<oneshot::Sender<()>>)>;
+type Microtasks = Vec<Box<dyn FnOnce()>>;
+
+// A manual timer for tests, so delays can be asserted without a runtime.
+// Once installed, every sleep on the current thread waits until the timer is advanced,
+// and microtasks wait until they are run, instead of running immediately.
+#[derive(Clone, Default)]
+pub(crate) struct TestTimer {
+    sleeps: Rc<RefCell<Sleeps>>,
+    microtasks: Rc<RefCell<Microtasks>>,
+}
+
+impl TestTimer {
+    // Tests run on their own thread, so the timer never leaks into another test.
+    pub(crate) fn install() -> Self {
+        let timer = Self::default();
+        TIMER.with(|current| *current.borrow_mut() = Some(timer.clone()));
+        timer
+    }
+
+    pub(crate) fn current() -> Option<Self> {
+        TIMER.with(|current| current.borrow().clone())
+    }
+
+    pub(crate) async fn sleep(&self, duration: Duration) {
+        let (sender, receiver) = oneshot::channel();
+        self.sleeps.borrow_mut().push((duration, Some(sender)));
+        let _ = receiver.await;
+    }
+
+    // The durations of every sleep so far, including finished ones.
+    pub(crate) fn requested(&self) -> Vec<Duration> {
+        self.sleeps
+            .borrow()
+            .iter()
+            .map(|(duration, _)| *duration)
+            .collect()
+    }
+
+    // Finishes every pending sleep, returns how many were pending.
+    pub(crate) fn fire(&self) -> usize {
+        let senders = self
+            .sleeps
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(|(_, sender)| sender.take())
+            .collect::<Vec<_>>();
+        let count = senders.len();
+        for sender in senders {
+            let _ = sender.send(());
+        }
+        count
+    }
+
+    pub(crate) fn queue_microtask(&self, task: impl FnOnce() + 'static) {
+        self.microtasks.borrow_mut().push(Box::new(task));
+    }
+
+    // Runs the queued microtasks, including those they queue, returns how many ran.
+    pub(crate) fn run_microtasks(&self) -> usize {
+        let mut count = 0;
+        loop {
+            let tasks = self.microtasks.take();
+            if tasks.is_empty() {
+                return count;
+            }
+            count += tasks.len();
+            for task in tasks {
+                task();
+            }
+        }
+    }
+}
@@ -1,15 +1,20 @@
 use std::{cell::Cell, rc::Rc, time::Duration};
 
 use leptos::{create_effect, leptos_dom::helpers::TimeoutHandle, on_cleanup};
-use leptos_query::Instant;
 
-pub(crate) fn use_timeout(func: impl Fn() -> Option<TimeoutHandle> + 'static) {
-    // Saves last interval to be cleared on cleanup.
+use crate::instant::Instant;
+
+/// Runs `func` in an effect, which may schedule a timeout, e.g. with [`leptos::set_timeout_with_handle`].
+///
+/// The previous timeout is cleared whenever the effect re-runs, and the last one is cleared on cleanup.
+/// Effects don't run on the server, so nothing is scheduled under SSR.
+pub fn use_timeout(func: impl Fn() -> Option<TimeoutHandle> + 'static) {
+    // Saves last timeout to be cleared on cleanup.
     let timeout: Rc<Cell<Option<TimeoutHandle>>> = Rc::new(Cell::new(None));
     let clean_up = {
-        let interval = timeout.clone();
+        let timeout = timeout.clone();
         move || {
-            if let Some(handle) = interval.take() {
+            if let Some(handle) = timeout.take() {
                 handle.clear();
             }
         }
@@ -29,7 +34,9 @@ pub(crate) fn use_timeout(func: impl Fn() -> Option<TimeoutHandle> + 'static) {
     });
 }
 
-pub(crate) fn time_until_stale(updated_at: Instant, stale_time: Duration) -> Duration {
+/// The time left until data updated at `updated_at` is older than `stale_time`.
+/// Zero if it is already stale.
+pub fn time_until_stale(updated_at: Instant, stale_time: Duration) -> Duration {
     let updated_at = updated_at.0.as_millis() as i64;
     let now = Instant::now().0.as_millis() as i64;
     let stale_time = stale_time.as_millis() as i64;
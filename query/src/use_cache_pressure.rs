@@ -0,0 +1,69 @@
+use leptos::*;
+
+use crate::use_query_client;
+
+/// Returns a signal that is true while the number of queries in the cache exceeds `threshold`.
+///
+/// Useful on memory constrained devices, to warn or free memory before the cache grows too large.
+///
+/// Example:
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[component]
+/// fn App() -> impl IntoView {
+///     let under_pressure = use_cache_pressure(500);
+///
+///     create_effect(move |_| {
+///         if under_pressure.get() {
+///             logging::warn!("Query cache has more than 500 entries");
+///         }
+///     });
+/// }
+/// ```
+pub fn use_cache_pressure(threshold: usize) -> Signal<bool> {
+    let size = use_query_client().size();
+
+    // Memoized, so subscribers are only notified when the threshold is crossed.
+    create_memo(move |_| size.get() > threshold).into()
+}
+
+#[cfg(all(test, not(any(feature = "csr", feature = "hydrate"))))]
+mod tests {
+    use super::*;
+    use crate::provide_query_client;
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn notifies_when_threshold_is_crossed() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let under_pressure = use_cache_pressure(2);
+        let notified = Rc::new(Cell::new(0));
+        create_isomorphic_effect({
+            let notified = notified.clone();
+            move |_| {
+                under_pressure.track();
+                notified.set(notified.get() + 1);
+            }
+        });
+        assert!(!under_pressure.get_untracked());
+
+        client.set_query_data::<u32, u32>(0, 0);
+        client.set_query_data::<u32, u32>(1, 1);
+        assert!(!under_pressure.get_untracked());
+
+        client.set_query_data::<u32, u32>(2, 2);
+        client.set_query_data::<u32, u32>(3, 3);
+        assert!(under_pressure.get_untracked());
+        assert_eq!(2, notified.get());
+
+        client.cache.evict_query::<u32, u32>(&3);
+        client.cache.evict_query::<u32, u32>(&2);
+        assert!(!under_pressure.get_untracked());
+        assert_eq!(3, notified.get());
+    }
+}
@@ -0,0 +1,157 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use leptos::*;
+
+use crate::{
+    cache_observer::{CacheEvent, CacheObserver, ObserverAdded, QueryCacheKey},
+    use_query_client, QueryState,
+};
+
+/// Returns a signal that is true while any query tagged with `tag` is fetching.
+///
+/// Queries are tagged with [`QueryOptions::tags`](crate::QueryOptions::tags).
+/// Useful for showing a localized loading indicator for a section of the UI.
+///
+/// NOTE: A query is considered part of the group once a tagged [`use_query()`](crate::use_query()) is mounted for it,
+/// so this should be called before the tagged queries are mounted, e.g. in a parent component.
+///
+/// Example:
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[component]
+/// fn Sidebar() -> impl IntoView {
+///     let is_fetching = use_is_group_fetching("sidebar");
+///
+///     view! {
+///         <Show when=move || is_fetching.get()>
+///             <p>"Refreshing..."</p>
+///         </Show>
+///         // Queries using QueryOptions::default().set_tags(vec!["sidebar".into()])
+///     }
+/// }
+/// ```
+pub fn use_is_group_fetching(tag: &str) -> Signal<bool> {
+    let is_fetching = RwSignal::new(false);
+
+    let observer = GroupFetchingObserver {
+        tag: tag.to_string(),
+        tagged: Rc::new(RefCell::new(HashSet::new())),
+        fetching: Rc::new(RefCell::new(HashSet::new())),
+        is_fetching,
+    };
+
+    use_query_client().register_cache_observer(observer);
+
+    is_fetching.into()
+}
+
+struct GroupFetchingObserver {
+    tag: String,
+    tagged: Rc<RefCell<HashSet<QueryCacheKey>>>,
+    fetching: Rc<RefCell<HashSet<QueryCacheKey>>>,
+    is_fetching: RwSignal<bool>,
+}
+
+impl GroupFetchingObserver {
+    fn update_fetching(&self, key: QueryCacheKey, state: &QueryState<String>) {
+        let mut fetching = self.fetching.borrow_mut();
+        if matches!(state, QueryState::Loading | QueryState::Fetching(_)) {
+            fetching.insert(key);
+        } else {
+            fetching.remove(&key);
+        }
+    }
+
+    fn sync_signal(&self) {
+        let fetching = self.fetching.borrow();
+        let is_fetching = self
+            .tagged
+            .borrow()
+            .iter()
+            .any(|key| fetching.contains(key));
+
+        // Avoid notifying subscribers when nothing changed.
+        if self.is_fetching.get_untracked() != is_fetching {
+            self.is_fetching.set(is_fetching);
+        }
+    }
+}
+
+impl CacheObserver for GroupFetchingObserver {
+    fn process_cache_event(&self, event: CacheEvent) {
+        match event {
+            CacheEvent::Created(query) => self.update_fetching(query.key, &query.state),
+            CacheEvent::Updated(query) => self.update_fetching(query.key, &query.state),
+            CacheEvent::Removed(key) => {
+                self.tagged.borrow_mut().remove(&key);
+                self.fetching.borrow_mut().remove(&key);
+            }
+            CacheEvent::ObserverAdded(ObserverAdded { key, options }) => {
+                if options.tags.contains(&self.tag) {
+                    self.tagged.borrow_mut().insert(key);
+                }
+            }
+            CacheEvent::ObserverRemoved(_) => return,
+        }
+        self.sync_signal();
+    }
+}
+
+#[cfg(all(test, not(any(feature = "csr", feature = "hydrate"))))]
+mod tests {
+    use super::*;
+    use crate::{
+        provide_query_client, query, query::Query, query_observer::QueryObserver, QueryOptions,
+    };
+    use futures::{future::LocalBoxFuture, FutureExt};
+    use futures_channel::oneshot;
+
+    // Starts a fetch that completes once a value is sent.
+    fn start_fetch(query: Query<u32, u32>) -> (oneshot::Sender<u32>, LocalBoxFuture<'static, ()>) {
+        let (sender, receiver) = oneshot::channel::<u32>();
+        let receiver = RefCell::new(Some(receiver));
+        let mut fetch = query::execute_query(query, move |_| {
+            receiver
+                .borrow_mut()
+                .take()
+                .expect("Fetcher called once")
+                .map(|value| value.unwrap_or_default())
+        })
+        .boxed_local();
+        assert!((&mut fetch).now_or_never().is_none());
+        (sender, fetch)
+    }
+
+    #[test]
+    fn only_tagged_queries_count() {
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        let is_fetching = use_is_group_fetching("sidebar");
+
+        let tagged = client.cache.get_or_create_query::<u32, u32>(0);
+        let _tagged_observer = QueryObserver::no_fetcher(
+            QueryOptions::default().set_tags(vec!["sidebar".into()]),
+            Some(tagged.clone()),
+        );
+        let untagged = client.cache.get_or_create_query::<u32, u32>(1);
+        let _untagged_observer =
+            QueryObserver::no_fetcher(QueryOptions::default(), Some(untagged.clone()));
+
+        let (untagged_sender, untagged_fetch) = start_fetch(untagged);
+        assert!(!is_fetching.get_untracked());
+
+        let (tagged_sender, tagged_fetch) = start_fetch(tagged);
+        assert!(is_fetching.get_untracked());
+
+        tagged_sender.send(1).unwrap();
+        assert!(tagged_fetch.now_or_never().is_some());
+        assert!(!is_fetching.get_untracked());
+
+        untagged_sender.send(1).unwrap();
+        assert!(untagged_fetch.now_or_never().is_some());
+        assert!(!is_fetching.get_untracked());
+    }
+}
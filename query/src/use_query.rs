@@ -2,11 +2,12 @@ use crate::query::Query;
 use crate::query_observer::{ListenerKey, QueryObserver};
 use crate::query_result::QueryResult;
 use crate::{
-    query_is_suppressed, use_query_client, QueryOptions, QueryState, RefetchFn, ResourceOption,
+    query_is_suppressed, use_query_client, QueryData, QueryError, QueryOptions, QueryState,
+    RefetchFn, ResourceOption,
 };
 use leptos::leptos_dom::HydrationCtx;
 use leptos::*;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::future::Future;
 use std::rc::Rc;
 use std::time::Duration;
@@ -21,6 +22,12 @@ use std::time::Duration;
 /// - Refetch intervals
 /// - Memory management with cache lifetimes
 ///
+/// If the same key is used with multiple fetchers, the fetcher of the most recently mounted `use_query` is used.
+/// So re-mounting with a new fetcher (e.g. one that captures a refreshed auth token) replaces the previous one.
+/// To replace the fetcher of mounted queries, use [`QueryClient::set_query_fetcher()`](crate::QueryClient::set_query_fetcher).
+///
+/// The `key` function is tracked: it is re-run whenever a signal it reads changes.
+/// Keep it cheap and derive it only from the signals that identify the query, since each re-run performs a cache lookup.
 ///
 /// Example
 /// ```
@@ -68,29 +75,122 @@ where
     K: crate::QueryKey + 'static,
     V: crate::QueryValue + 'static,
     Fu: Future<Output = V> + 'static,
+{
+    use futures::FutureExt;
+
+    use_fallible_query(key, move |key| fetcher(key).map(Ok), options, true)
+}
+
+/// Creates a query whose fetcher can fail.
+///
+/// When the fetcher returns an error, the query transitions to [`QueryState::Error`], keeping the last good data if any.
+/// The error is available as [`QueryResult::error`], downcast it with [`QueryError::downcast()`](crate::QueryError::downcast).
+/// Its [`Display`](std::fmt::Display) output is shown in the devtools.
+/// A failed query is retried when it is refetched, or when an observer mounts.
+///
+/// Example
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[derive(Debug)]
+/// struct NotFound;
+///
+/// impl std::fmt::Display for NotFound {
+///     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+///         f.write_str("Track not found")
+///     }
+/// }
+///
+/// fn use_track_query(id: u32) -> QueryResult<String, impl RefetchFn> {
+///     use_query_result(
+///         move || id,
+///         |id| async move {
+///             if id == 0 {
+///                 Err(NotFound)
+///             } else {
+///                 Ok(format!("Track {id}"))
+///             }
+///         },
+///         QueryOptions::default(),
+///     )
+/// }
+///
+/// #[component]
+/// fn Track(id: u32) -> impl IntoView {
+///     let QueryResult { data, error, .. } = use_track_query(id);
+///     let not_found = move || error.get().is_some_and(|error| error.downcast::<NotFound>().is_some());
+///
+///     view! {
+///         <Show when=not_found fallback=move || view! { <p>{data}</p> }>
+///             <p>"Track not found"</p>
+///         </Show>
+///     }
+/// }
+/// ```
+pub fn use_query_result<K, V, E, Fu>(
+    key: impl Fn() -> K + 'static,
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: QueryOptions<V>,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    E: std::fmt::Debug + std::fmt::Display + Send + Sync + 'static,
+    Fu: Future<Output = Result<V, E>> + 'static,
+{
+    use futures::FutureExt;
+
+    use_fallible_query(
+        key,
+        move |key| fetcher(key).map(|result| result.map_err(QueryError::new)),
+        options,
+        false,
+    )
+}
+
+fn use_fallible_query<K, V, Fu>(
+    key: impl Fn() -> K + 'static,
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: QueryOptions<V>,
+    infallible: bool,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    Fu: Future<Output = Result<V, QueryError>> + 'static,
 {
     let options = options.validate();
+    let is_enabled = options.enabled.unwrap_or_else(|| Signal::derive(|| true));
     // Find relevant state.
-    let query = use_query_client().cache.get_query_signal(key);
+    let initial_data = {
+        let initial_data_fn = options.initial_data_fn.clone();
+        // The server seeds its own default, while the client fetches the real value.
+        #[cfg(not(any(feature = "hydrate", feature = "csr")))]
+        let ssr_default = options.ssr_default.clone();
+        let updated_at = options.initial_data_updated_at;
+        move || {
+            let data = initial_data_fn.as_ref().and_then(|f| f());
+            #[cfg(not(any(feature = "hydrate", feature = "csr")))]
+            let data = data.or_else(|| ssr_default.clone());
+            Some(QueryData {
+                data: data?,
+                updated_at: updated_at.unwrap_or_else(crate::Instant::now),
+            })
+        }
+    };
+    let query = use_query_client().cache.get_query_signal(key, initial_data);
 
-    let query_state = register_observer_handle_cleanup(fetcher, query, options.clone());
+    let query_state = register_observer_handle_cleanup(fetcher, query, options.clone(), infallible);
 
-    let resource_fetcher = move |query: Query<K, V>| {
-        async move {
-            match query.get_state() {
-                // Immediately provide cached value.
-                QueryState::Loaded(data)
-                | QueryState::Invalid(data)
-                | QueryState::Fetching(data) => ResourceData(Some(data.data)),
-
-                // Suspend indefinitely and wait for interruption.
-                QueryState::Created | QueryState::Loading => {
-                    sleep(LONG_TIME).await;
-                    ResourceData(None)
-                }
-            }
-        }
+    let suspended = SuspendedLoads::released_on_cleanup();
+
+    let fetches = {
+        let options = options.clone();
+        move || options.fetches()
     };
+    let resource_fetcher =
+        move |query: Query<K, V>| load_resource_data(query, fetches(), suspended.clone());
 
     let resource: Resource<Query<K, V>, ResourceData<V>> = {
         let default = options.default_value;
@@ -103,6 +203,21 @@ where
             ResourceOption::Blocking => {
                 create_blocking_resource(move || query.get(), resource_fetcher)
             }
+            // Blocks on the server, so the HTML contains the data, but not on the client.
+            ResourceOption::BlockingSsr => {
+                #[cfg(not(any(feature = "hydrate", feature = "csr")))]
+                {
+                    create_blocking_resource(move || query.get(), resource_fetcher)
+                }
+                #[cfg(any(feature = "hydrate", feature = "csr"))]
+                {
+                    create_resource_with_initial_value(
+                        move || query.get(),
+                        resource_fetcher,
+                        default.map(|default| ResourceData(Some(default))),
+                    )
+                }
+            }
             ResourceOption::Local => create_local_resource_with_initial_value(
                 move || query.get(),
                 resource_fetcher,
@@ -112,12 +227,19 @@ where
     };
 
     // Ensure latest data in resource.
-    create_isomorphic_effect(move |_| {
+    create_isomorphic_effect(move |last_version: Option<(Query<K, V>, u64)>| {
         query_state.track();
+        let query = query.get_untracked();
+        let version = query.data_version();
+        // The resource already has the data, so views of it don't re-render.
+        if last_version.as_ref() == Some(&(query.clone(), version)) {
+            return (query, version);
+        }
         // If query is supressed, we have to make sure we don't refetch to avoid calling spawn_local.
         if !query_is_suppressed() {
             resource.refetch();
         }
+        (query, version)
     });
 
     // First read.
@@ -132,8 +254,36 @@ where
         }
     }
 
+    let (previous_data, is_previous_data) = if options.keep_previous_data {
+        use_previous_data(query, query_state)
+    } else {
+        (Signal::derive(|| None), Signal::derive(|| false))
+    };
+
+    // The server suspends, so the fetched value is streamed to the client.
+    let placeholder_data = options.placeholder_data.filter(|_| !cfg!(feature = "ssr"));
     let data = Signal::derive({
         move || {
+            // Don't read the loading resource either, so the previous data isn't replaced by a fallback.
+            if is_previous_data.get() {
+                return previous_data.get();
+            }
+
+            let placeholder = || {
+                let first_load = query_state
+                    .with(|state| matches!(state, QueryState::Created | QueryState::Loading));
+                placeholder_data
+                    .as_ref()
+                    .filter(|_| first_load)
+                    .map(|f| f())
+            };
+            // Don't read a loading resource, that suspends.
+            if resource.loading().get() {
+                if let Some(placeholder) = placeholder() {
+                    return Some(placeholder);
+                }
+            }
+
             let read = resource.get().and_then(|r| r.0);
             let _ = read;
 
@@ -145,10 +295,10 @@ where
                 let query = query.get_untracked();
                 if query.with_state(|state| matches!(state, QueryState::Created)) {
                     let data = crate::QueryData::now(data.clone());
-                    query.set_state(QueryState::Loaded(data));
+                    query.set_state_from(QueryState::Loaded(data), crate::FetchSource::Initial);
                 }
             }
-            read
+            read.or_else(placeholder)
         }
     });
 
@@ -164,16 +314,503 @@ where
         is_invalid: Signal::derive(move || {
             query_state.with(|state| matches!(state, QueryState::Invalid(_)))
         }),
+        is_enabled,
+        is_previous_data,
+        error: Signal::derive(move || query_state.with(|state| state.error().cloned())),
+        last_fetch_source: Signal::derive(move || {
+            // Source is updated right before the state, so tracking the state is sufficient.
+            query_state.track();
+            query.with(|q| q.get_fetch_source())
+        }),
+        age: use_age(query_state),
         refetch: move || query.with_untracked(|q| q.execute()),
+        retry: Rc::new(move || query.with_untracked(|q| q.retry())),
+        raw_query: Signal::derive(move || Rc::new(query.get()) as Rc<dyn std::any::Any>),
+    }
+}
+
+/// Creates a query that derives view-specific data from the cached value, e.g. a single field of a large struct.
+///
+/// The cache keeps the full value `V`, which is shared with every other query of the same key.
+/// `select` is memoized per call site, so consumers of [`QueryResult::data`] and [`QueryResult::state`] are only notified when the selected value changes.
+/// Multiple selectors on the same key share the cached value and its fetches.
+///
+/// NOTE: [`QueryResult::query()`] returns [`None`](Option::None) on the returned result, as the cached value is not of type `S`.
+///
+/// Example
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+/// struct Track {
+///     title: String,
+///     lyrics: String,
+/// }
+///
+/// async fn get_track(id: u32) -> Track {
+///     todo!()
+/// }
+///
+/// #[component]
+/// fn TrackTitle(id: u32) -> impl IntoView {
+///     let QueryResult { data, .. } = use_query_with_select(
+///         move || id,
+///         get_track,
+///         QueryOptions::default(),
+///         |track| track.title.clone(),
+///     );
+///
+///     view! { <h1>{data}</h1> }
+/// }
+/// ```
+pub fn use_query_with_select<K, V, S, Fu>(
+    key: impl Fn() -> K + 'static,
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: QueryOptions<V>,
+    select: impl Fn(&V) -> S + 'static,
+) -> QueryResult<S, impl RefetchFn>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    S: Clone + PartialEq + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    let result = use_query(key, fetcher, options);
+    let select = Rc::new(select);
+
+    let data = select_memo(result.data, {
+        let select = select.clone();
+        move |data: &Option<V>| data.as_ref().map(|data| select(data))
+    });
+    let state = select_memo(result.state, move |state| {
+        state.map_data(|data| select(data))
+    });
+
+    QueryResult {
+        data,
+        state,
+        is_loading: result.is_loading,
+        is_fetching: result.is_fetching,
+        is_invalid: result.is_invalid,
+        is_enabled: result.is_enabled,
+        is_previous_data: result.is_previous_data,
+        error: result.error,
+        last_fetch_source: result.last_fetch_source,
+        age: result.age,
+        refetch: result.refetch,
+        retry: result.retry,
+        // Holds a `Query<K, V>`, so `query()` returns None unless `S` is `V`.
+        raw_query: result.raw_query,
+    }
+}
+
+/// Creates a query that depends on a value that isn't available yet, e.g. the id of the signed in user, or the data of another query.
+///
+/// The query is [disabled](QueryOptions::enabled) while `source` is `None`, so it is neither fetched nor suspends, and [`QueryResult::data`] is `None`.
+/// Once `source` is `Some`, `key_fn` derives the key from it, and the query is fetched like one of [`use_query()`].
+///
+/// The query is created once `source` is first `Some`, and cached under `K` like any other query,
+/// so it shares its data with queries of the same key, and can be accessed through the [`QueryClient`](crate::QueryClient), e.g. to invalidate it.
+/// If `source` becomes `None` again, the query keeps its last key and is disabled.
+///
+/// Example
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// async fn get_user_id(name: String) -> u32 {
+///     todo!()
+/// }
+///
+/// async fn get_posts(user_id: u32) -> Vec<String> {
+///     todo!()
+/// }
+///
+/// fn use_user_posts(name: String) -> QueryResult<Vec<String>, impl RefetchFn> {
+///     let user_id = use_query(move || name.clone(), get_user_id, QueryOptions::default());
+///
+///     // Fetched once the user id is loaded.
+///     use_dependent_query(
+///         user_id.data,
+///         |user_id| user_id,
+///         get_posts,
+///         QueryOptions::default(),
+///     )
+/// }
+/// ```
+pub fn use_dependent_query<S, K, V, Fu>(
+    source: Signal<Option<S>>,
+    key_fn: impl Fn(S) -> K + 'static,
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: QueryOptions<V>,
+) -> QueryResult<V, impl RefetchFn>
+where
+    S: Clone + 'static,
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    let has_source = Signal::derive(move || source.with(Option::is_some));
+    let enabled = match options.enabled {
+        Some(enabled) => Signal::derive(move || has_source.get() && enabled.get()),
+        None => has_source,
+    };
+    let options = options.set_enabled(Some(enabled));
+
+    // The key is derived from the source, so the query can't be created before the source is available.
+    let dependent = RwSignal::new(None);
+    let owner = Owner::current();
+    let key_fn = Rc::new(key_fn);
+    let fetcher = Rc::new(fetcher);
+    create_isomorphic_effect(move |created: Option<bool>| {
+        if created == Some(true) {
+            return true;
+        }
+        let Some(initial_key) = source.get().map(|source| key_fn(source)) else {
+            return false;
+        };
+        let key_fn = key_fn.clone();
+        let fetcher = fetcher.clone();
+        let options = options.clone();
+        // Owned by the caller, not by this effect, so the query outlives the next run.
+        let create = || {
+            // Keeps the last key while the source is unavailable.
+            let key = create_memo(move |last: Option<&K>| match source.get() {
+                Some(source) => key_fn(source),
+                None => last.cloned().unwrap_or_else(|| initial_key.clone()),
+            });
+            use_query(move || key.get(), move |key| fetcher(key), options)
+        };
+        let result = match owner {
+            Some(owner) => with_owner(owner, create),
+            None => create(),
+        };
+        dependent.set(Some(result));
+        true
+    });
+
+    QueryResult {
+        data: Signal::derive(move || dependent.with(|d| d.as_ref().and_then(|d| d.data.get()))),
+        state: Signal::derive(move || {
+            dependent.with(|d| d.as_ref().map(|d| d.state.get()).unwrap_or_default())
+        }),
+        is_loading: Signal::derive(move || {
+            dependent.with(|d| d.as_ref().is_some_and(|d| d.is_loading.get()))
+        }),
+        is_fetching: Signal::derive(move || {
+            dependent.with(|d| d.as_ref().is_some_and(|d| d.is_fetching.get()))
+        }),
+        is_invalid: Signal::derive(move || {
+            dependent.with(|d| d.as_ref().is_some_and(|d| d.is_invalid.get()))
+        }),
+        is_enabled: Signal::derive(move || {
+            dependent.with(|d| d.as_ref().is_some_and(|d| d.is_enabled.get()))
+        }),
+        is_previous_data: Signal::derive(move || {
+            dependent.with(|d| d.as_ref().is_some_and(|d| d.is_previous_data.get()))
+        }),
+        error: Signal::derive(move || dependent.with(|d| d.as_ref().and_then(|d| d.error.get()))),
+        last_fetch_source: Signal::derive(move || {
+            dependent.with(|d| d.as_ref().and_then(|d| d.last_fetch_source.get()))
+        }),
+        age: Signal::derive(move || dependent.with(|d| d.as_ref().and_then(|d| d.age.get()))),
+        refetch: move || {
+            dependent.with_untracked(|d| {
+                if let Some(d) = d {
+                    (d.refetch)()
+                }
+            })
+        },
+        retry: Rc::new(move || {
+            dependent.with_untracked(|d| {
+                if let Some(d) = d {
+                    (d.retry)()
+                }
+            })
+        }),
+        raw_query: Signal::derive(move || {
+            dependent.with(|d| match d {
+                Some(d) => d.raw_query.get(),
+                None => Rc::new(()) as Rc<dyn std::any::Any>,
+            })
+        }),
+    }
+}
+
+// Snapshots the data of the last key that had data, which is shown while the current key loads for the first time.
+fn use_previous_data<K, V>(
+    query: Memo<Query<K, V>>,
+    query_state: Signal<QueryState<V>>,
+) -> (Signal<Option<V>>, Signal<bool>)
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+{
+    let previous_data = RwSignal::new(None::<V>);
+    create_isomorphic_effect(move |previous: Option<Query<K, V>>| {
+        let current = query.get();
+        if let Some(data) = previous
+            .filter(|previous| *previous != current)
+            .and_then(|previous| previous.with_state(|state| state.data().cloned()))
+        {
+            previous_data.set(Some(data));
+        }
+        current
+    });
+    let is_previous_data = Signal::derive(move || {
+        query_state.with(|state| matches!(state, QueryState::Created | QueryState::Loading))
+            && previous_data.with(Option::is_some)
+    });
+    (previous_data.into(), is_previous_data)
+}
+
+// Only notifies subscribers when the selected value changes.
+fn select_memo<T, S>(source: Signal<T>, select: impl Fn(&T) -> S + 'static) -> Signal<S>
+where
+    T: 'static,
+    S: PartialEq + 'static,
+{
+    create_memo(move |_| source.with(|source| select(source))).into()
+}
+
+/// Creates a query, choosing the [`ResourceOption`] at setup from a reactive source.
+///
+/// Useful when the resource type depends on runtime context, e.g. blocking for above-the-fold content and non-blocking otherwise.
+/// The resource option in `options` is ignored in favor of `resource_option`.
+///
+/// NOTE: The resource type is fixed once the query is created. Later changes to `resource_option` are not applied,
+/// and a warning is logged in debug builds.
+///
+/// Example
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// fn use_track_query(id: u32, above_the_fold: Signal<bool>) -> QueryResult<String, impl RefetchFn> {
+///     use_query_with_resource_option(
+///         move || id,
+///         |id| async move { format!("Track {id}") },
+///         QueryOptions::default(),
+///         Signal::derive(move || {
+///             if above_the_fold.get() {
+///                 ResourceOption::Blocking
+///             } else {
+///                 ResourceOption::NonBlocking
+///             }
+///         }),
+///     )
+/// }
+/// ```
+pub fn use_query_with_resource_option<K, V, Fu>(
+    key: impl Fn() -> K + 'static,
+    fetcher: impl Fn(K) -> Fu + 'static,
+    options: QueryOptions<V>,
+    resource_option: impl Into<MaybeSignal<ResourceOption>>,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    let resource_option = resource_option.into();
+    let initial_option = resource_option.get_untracked();
+
+    create_effect(move |_| {
+        let current_option = resource_option.get();
+        if current_option != initial_option {
+            logging::debug_warn!(
+                "use_query_with_resource_option: Resource option changed from {:?} to {:?} after the query was created. The change is ignored.",
+                initial_option,
+                current_option
+            );
+        }
+    });
+
+    use_query(
+        key,
+        fetcher,
+        options.set_resource_option(Some(initial_option)),
+    )
+}
+
+/// Creates a query whose fetcher also receives the client's request context,
+/// set with [`QueryClient::set_request_context()`](crate::QueryClient::set_request_context).
+///
+/// Useful for cross-cutting request concerns, e.g. auth headers or a request id, instead of capturing them in every fetcher.
+/// The context is read when a fetch starts, so a changed context flows into the next fetch.
+///
+/// The fetcher receives `None` if no context of type `C` was set, e.g. before the user signed in.
+///
+/// Example
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[derive(Clone)]
+/// struct Auth {
+///     token: String,
+/// }
+///
+/// fn use_track_query(id: u32) -> QueryResult<String, impl RefetchFn> {
+///     use_query_with_context(
+///         move || id,
+///         |id, auth: Option<Auth>| async move {
+///             match auth {
+///                 Some(auth) => format!("Track {id} for {}", auth.token),
+///                 None => format!("Track {id}"),
+///             }
+///         },
+///         QueryOptions::default(),
+///     )
+/// }
+/// ```
+pub fn use_query_with_context<K, V, C, Fu>(
+    key: impl Fn() -> K + 'static,
+    fetcher: impl Fn(K, Option<C>) -> Fu + 'static,
+    options: QueryOptions<V>,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    C: Clone + 'static,
+    Fu: Future<Output = V> + 'static,
+{
+    use_query(key, with_request_context(fetcher), options)
+}
+
+/// Creates a query whose fetcher yields progressive values, e.g. tokens of a generated text or pages of a chunked list.
+///
+/// Every value yielded by the stream replaces the data of the query, which stays [`QueryState::Fetching`] until the stream ends.
+/// The last value then settles the query as [`QueryState::Loaded`].
+/// Each value should therefore be the complete data so far, e.g. accumulate with [`StreamExt::scan`](futures::StreamExt::scan).
+///
+/// Cancelling the request, e.g. with [`QueryClient::cancel_query()`](crate::QueryClient::cancel_query), stops consuming the stream.
+/// A cancelled request is reverted like any other, so partial values of a first load are discarded.
+///
+/// If the stream ends without yielding a value, the query fails with an [`EmptyStream`](crate::EmptyStream) error, keeping the previous data if any.
+///
+/// Example
+/// ```
+/// use futures::stream::{self, StreamExt};
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// fn use_summary_query(id: u32) -> QueryResult<String, impl RefetchFn> {
+///     use_streaming_query(
+///         move || id,
+///         |id| {
+///             let tokens = vec!["Summary".to_string(), " of".to_string(), format!(" {id}")];
+///             stream::iter(tokens).scan(String::new(), |text, token| {
+///                 text.push_str(&token);
+///                 futures::future::ready(Some(text.clone()))
+///             })
+///         },
+///         QueryOptions::default(),
+///     )
+/// }
+/// ```
+pub fn use_streaming_query<K, V, St>(
+    key: impl Fn() -> K + 'static,
+    stream_fetcher: impl Fn(K) -> St + 'static,
+    options: QueryOptions<V>,
+) -> QueryResult<V, impl RefetchFn>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    St: futures::Stream<Item = V> + 'static,
+{
+    use_fallible_query(key, consume_stream(stream_fetcher), options, false)
+}
+
+// Publishes every value of the stream to the query, and resolves with the last one.
+// Fails if the stream is empty, keeping the previous data if any.
+pub(crate) fn consume_stream<K, V, St>(
+    stream_fetcher: impl Fn(K) -> St + 'static,
+) -> impl Fn(K) -> futures::future::LocalBoxFuture<'static, Result<V, QueryError>> + 'static
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+    St: futures::Stream<Item = V> + 'static,
+{
+    use futures::{FutureExt, StreamExt};
+
+    let client = use_query_client();
+    move |key| {
+        // Called synchronously when the request starts, so this is the token of this request.
+        let query = client.cache.get_query::<K, V, _>(&key);
+        let token = query.as_ref().and_then(|query| query.get_cancel_token());
+        let stream = stream_fetcher(key);
+        async move {
+            let mut stream = std::pin::pin!(stream);
+            let mut latest = None;
+            while let Some(value) = stream.next().await {
+                // The result of a cancelled request is discarded, so stop here.
+                if token.as_ref().is_some_and(|token| token.is_cancelled()) {
+                    return Ok(value);
+                }
+                if let Some(query) = query.as_ref() {
+                    query.set_state(QueryState::Fetching(QueryData::now(value.clone())));
+                }
+                latest = Some(value);
+            }
+            latest.ok_or_else(|| QueryError::new(crate::EmptyStream))
+        }
+        .boxed_local()
     }
 }
 
+// Passes the client's current request context to the fetcher.
+pub(crate) fn with_request_context<K, C, Fu>(
+    fetcher: impl Fn(K, Option<C>) -> Fu + 'static,
+) -> impl Fn(K) -> Fu + 'static
+where
+    C: Clone + 'static,
+{
+    let client = use_query_client();
+    move |key| fetcher(key, client.request_context::<C>())
+}
+
+// How long ago the data was updated, re-evaluated on a coarse tick that slows down as the data ages.
+fn use_age<V>(state: Signal<QueryState<V>>) -> Signal<Option<Duration>>
+where
+    V: crate::QueryValue + 'static,
+{
+    let tick = create_trigger();
+    let age = move || {
+        state
+            .with(|state| state.updated_at())
+            .map(|updated_at| crate::Instant::now().0.saturating_sub(updated_at.0))
+    };
+
+    #[cfg(any(feature = "hydrate", feature = "csr"))]
+    crate::use_timeout(move || {
+        tick.track();
+        let interval = match age()? {
+            age if age < Duration::from_secs(60) => Duration::from_secs(1),
+            age if age < Duration::from_secs(60 * 60) => Duration::from_secs(60),
+            _ => Duration::from_secs(60 * 60),
+        };
+        set_timeout_with_handle(move || tick.notify(), interval).ok()
+    });
+
+    Signal::derive(move || {
+        tick.track();
+        age()
+    })
+}
+
 const LONG_TIME: Duration = Duration::from_secs(60 * 60 * 24);
 
-async fn sleep(duration: Duration) {
+pub(crate) async fn sleep(duration: Duration) {
     use cfg_if::cfg_if;
     cfg_if! {
-        if #[cfg(any(feature = "hydrate", feature = "csr"))] {
+        // Tests have no runtime, delays only take effect with an installed test timer.
+        if #[cfg(test)] {
+            if let Some(timer) = crate::test_timer::TestTimer::current() {
+                timer.sleep(duration).await;
+            }
+        } else if #[cfg(any(feature = "hydrate", feature = "csr"))] {
             gloo_timers::future::sleep(duration).await;
         } else if #[cfg(feature = "ssr")] {
             tokio::time::sleep(duration).await;
@@ -184,6 +821,80 @@ async fn sleep(duration: Duration) {
     }
 }
 
+pub(crate) fn queue_microtask(task: impl FnOnce() + 'static) {
+    // Off the browser, microtasks run immediately, unless a test timer queues them.
+    #[cfg(test)]
+    if let Some(timer) = crate::test_timer::TestTimer::current() {
+        timer.queue_microtask(task);
+        return;
+    }
+    leptos::queue_microtask(task);
+}
+
+async fn load_resource_data<K, V>(
+    query: Query<K, V>,
+    fetches: bool,
+    suspended: SuspendedLoads,
+) -> ResourceData<V>
+where
+    K: crate::QueryKey + 'static,
+    V: crate::QueryValue + 'static,
+{
+    match query.get_state() {
+        // Immediately provide cached value.
+        QueryState::Loaded(data)
+        | QueryState::Invalid(data)
+        | QueryState::Fetching(data)
+        | QueryState::Error {
+            data: Some(data), ..
+        } => ResourceData(Some(data.data)),
+
+        // The fetch failed, there is nothing to wait for.
+        QueryState::Error { data: None, .. } => ResourceData(None),
+
+        // Nothing will be fetched, don't suspend.
+        QueryState::Created if !fetches => ResourceData(None),
+
+        // Suspend indefinitely and wait for interruption.
+        // Released on unmount, e.g. before a fetch started, so an outer Suspense doesn't stay on its fallback.
+        QueryState::Created | QueryState::Loading => {
+            futures::future::select(Box::pin(sleep(LONG_TIME)), suspended.wait()).await;
+            ResourceData(None)
+        }
+    }
+}
+
+// Resource loads waiting for the query to receive data.
+#[derive(Clone, Default)]
+struct SuspendedLoads(Rc<RefCell<Vec<futures_channel::oneshot::Sender<()>>>>);
+
+impl SuspendedLoads {
+    // Pending loads are released when the current owner is cleaned up, i.e. the component unmounts.
+    fn released_on_cleanup() -> Self {
+        let suspended = Self::default();
+        on_cleanup({
+            let suspended = suspended.clone();
+            move || suspended.release()
+        });
+        suspended
+    }
+
+    fn wait(&self) -> futures_channel::oneshot::Receiver<()> {
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        let mut senders = self.0.borrow_mut();
+        senders.retain(|sender| !sender.is_canceled());
+        senders.push(sender);
+        receiver
+    }
+
+    // Resolves every pending load.
+    fn release(&self) {
+        for sender in self.0.borrow_mut().drain(..) {
+            let _ = sender.send(());
+        }
+    }
+}
+
 /// Wrapper type to enable using `Serializable`
 #[derive(Clone, Debug)]
 pub struct ResourceData<V>(Option<V>);
@@ -212,24 +923,27 @@ pub(crate) fn register_observer_handle_cleanup<K, V, Fu>(
     fetcher: impl Fn(K) -> Fu + 'static,
     query: Memo<Query<K, V>>,
     options: QueryOptions<V>,
+    infallible: bool,
 ) -> Signal<QueryState<V>>
 where
     K: crate::QueryKey + 'static,
     V: crate::QueryValue + 'static,
-    Fu: Future<Output = V> + 'static,
+    Fu: Future<Output = Result<V, QueryError>> + 'static,
 {
     let state_signal = RwSignal::new(query.get_untracked().get_state());
-    let observer = Rc::new(QueryObserver::with_fetcher(
+    let enabled = options.enabled;
+    let observer = Rc::new(QueryObserver::with_fetcher_of_kind(
         fetcher,
         options,
         query.get_untracked(),
+        infallible,
     ));
     let listener = Rc::new(Cell::new(None::<ListenerKey>));
 
     create_isomorphic_effect({
         let observer = observer.clone();
         let listener = listener.clone();
-        move |_| {
+        move |was_enabled: Option<bool>| {
             // Ensure listener is set
             if listener.get().is_none() {
                 let listener_id = observer.add_listener(move |state| {
@@ -241,7 +955,15 @@ where
             // Update
             let query = query.get();
             state_signal.set(query.get_state());
-            observer.update_query(Some(query));
+            observer.update_query(Some(query.clone()));
+
+            // Fetch once enabled, e.g. when the value the query depends on becomes available.
+            #[allow(clippy::unnecessary_map_or)]
+            let enabled = enabled.map_or(true, |enabled| enabled.get());
+            if enabled && was_enabled == Some(false) {
+                observer.ensure_execute(&query);
+            }
+            enabled
         }
     });
 
@@ -256,3 +978,606 @@ where
 
     state_signal.into()
 }
+
+#[cfg(all(test, not(any(feature = "csr", feature = "hydrate"))))]
+mod tests {
+    use super::{load_resource_data, SuspendedLoads};
+    use futures::FutureExt;
+
+    #[test]
+    fn unmount_releases_suspended_load_of_unfetched_query() {
+        use crate::test_timer::TestTimer;
+        use crate::*;
+        use leptos::*;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        let _timer = TestTimer::install();
+
+        // The component mounts, and its resource suspends before the query is fetched.
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let (suspended, disposer) =
+            as_child_of_current_owner(|_: ()| SuspendedLoads::released_on_cleanup())(());
+        let mut first = load_resource_data(query.clone(), true, suspended.clone()).boxed_local();
+        let mut second = load_resource_data(query.clone(), true, suspended).boxed_local();
+        assert!((&mut first).now_or_never().is_none());
+        assert!((&mut second).now_or_never().is_none());
+
+        // Unmounting resolves the loads, so an outer Suspense shows its children.
+        drop(disposer);
+        assert_eq!(Some(None), first.now_or_never().map(|data| data.0));
+        assert_eq!(Some(None), second.now_or_never().map(|data| data.0));
+        assert!(query.with_state(|state| matches!(state, QueryState::Created)));
+    }
+
+    #[test]
+    fn age() {
+        use crate::{Instant, QueryData, QueryState};
+        use leptos::*;
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        let state = RwSignal::new(QueryState::<u32>::Created);
+        let age = super::use_age(state.into());
+        assert_eq!(None, age.get_untracked());
+
+        let loaded_ago = |ago: Duration| {
+            state.set(QueryState::Loaded(QueryData {
+                data: 0,
+                updated_at: Instant(Instant::now().0 - ago),
+            }))
+        };
+        loaded_ago(Duration::from_secs(180));
+        let elapsed = age.get_untracked().unwrap();
+        assert!(elapsed >= Duration::from_secs(180) && elapsed < Duration::from_secs(181));
+
+        // Data from the future, e.g. due to clock skew, is brand new.
+        loaded_ago(Duration::ZERO);
+        state.update(|state| {
+            if let QueryState::Loaded(data) = state {
+                data.updated_at = Instant(data.updated_at.0 + Duration::from_secs(60));
+            }
+        });
+        assert_eq!(Some(Duration::ZERO), age.get_untracked());
+    }
+
+    #[test]
+    fn finished_loads_are_pruned() {
+        let suspended = SuspendedLoads::default();
+        drop(suspended.wait());
+        let _pending = suspended.wait();
+
+        assert_eq!(1, suspended.0.borrow().len());
+    }
+
+    #[test]
+    fn streaming_query_publishes_every_value() {
+        use crate::query_observer::QueryObserver;
+        use crate::{provide_query_client, query, use_query_client, QueryOptions, QueryState};
+        use leptos::*;
+        use std::{cell::RefCell, rc::Rc};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let observer = QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+        let notifications = Rc::new(RefCell::new(Vec::new()));
+        observer.add_listener({
+            let notifications = notifications.clone();
+            move |state: &QueryState<u32>| notifications.borrow_mut().push(state.clone())
+        });
+
+        let fetcher = super::consume_stream(|_: u32| futures::stream::iter([1, 2, 3]));
+        assert!(query::execute_fallible_query(query.clone(), fetcher)
+            .now_or_never()
+            .is_some());
+
+        let notifications = notifications.borrow();
+        let partial = notifications
+            .iter()
+            .filter_map(|state| match state {
+                QueryState::Fetching(data) => Some(data.data),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 2, 3], partial);
+        assert!(matches!(
+            notifications.last(),
+            Some(QueryState::Loaded(data)) if data.data == 3
+        ));
+        assert!(!query.is_fetching());
+    }
+
+    #[test]
+    fn cancelled_streaming_query_stops_consuming() {
+        use crate::{provide_query_client, query, use_query_client};
+        use futures::StreamExt;
+        use leptos::*;
+        use std::{cell::Cell, rc::Rc};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let consumed = Rc::new(Cell::new(0));
+        let fetcher = super::consume_stream({
+            let consumed = consumed.clone();
+            let client = client.clone();
+            move |_: u32| {
+                let consumed = consumed.clone();
+                let client = client.clone();
+                futures::stream::iter([1, 2, 3]).inspect(move |value| {
+                    consumed.set(consumed.get() + 1);
+                    if *value == 1 {
+                        client.cancel_query::<u32, u32>(0);
+                    }
+                })
+            }
+        });
+        assert!(query::execute_fallible_query(query.clone(), fetcher)
+            .now_or_never()
+            .is_some());
+
+        assert_eq!(1, consumed.get());
+        assert!(query.with_state(|state| state.data().is_none()));
+        assert!(!query.is_fetching());
+    }
+
+    #[test]
+    fn empty_streaming_query_fails() {
+        use crate::{provide_query_client, query, use_query_client, EmptyStream};
+        use leptos::*;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let empty = || super::consume_stream(|_: u32| futures::stream::empty());
+        assert!(query::execute_fallible_query(query.clone(), empty())
+            .now_or_never()
+            .is_some());
+        let error = query.with_state(|state| state.error().cloned()).unwrap();
+        assert!(error.downcast::<EmptyStream>().is_some());
+
+        // The previous data is kept.
+        client.set_query_data::<u32, u32>(0, 1);
+        assert!(query::execute_fallible_query(query.clone(), empty())
+            .now_or_never()
+            .is_some());
+        assert!(query.with_state(|state| state.error().is_some() && state.data() == Some(&1)));
+        assert!(!query.is_fetching());
+    }
+
+    #[test]
+    fn typed_error() {
+        use crate::{provide_query_client, use_query_client, QueryError, QueryOptions};
+        use leptos::*;
+
+        #[derive(Debug, PartialEq)]
+        struct NotFound;
+
+        impl std::fmt::Display for NotFound {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("Not found")
+            }
+        }
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let result = super::use_query_result(
+            || 0,
+            |_: u32| async { Err::<u32, _>(NotFound) },
+            QueryOptions::default(),
+        );
+        let not_found = result.error_as::<NotFound>();
+        let other = result.error_as::<String>();
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let fetch = crate::query::execute_fallible_query(query, |_| async {
+            Err::<u32, _>(QueryError::new(NotFound))
+        });
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(Some(&NotFound), not_found.get_untracked().as_deref());
+        assert_eq!(None, other.get_untracked());
+    }
+
+    #[test]
+    fn selected_result_has_no_query() {
+        use crate::{provide_query_client, use_query_client, QueryOptions};
+        use leptos::*;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        client.set_query_data::<u32, (u32, String)>(0, (1, "one".into()));
+
+        let result = super::use_query_with_select(
+            || 0_u32,
+            |_| async { (1_u32, "one".to_string()) },
+            QueryOptions::default(),
+            |value| value.1.clone(),
+        );
+        assert_eq!(Some("one".to_string()), result.data.get_untracked());
+        assert!(result.query::<u32>().is_none());
+    }
+
+    #[test]
+    fn placeholder_data() {
+        use crate::{provide_query_client, use_query, use_query_client, QueryOptions, QueryState};
+        use leptos::*;
+        use std::rc::Rc;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let result = use_query(
+            || 0,
+            |_: u32| async { "fetched".to_string() },
+            QueryOptions {
+                placeholder_data: Some(Rc::new(|| "placeholder".to_string())),
+                ..QueryOptions::default()
+            },
+        );
+        // The server suspends instead.
+        let placeholder = (!cfg!(feature = "ssr")).then(|| "placeholder".to_string());
+
+        assert_eq!(placeholder, result.data.get_untracked());
+        // Enabled without the option.
+        assert!(result.is_enabled.get_untracked());
+        assert_eq!(Some(0), result.query::<u32>().map(|query| *query.get_key()));
+        assert!(result.query::<u64>().is_none());
+        // Never written into the cache.
+        assert_eq!(None, client.get_query_data::<u32, String>(&0));
+
+        // Still loading while the placeholder is shown.
+        let query = client.cache.get_or_create_query::<u32, String>(0);
+        query.set_state(QueryState::Loading);
+        assert_eq!(placeholder, result.data.get_untracked());
+        assert!(result.is_loading.get_untracked());
+
+        // Dropped once the fetched data arrives.
+        let fetch = crate::query::execute_query(query, |_| async { "fetched".to_string() });
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(Some("fetched".to_string()), result.data.get_untracked());
+        assert!(!result.is_loading.get_untracked());
+    }
+
+    #[test]
+    fn resource_option_is_chosen_at_setup() {
+        use crate::{provide_query_client, use_query_client, QueryOptions, ResourceOption};
+        use leptos::*;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        // Whether loading the resource of the query blocks the suspense that read it.
+        let blocks = |key: u32, resource_option: MaybeSignal<ResourceOption>| {
+            let suspense = SuspenseContext::new();
+            provide_context(suspense);
+            let result = super::use_query_with_resource_option(
+                move || key,
+                |_| async { 1_u32 },
+                QueryOptions::default().set_enabled(Some(Signal::derive(|| false))),
+                resource_option,
+            );
+            result.data.get();
+            client.set_query_data::<u32, u32>(key, 2);
+            assert_eq!(Some(2), result.data.get_untracked());
+            suspense.should_block()
+        };
+
+        assert!(blocks(0, ResourceOption::Blocking.into()));
+        assert!(!blocks(1, ResourceOption::NonBlocking.into()));
+
+        // Later changes are ignored.
+        let resource_option = RwSignal::new(ResourceOption::NonBlocking);
+        assert!(!blocks(2, resource_option.into()));
+        resource_option.set(ResourceOption::Blocking);
+        client.set_query_data::<u32, u32>(2, 3);
+        assert!(!use_context::<SuspenseContext>()
+            .expect("Suspense context")
+            .should_block());
+    }
+
+    #[test]
+    fn last_fetch_source() {
+        use crate::{provide_query_client, use_query, use_query_client, FetchSource, QueryOptions};
+        use leptos::*;
+        use std::time::Duration;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        client.set_query_data::<u32, u32>(0, 1);
+
+        let result = use_query(
+            || 0,
+            |_: u32| async { 2_u32 },
+            QueryOptions::default().set_stale_time(Some(Duration::from_secs(60))),
+        );
+        assert_eq!(
+            Some(FetchSource::Manual),
+            result.last_fetch_source.get_untracked()
+        );
+
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let fetch = crate::query::execute_query(query, |_| async { 2 });
+        assert!(fetch.now_or_never().is_some());
+        assert_eq!(Some(2), result.data.get_untracked());
+        assert_eq!(
+            Some(FetchSource::Network),
+            result.last_fetch_source.get_untracked()
+        );
+
+        client.set_query_data::<u32, u32>(0, 3);
+        assert_eq!(
+            Some(FetchSource::Manual),
+            result.last_fetch_source.get_untracked()
+        );
+    }
+
+    #[test]
+    fn shared_data_does_not_update_views() {
+        use crate::{provide_query_client, use_query, use_query_client, QueryOptions};
+        use leptos::*;
+        use std::{cell::Cell, rc::Rc};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        client.set_query_data::<u32, u32>(0, 1);
+
+        let result = use_query(
+            || 0,
+            |_: u32| async { 1_u32 },
+            QueryOptions::default().set_structural_sharing(true),
+        );
+        let renders = Rc::new(Cell::new(0));
+        create_isomorphic_effect({
+            let renders = renders.clone();
+            move |_| {
+                result.data.track();
+                renders.set(renders.get() + 1);
+            }
+        });
+        let renders_before = renders.get();
+
+        // Equal data only ends the fetch.
+        let query = client.cache.get_or_create_query::<u32, u32>(0);
+        let refetch = |value: u32| {
+            let fetch = crate::query::execute_query(query.clone(), move |_| async move { value });
+            assert!(fetch.now_or_never().is_some());
+        };
+        refetch(1);
+        assert_eq!(renders_before, renders.get());
+        assert!(!result.is_fetching.get_untracked());
+        assert_eq!(Some(1), result.data.get_untracked());
+
+        refetch(2);
+        assert!(renders.get() > renders_before);
+        assert_eq!(Some(2), result.data.get_untracked());
+    }
+
+    #[test]
+    fn select_is_memoized() {
+        use crate::{provide_query_client, use_query_client, QueryOptions};
+        use leptos::*;
+        use std::{cell::Cell, rc::Rc};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+        client.set_query_data::<u32, (u32, String)>(0, (1, "one".into()));
+
+        let use_select = |select: fn(&(u32, String)) -> u32| {
+            super::use_query_with_select(
+                || 0,
+                |_: u32| async { (1, "one".to_string()) },
+                QueryOptions::default(),
+                select,
+            )
+        };
+        let first = use_select(|value| value.0);
+        let second = use_select(|value| value.1.len() as u32);
+        assert_eq!(Some(1), first.data.get_untracked());
+        assert_eq!(Some(3), second.data.get_untracked());
+
+        let source = RwSignal::new((1, "one".to_string()));
+        let calls = Rc::new(Cell::new(0));
+        let selected = super::select_memo(source.into(), {
+            let calls = calls.clone();
+            move |value: &(u32, String)| {
+                calls.set(calls.get() + 1);
+                value.0
+            }
+        });
+        let notified = Rc::new(Cell::new(0));
+        create_isomorphic_effect({
+            let notified = notified.clone();
+            move |_| {
+                selected.track();
+                notified.set(notified.get() + 1);
+            }
+        });
+        assert_eq!(1, notified.get());
+
+        // Only the unselected part changed.
+        source.update(|value| value.1 = "uno".into());
+        assert_eq!(2, calls.get());
+        assert_eq!(1, notified.get());
+
+        source.update(|value| value.0 = 2);
+        assert_eq!(2, selected.get_untracked());
+        assert_eq!(2, notified.get());
+    }
+
+    #[test]
+    fn disabled_query_is_fetched_once_enabled() {
+        use crate::{provide_query_client, use_query_client, QueryOptions, QueryState};
+        use leptos::*;
+        use std::{cell::Cell, rc::Rc};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let enabled = RwSignal::new(false);
+        let query = create_memo(move |_| client.cache.get_or_create_query::<u32, u32>(0));
+        let state = super::register_observer_handle_cleanup(
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { Ok(1) }
+                }
+            },
+            query,
+            QueryOptions::default().set_enabled(Some(enabled.into())),
+            false,
+        );
+
+        query.get_untracked().execute();
+        assert_eq!(0, fetches.get());
+        assert!(state.with_untracked(|state| matches!(state, QueryState::Created)));
+
+        enabled.set(true);
+        assert_eq!(1, fetches.get());
+        assert_eq!(Some(1), state.with_untracked(|state| state.data().cloned()));
+
+        // Fresh data isn't refetched when enabled again.
+        enabled.set(false);
+        enabled.set(true);
+        assert_eq!(1, fetches.get());
+    }
+
+    #[test]
+    fn ssr_default_seeds_loaded_state() {
+        use crate::{provide_query_client, use_query_client, QueryOptions, QueryState};
+        use leptos::*;
+        use std::{cell::Cell, rc::Rc, time::Duration};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let result = super::use_query(
+            || 0_u32,
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.set(fetches.get() + 1);
+                    async { 10_u32 }
+                }
+            },
+            QueryOptions::default()
+                .set_ssr_default(Some(1))
+                .set_stale_time(Some(Duration::from_secs(60))),
+        );
+
+        assert_eq!(Some(1), result.data.get_untracked());
+        assert!(matches!(
+            client.get_query_state::<u32, u32>(|| 0).get_untracked(),
+            Some(QueryState::Loaded(data)) if data.data == 1
+        ));
+        assert_eq!(0, fetches.get());
+    }
+
+    #[test]
+    fn dependent_query_waits_for_source() {
+        use crate::{provide_query_client, use_query_client, QueryOptions};
+        use leptos::*;
+        use std::{cell::Cell, rc::Rc};
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        let fetches = Rc::new(Cell::new(0));
+        let source = RwSignal::new(None::<u32>);
+        let result = super::use_dependent_query(
+            source.into(),
+            |id| id * 10,
+            {
+                let fetches = fetches.clone();
+                move |key: u32| {
+                    fetches.set(fetches.get() + 1);
+                    async move { key + 1 }
+                }
+            },
+            QueryOptions::default(),
+        );
+
+        // Neither fetched nor loading without a source.
+        assert_eq!(None, result.data.get_untracked());
+        assert!(!result.is_loading.get_untracked());
+        assert!(!result.is_enabled.get_untracked());
+        assert_eq!(0, fetches.get());
+        assert_eq!(0, client.size().get_untracked());
+
+        // The key is derived from the source, and shares the cache entry of that key.
+        client.set_query_data::<u32, u32>(10, 11);
+        source.set(Some(1));
+        assert_eq!(Some(11), result.data.get_untracked());
+        assert!(result.is_enabled.get_untracked());
+        assert_eq!(0, fetches.get());
+
+        // Invalidated by its key, like any other query. Suppressed, so the refetch doesn't run here.
+        assert!(crate::with_query_suppression(
+            || client.invalidate_query::<u32, u32>(10)
+        ));
+        assert!(result.is_invalid.get_untracked());
+        assert!(result.query::<u32>().is_some());
+
+        // Disabled without a source, keeping the last key.
+        source.set(None);
+        assert!(!result.is_enabled.get_untracked());
+        client.set_query_data::<u32, u32>(10, 12);
+        assert_eq!(Some(12), result.data.get_untracked());
+        assert_eq!(0, fetches.get());
+        assert_eq!(1, client.size().get_untracked());
+    }
+
+    #[test]
+    fn previous_data_is_shown_while_new_key_loads() {
+        use crate::{provide_query_client, use_query_client, QueryData, QueryState};
+        use leptos::*;
+
+        let _ = create_runtime();
+        provide_query_client();
+        let client = use_query_client();
+
+        client.set_query_data::<u32, u32>(0, 10);
+        let key = RwSignal::new(0_u32);
+        let query = client
+            .cache
+            .get_query_signal::<u32, u32>(move || key.get(), || None);
+        let state = RwSignal::new(query.get_untracked().get_state());
+        let (previous_data, is_previous_data) = super::use_previous_data(query, state.into());
+        assert!(!is_previous_data.get_untracked());
+
+        key.set(1);
+        state.set(QueryState::Loading);
+        assert!(is_previous_data.get_untracked());
+        assert_eq!(Some(10), previous_data.get_untracked());
+
+        state.set(QueryState::Loaded(QueryData::now(11)));
+        assert!(!is_previous_data.get_untracked());
+
+        // A key without data keeps the data of the last key that had some.
+        key.set(2);
+        state.set(QueryState::Created);
+        key.set(3);
+        assert_eq!(Some(10), previous_data.get_untracked());
+    }
+}
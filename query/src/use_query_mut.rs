@@ -0,0 +1,56 @@
+use std::rc::Rc;
+
+use leptos::*;
+
+use crate::{use_query_client, QueryKey, QueryValue};
+
+/// Returns the cached data of a query, and a setter that writes straight into the cache.
+///
+/// Useful for local first editing of cached entities, e.g. binding a form to a query.
+/// The write is optimistic: it is immediately visible to every observer of the query, like [`QueryClient::set_query_data()`](crate::QueryClient::set_query_data).
+/// Syncing the change to the server, and rolling back if that fails, is left to a separate mutation.
+///
+/// The data is [`None`](Option::None) until the query has data. The setter creates the query if it doesn't exist yet.
+/// Nothing is fetched, use [`use_query()`](crate::use_query()) to load the data.
+///
+/// Example:
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[component]
+/// fn TodoTitle(id: u32) -> impl IntoView {
+///     let (title, set_title) = use_query_mut::<u32, String>(move || id);
+///
+///     view! {
+///         <input
+///             prop:value=move || title.get().unwrap_or_default()
+///             on:input=move |ev| set_title(event_target_value(&ev))
+///         />
+///     }
+/// }
+/// ```
+pub fn use_query_mut<K, V>(
+    key: impl Fn() -> K + 'static,
+) -> (Signal<Option<V>>, impl Fn(V) + Clone + 'static)
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    let client = use_query_client();
+    let key = Rc::new(key);
+
+    let state = client.get_query_state::<K, V>({
+        let key = key.clone();
+        move || key()
+    });
+    let data = Signal::derive(move || {
+        state.with(|state| state.as_ref().and_then(|state| state.data().cloned()))
+    });
+
+    let set_data = move |data: V| {
+        client.set_query_data::<K, V>(key(), data);
+    };
+
+    (data, set_data)
+}
@@ -0,0 +1,204 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use leptos::*;
+
+use crate::{
+    query::Query,
+    query_observer::{ListenerKey, QueryObserver},
+    use_query_client, QueryKey, QueryOptions, QueryState, QueryValue,
+};
+
+/// Observes the state of several existing queries, without fetching them.
+///
+/// Useful for dashboards that display data fetched elsewhere, e.g. by [`use_query()`](crate::use_query()).
+/// The observers keep the queries from being garbage collected, and are removed together when the scope is cleaned up.
+/// As the key list changes, observers of keys that are no longer listed are removed.
+///
+/// Returns the states in the order of `keys`. Keys without a query in the cache are skipped until the query is created.
+/// A key listed more than once is observed once, and its state is returned for each occurrence.
+///
+/// A query that no [`use_query()`](crate::use_query()) fetches stays [`Created`](QueryState::Created).
+/// Once it has been created for longer than [`QueryClientConfig::fetcher_grace_period`](crate::QueryClientConfig::fetcher_grace_period),
+/// it is skipped like a missing query until it is loaded, rather than appearing to load forever.
+///
+/// Example:
+/// ```
+/// use leptos::*;
+/// use leptos_query::*;
+///
+/// #[component]
+/// fn Dashboard(ids: Vec<u32>) -> impl IntoView {
+///     let states = use_query_states::<u32, String>(move || ids.clone());
+///
+///     view! {
+///         <ul>
+///             {move || {
+///                 states
+///                     .get()
+///                     .into_iter()
+///                     .map(|(id, state)| view! { <li>{id} ": " {state.data().cloned()}</li> })
+///                     .collect_view()
+///             }}
+///         </ul>
+///     }
+/// }
+/// ```
+pub fn use_query_states<K, V>(
+    keys: impl Fn() -> Vec<K> + 'static,
+) -> Signal<Vec<(K, QueryState<V>)>>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    let client = use_query_client();
+    let cache = client.cache.clone();
+    let size = client.size();
+    let grace_period = client.config.fetcher_grace_period;
+
+    let queries = create_memo(move |_| {
+        // Subscribe to inserts/deletions.
+        size.track();
+        keys()
+            .into_iter()
+            .filter_map(|key| {
+                let query = cache.get_query::<K, V, _>(&key)?;
+                Some((key, query))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let states = RwSignal::new(Vec::<(K, QueryState<V>)>::new());
+    // Keys whose query was created past the grace period without a fetcher.
+    let unfetched = RwSignal::new(HashSet::<K>::new());
+    let observers = Rc::new(RefCell::new(ObserverSet::default()));
+
+    create_isomorphic_effect({
+        let observers = observers.clone();
+        move |_| {
+            let queries = queries.get();
+            let mut observers = observers.borrow_mut();
+            let mut previous = std::mem::take(&mut observers.0);
+
+            for (key, query) in queries.iter() {
+                // Already observed for an earlier occurrence of the key.
+                if observers.0.contains_key(key) {
+                    continue;
+                }
+                let entry = match previous.remove(key) {
+                    Some(entry) => {
+                        entry.0.update_query(Some(query.clone()));
+                        entry
+                    }
+                    None => {
+                        let observer =
+                            QueryObserver::no_fetcher(QueryOptions::default(), Some(query.clone()));
+                        let listener = observer.add_listener({
+                            let key = key.clone();
+                            move |state| {
+                                states.update(|states| {
+                                    for (_, current) in states.iter_mut().filter(|(k, _)| *k == key)
+                                    {
+                                        *current = state.clone();
+                                    }
+                                })
+                            }
+                        });
+                        if let Some(grace_period) = grace_period {
+                            skip_unfetched_after(
+                                grace_period,
+                                key.clone(),
+                                query.clone(),
+                                unfetched,
+                            );
+                        }
+                        (observer, listener)
+                    }
+                };
+                observers.0.insert(key.clone(), entry);
+            }
+
+            // Keys that are no longer listed.
+            ObserverSet(previous).clear();
+
+            states.set(
+                queries
+                    .into_iter()
+                    .map(|(key, query)| (key, query.get_state()))
+                    .collect(),
+            );
+        }
+    });
+
+    on_cleanup(move || observers.borrow_mut().clear());
+
+    Signal::derive(move || {
+        unfetched.with(|unfetched| {
+            states.with(|states| {
+                states
+                    .iter()
+                    .filter(|(key, state)| {
+                        !(matches!(state, QueryState::Created) && unfetched.contains(key))
+                    })
+                    .cloned()
+                    .collect()
+            })
+        })
+    })
+}
+
+// Marks the key as unfetched if the query is still created after the grace period, and nothing can fetch it.
+fn skip_unfetched_after<K, V>(
+    grace_period: std::time::Duration,
+    key: K,
+    query: Query<K, V>,
+    unfetched: RwSignal<HashSet<K>>,
+) where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    if !query.with_state(|state| matches!(state, QueryState::Created)) {
+        return;
+    }
+    spawn_local(async move {
+        crate::use_query::sleep(grace_period).await;
+        if !query.with_state(|state| matches!(state, QueryState::Created)) || query.has_fetcher() {
+            return;
+        }
+        // The scope was cleaned up in the meantime.
+        if unfetched
+            .try_update(|unfetched| unfetched.insert(key.clone()))
+            .is_some()
+        {
+            logging::debug_warn!(
+                "use_query_states: Query {:?} was not fetched within {:?}. Is an enabled use_query for this key mounted?",
+                key,
+                grace_period
+            );
+        }
+    });
+}
+
+struct ObserverSet<K, V>(HashMap<K, (QueryObserver<K, V>, ListenerKey)>);
+
+impl<K, V> Default for ObserverSet<K, V> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<K, V> ObserverSet<K, V>
+where
+    K: QueryKey + 'static,
+    V: QueryValue + 'static,
+{
+    fn clear(&mut self) {
+        for (_, (observer, listener)) in self.0.drain() {
+            observer.remove_listener(listener);
+            observer.cleanup();
+        }
+    }
+}